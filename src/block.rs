@@ -406,7 +406,7 @@ impl Block {
             if key.is(name) && found_date.is_none() {
                 found = Some(bv);
             } else if let Ok(isdate) = Date::try_from(key) {
-                if isdate <= date && (found_date.is_none() || found_date.unwrap() < isdate) {
+                if isdate <= date && found_date.map_or(true, |found_date| found_date < isdate) {
                     if let Some(value) = bv.get_block().and_then(|b| b.get_field(name)) {
                         found_date = Some(isdate);
                         found = Some(value);
@@ -494,6 +494,7 @@ impl Block {
     /// is used as the separator because it can't show up in normal parsing).
     ///
     /// This function is used as a last resort when validating awkward syntax.
+    #[must_use]
     pub fn condense_tag(self, tag: &str) -> Self {
         let mut other = Block::new(self.loc);
         let mut reserve: Option<(Token, Comparator, Token)> = None;
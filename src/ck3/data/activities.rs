@@ -647,7 +647,7 @@ impl DbKind for ActivityLocale {
         vd.field_validated_block("is_available", |block, data| {
             validate_trigger(block, data, &mut sc, Tooltipped::No);
         });
-        vd.field_script_value("chance", &mut sc);
+        vd.field_script_value_in_range("chance", &mut sc, 0.0, 100.0);
         vd.field_validated_block("on_enter_locale", |block, data| {
             validate_effect(block, data, &mut sc, Tooltipped::No);
         });
@@ -121,6 +121,15 @@ impl Characters {
         }
     }
 
+    pub fn get(&self, key: &str) -> Option<&Character> {
+        self.characters.get(key)
+    }
+
+    /// Iterate over all characters except the ones with duplicate ids.
+    pub fn iter(&self) -> impl Iterator<Item = &Character> {
+        self.characters.values()
+    }
+
     pub fn verify_alive(&self, item: &Token, date: Date) {
         if !self.is_alive(item, date) {
             let msg = format!("{item} is not alive on {date}");
@@ -268,6 +277,11 @@ impl FileHandler<Block> for Characters {
     }
 }
 
+/// The youngest age at which a character can plausibly become a parent.
+const MIN_PARENT_AGE: i16 = 12;
+/// How long a father can have been dead when his child is born.
+const GESTATION_MONTHS: i16 = 9;
+
 #[atomic_enum]
 enum AncestorState {
     Unchecked,
@@ -310,6 +324,111 @@ impl Character {
         self.block.get_field_value_at_date("dynasty", date)
     }
 
+    pub fn key(&self) -> &Token {
+        &self.key
+    }
+
+    pub fn name(&self) -> Option<&Token> {
+        self.block.get_field_value("name")
+    }
+
+    pub fn father(&self) -> Option<&Token> {
+        self.block.get_field_value("father")
+    }
+
+    pub fn mother(&self) -> Option<&Token> {
+        self.block.get_field_value("mother")
+    }
+
+    /// Return the date of the history entry that has the `birth` field.
+    pub fn birth_date(&self) -> Option<Date> {
+        self.first_history_date_with("birth")
+    }
+
+    /// Return the date of the history entry that has the `death` field.
+    pub fn death_date(&self) -> Option<Date> {
+        self.first_history_date_with("death")
+    }
+
+    fn first_history_date_with(&self, field: &str) -> Option<Date> {
+        self.block
+            .iter_definitions()
+            .filter(|(_, block)| block.has_key(field))
+            .filter_map(|(key, _)| Date::try_from(key).ok())
+            .min()
+    }
+
+    /// Return the history entries up to and including `date`, in date order.
+    fn history_until(&self, date: Date) -> Vec<(Date, &Block)> {
+        let mut vec: Vec<(Date, &Block)> = self
+            .block
+            .iter_definitions()
+            .filter_map(|(key, block)| Date::try_from(key).ok().map(|d| (d, block)))
+            .filter(|(d, _)| *d <= date)
+            .collect();
+        // stable sort, to keep entries with the same date in file order
+        vec.sort_by_key(|(d, _)| *d);
+        vec
+    }
+
+    /// Return the spouses this character has at `date`, according to their own history entries.
+    /// Marriages recorded in the spouses' histories are not included.
+    pub fn spouses_at(&self, date: Date) -> Vec<&Token> {
+        let mut spouses: Vec<&Token> = Vec::new();
+        for (_, block) in self.history_until(date) {
+            for (key, value) in block.iter_assignments() {
+                match key.as_str() {
+                    "add_spouse" | "add_matrilineal_spouse" | "add_same_sex_spouse" => {
+                        spouses.push(value);
+                    }
+                    "remove_spouse" => spouses.retain(|spouse| spouse.as_str() != value.as_str()),
+                    _ => (),
+                }
+            }
+        }
+        spouses
+    }
+
+    /// Return the date at which this character's own history last removed `other` as a spouse,
+    /// if that happened on or before `date`.
+    pub fn spouse_removed_at(&self, other: &str, date: Date) -> Option<Date> {
+        let mut removed = None;
+        for (d, block) in self.history_until(date) {
+            for (key, value) in block.iter_assignments() {
+                if value.is(other) {
+                    if key.is("remove_spouse") {
+                        removed = Some(d);
+                    } else if key.as_str().starts_with("add_") && key.as_str().ends_with("spouse") {
+                        removed = None;
+                    }
+                }
+            }
+        }
+        removed
+    }
+
+    /// Return the date at which this character's own history last married `other`,
+    /// if that happened on or before `date`.
+    pub fn spouse_added_at(&self, other: &str, date: Date) -> Option<Date> {
+        let mut added = None;
+        for (d, block) in self.history_until(date) {
+            for (key, value) in block.iter_assignments() {
+                if value.is(other)
+                    && key.as_str().starts_with("add_")
+                    && key.as_str().ends_with("spouse")
+                {
+                    added = Some(d);
+                }
+            }
+        }
+        added
+    }
+
+    /// Return the character's chosen realm capital at `date`, if any.
+    pub fn capital_at(&self, date: Date) -> Option<&Token> {
+        self.block.get_field_value_at_date("capital", date)
+    }
+
     pub fn get_house(&self, date: Date) -> Option<&Token> {
         self.block.get_field_value_at_date("dynasty_house", date)
     }
@@ -624,6 +743,47 @@ impl Character {
 
         life_events.sort_unstable();
         Self::validate_life(&self.key, life_events);
+
+        self.check_parents(data);
+    }
+
+    /// Check that the parents' lives fit around this character's birth.
+    fn check_parents(&self, data: &Everything) {
+        let Some(birth) = self.birth_date() else {
+            return;
+        };
+        for (field, gender) in [("father", Gender::Male), ("mother", Gender::Female)] {
+            let Some(token) = self.block.get_field_value(field) else {
+                continue;
+            };
+            let Some(parent) = data.characters.get(token.as_str()) else {
+                continue;
+            };
+            if let Some(parent_birth) = parent.birth_date() {
+                let age = birth.years_since(parent_birth);
+                if parent_birth > birth {
+                    let msg = format!("{field} {token} was born after this character");
+                    err(ErrorKey::History).msg(msg).loc(token).push();
+                } else if age < MIN_PARENT_AGE {
+                    let msg =
+                        format!("{field} {token} was only {age} when this character was born");
+                    warn(ErrorKey::History).msg(msg).loc(token).push();
+                }
+            }
+            if let Some(parent_death) = parent.death_date() {
+                // A father may die while the mother is pregnant.
+                let limit = match gender {
+                    Gender::Male => parent_death.add_months(GESTATION_MONTHS),
+                    Gender::Female => parent_death,
+                };
+                if birth > limit {
+                    let msg = format!(
+                        "this character was born on {birth}, after {field} {token} died on {parent_death}"
+                    );
+                    err(ErrorKey::History).msg(msg).loc(token).push();
+                }
+            }
+        }
     }
 
     fn check_pod_flags(&self, _data: &Everything) {
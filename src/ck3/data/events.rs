@@ -225,6 +225,10 @@ impl Event {
         Self { key, block, expects_scope }
     }
 
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+
     pub fn validate(&self, data: &Everything) {
         let mut vd = Validator::new(&self.block, data);
 
@@ -265,6 +269,7 @@ impl Event {
             }
         }
         sc.set_strict_scopes(false);
+        sc.set_origin(Item::Event, &self.key);
 
         // "dlc or mod this event comes from"
         vd.field_item("content_source", Item::Localization);
@@ -367,6 +372,8 @@ impl Event {
         // TODO: validate widget
         vd.field("widget");
         vd.field_block("widgets");
+
+        data.event_chains.record_inputs(Item::Event, &self.key, &self.block, &sc);
     }
 }
 
@@ -41,6 +41,15 @@ impl ProvinceHistories {
         }
     }
 
+    /// Return the value the province history gives to `field` at the given date.
+    pub fn get_value_at_date(&self, provid: ProvId, field: &str, date: Date) -> Option<&Token> {
+        self.provinces.get(&provid).and_then(|item| item.block.get_field_value_at_date(field, date))
+    }
+
+    pub fn key(&self, provid: ProvId) -> Option<&Token> {
+        self.provinces.get(&provid).map(|item| &item.key)
+    }
+
     pub fn check_pod_faiths(&self, data: &Everything, titles: &Titles) {
         for bookmark in [
             Date::new(1230, 1, 4),
@@ -136,8 +145,6 @@ impl ProvinceHistory {
 
     fn validate(&self, provid: ProvId, data: &Everything) {
         data.provinces_ck3.verify_exists_provid(provid, &self.key, Severity::Error);
-        // TODO: verify that all county-capital provinces have a culture and religion
-        // This needs province mappings to be loaded too
         let mut vd = Validator::new(&self.block, data);
         Self::validate_common(&mut vd, data);
         vd.field_value("terrain"); // TODO: this does not seem to be an Item::Terrain
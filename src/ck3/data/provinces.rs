@@ -1,9 +1,10 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use anyhow::{bail, Result};
 use bitvec::bitbox;
 use bitvec::boxed::BitBox;
-use image::{DynamicImage, Rgb};
+use image::{DynamicImage, Rgb, RgbImage};
 
 use crate::block::Block;
 use crate::db::{Db, DbKind};
@@ -219,6 +220,149 @@ impl Ck3Provinces {
             item.validate(self, data);
         }
     }
+
+    /// Read the `provinces.png` again, for the map analyses that need to know where each pixel is.
+    /// It is not kept in memory after loading because it's large and most runs don't need it.
+    fn read_provinces_png(data: &Everything) -> Result<RgbImage> {
+        let Some(entry) = data.fileset.get_files_under(Path::new("map_data/provinces.png")).first()
+        else {
+            bail!("map_data/provinces.png not found");
+        };
+        Ok(image::open(entry.fullpath())?.to_rgb8())
+    }
+
+    /// Compute which provinces border each other, from the pixels in `provinces.png` and the
+    /// crossings in `adjacencies.csv`. Each pair is stored with the lower province id first.
+    pub fn compute_adjacency(&self, data: &Everything) -> Result<TigerHashSet<(ProvId, ProvId)>> {
+        let img = Self::read_provinces_png(data)?;
+        let (width, height) = img.dimensions();
+
+        // First collect the bordering colors, then translate them to province ids.
+        // This avoids many lookups in the `provinces` map.
+        let mut color_pairs = TigerHashSet::default();
+        for (x, y, pixel) in img.enumerate_pixels() {
+            let index = ColorBitArray::get_index(*pixel);
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if nx < width && ny < height {
+                    let other = ColorBitArray::get_index(*img.get_pixel(nx, ny));
+                    if other != index {
+                        color_pairs.insert((index.min(other), index.max(other)));
+                    }
+                }
+            }
+        }
+
+        let ids: TigerHashMap<usize, ProvId> = self
+            .provinces
+            .values()
+            .map(|province| (ColorBitArray::get_index(province.color), province.id))
+            .collect();
+        let mut adjacent = TigerHashSet::default();
+        for (c1, c2) in color_pairs {
+            if let (Some(&p1), Some(&p2)) = (ids.get(&c1), ids.get(&c2)) {
+                adjacent.insert((p1.min(p2), p1.max(p2)));
+            }
+        }
+        for adjacency in &self.adjacencies {
+            let (from, to) = (adjacency.from, adjacency.to);
+            adjacent.insert((from.min(to), from.max(to)));
+        }
+        Ok(adjacent)
+    }
+
+    /// Margin around the frames drawn around problem areas in the debug image, so that areas of
+    /// a few pixels can be found when the image is zoomed out.
+    const MARK_MARGIN: u32 = 6;
+
+    /// Write an overview image of `provinces.png` in which every province is colored according to
+    /// the kind of problems it has. Province borders are drawn in black, and the areas that the
+    /// color checks flag are framed in magenta.
+    pub fn write_debug_png(&self, data: &Everything, path: &Path) -> Result<()> {
+        let img = Self::read_provinces_png(data)?;
+
+        let mut classes = TigerHashMap::default();
+        for province in self.provinces.values() {
+            let class = if classes.contains_key(&province.color) {
+                ProvinceClass::DuplicateColor
+            } else if self.sea_or_river.contains(&province.id) {
+                ProvinceClass::SeaOrRiver
+            } else if self.impassable.contains(&province.id) {
+                ProvinceClass::Impassable
+            } else {
+                ProvinceClass::Land
+            };
+            classes.insert(province.color, class);
+        }
+
+        let (width, height) = img.dimensions();
+        let mut out = RgbImage::new(width, height);
+        // The bounding box of each problem color, as (min x, min y, max x, max y).
+        let mut problems: TigerHashMap<Rgb<u8>, (u32, u32, u32, u32)> = TigerHashMap::default();
+        for (x, y, pixel) in img.enumerate_pixels() {
+            let border = (x + 1 < width && img.get_pixel(x + 1, y) != pixel)
+                || (y + 1 < height && img.get_pixel(x, y + 1) != pixel);
+            let class = classes.get(pixel).copied().unwrap_or(ProvinceClass::Undefined);
+            if matches!(class, ProvinceClass::DuplicateColor | ProvinceClass::Undefined) {
+                let bbox = problems.entry(*pixel).or_insert((x, y, x, y));
+                *bbox = (bbox.0.min(x), bbox.1.min(y), bbox.2.max(x), bbox.3.max(y));
+            }
+            // Problem colors are drawn over the borders, so that tiny problem areas stay visible.
+            let color = if border
+                && matches!(
+                    class,
+                    ProvinceClass::Land | ProvinceClass::SeaOrRiver | ProvinceClass::Impassable
+                ) {
+                Rgb([0, 0, 0])
+            } else {
+                class.color()
+            };
+            out.put_pixel(x, y, color);
+        }
+
+        let m = Self::MARK_MARGIN;
+        for (x1, y1, x2, y2) in problems.into_values() {
+            let (x1, y1) = (x1.saturating_sub(m), y1.saturating_sub(m));
+            let (x2, y2) = ((x2 + m).min(width - 1), (y2 + m).min(height - 1));
+            for x in x1..=x2 {
+                out.put_pixel(x, y1, Rgb([255, 0, 255]));
+                out.put_pixel(x, y2, Rgb([255, 0, 255]));
+            }
+            for y in y1..=y2 {
+                out.put_pixel(x1, y, Rgb([255, 0, 255]));
+                out.put_pixel(x2, y, Rgb([255, 0, 255]));
+            }
+        }
+
+        out.save(path)?;
+        Ok(())
+    }
+}
+
+/// The categories that provinces are colored by in the debug image of `provinces.png`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProvinceClass {
+    /// A normal land province without problems.
+    Land,
+    /// Sea zones and river provinces.
+    SeaOrRiver,
+    /// Other impassable provinces, such as mountains and lakes.
+    Impassable,
+    /// The color is used by more than one province in `definition.csv`.
+    DuplicateColor,
+    /// The color has no entry in `definition.csv`.
+    Undefined,
+}
+
+impl ProvinceClass {
+    fn color(self) -> Rgb<u8> {
+        match self {
+            ProvinceClass::Land => Rgb([200, 200, 190]),
+            ProvinceClass::SeaOrRiver => Rgb([90, 120, 200]),
+            ProvinceClass::Impassable => Rgb([110, 110, 110]),
+            ProvinceClass::DuplicateColor => Rgb([255, 150, 0]),
+            ProvinceClass::Undefined => Rgb([255, 0, 0]),
+        }
+    }
 }
 
 #[derive(Debug)]
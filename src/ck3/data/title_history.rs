@@ -52,6 +52,21 @@ impl TitleHistories {
         }
     }
 
+    pub fn iter_keys_and_tiers(&self) -> impl Iterator<Item = (&Token, Tier)> {
+        self.histories.values().map(|item| (&item.key, item.tier))
+    }
+
+    /// Return the value the title history gives to `field` at the given date.
+    pub fn get_value_at_date(&self, key: &str, field: &str, date: Date) -> Option<&Token> {
+        self.histories.get(key).and_then(|item| item.block.get_field_value_at_date(field, date))
+    }
+
+    /// Return true iff the title history gives the title a holder at the given date.
+    /// Unlike [`TitleHistories::verify_has_holder`], this does not check that the holder is alive.
+    pub fn has_holder(&self, key: &str, date: Date) -> bool {
+        self.get_value_at_date(key, "holder", date).is_some_and(|holder| !holder.is("0"))
+    }
+
     pub fn verify_has_holder(&self, key: &Token, date: Date, data: &Everything) {
         if let Some(item) = self.histories.get(key.as_str()) {
             item.verify_has_holder(key, date, data);
@@ -1,16 +1,21 @@
-use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::fmt::{Display, Formatter, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use crate::block::Block;
+use anyhow::Result;
+use serde_json::{json, to_string_pretty, Value};
+
+use crate::block::{Block, BV};
 use crate::ck3::data::provinces::ProvId;
 use crate::context::ScopeContext;
+use crate::date::Date;
 use crate::everything::Everything;
 use crate::fileset::{FileEntry, FileHandler};
-use crate::helpers::{dup_error, TigerHashMap};
+use crate::helpers::{color_to_rgb, dup_error, TigerHashMap, TigerHashSet};
 use crate::item::Item;
 use crate::pdxfile::PdxFile;
-use crate::report::{err, warn, ErrorKey};
+use crate::report::{err, tips, warn, ErrorKey};
 use crate::scopes::Scopes;
 use crate::token::Token;
 use crate::tooltipped::Tooltipped;
@@ -141,6 +146,166 @@ impl Titles {
     pub fn capital_of(&self, prov: ProvId) -> Option<&str> {
         self.baronies.get(&prov).and_then(|b| b.capital_of())
     }
+
+    /// Return the provinces of the baronies that are the capitals of their counties.
+    pub fn iter_county_capital_provinces(&self) -> impl Iterator<Item = (ProvId, &Token)> {
+        self.baronies
+            .iter()
+            .filter(|(_, b)| b.is_county_capital)
+            .map(|(provid, b)| (*provid, &b.key))
+    }
+
+    /// Return the de jure children of each title, sorted by key.
+    fn children(&self) -> TigerHashMap<&'static str, Vec<&Title>> {
+        let mut children: TigerHashMap<&str, Vec<&Title>> = TigerHashMap::default();
+        for title in self.titles.values() {
+            if let Some(parent) = title.parent {
+                children.entry(parent).or_default().push(title);
+            }
+        }
+        for vec in children.values_mut() {
+            vec.sort_by_key(|title| title.key.as_str());
+        }
+        children
+    }
+
+    /// Return the title of the given tier that `key` is de jure part of, or `key` itself if it is
+    /// of that tier.
+    fn de_jure_at_tier(&self, key: &str, tier: Tier) -> Option<&'static str> {
+        let mut title = self.titles.get(key)?;
+        loop {
+            if title.tier == tier {
+                return Some(title.key.as_str());
+            }
+            title = self.titles.get(title.parent?)?;
+        }
+    }
+
+    /// Write the de jure hierarchy of all titles to `path`.
+    /// If the filename ends in `.csv` it's written as one line per title, otherwise as nested JSON.
+    pub fn export(&self, path: &Path) -> Result<()> {
+        let children = self.children();
+        let mut roots: Vec<&Title> =
+            self.titles.values().filter(|title| title.parent.is_none()).map(Arc::as_ref).collect();
+        roots.sort_by_key(|title| title.key.as_str());
+
+        let output = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv")) {
+            let mut output =
+                "key;tier;parent;capital;province;empire;kingdom;duchy;county\n".to_string();
+            let mut titles: Vec<&Title> = self.titles.values().map(Arc::as_ref).collect();
+            titles.sort_by_key(|title| (std::cmp::Reverse(title.tier), title.key.as_str()));
+            for title in titles {
+                let key = title.key.as_str();
+                let _ = write!(
+                    output,
+                    "{key};{};{};{};{}",
+                    title.tier,
+                    title.parent.unwrap_or(""),
+                    title.block.get_field_value("capital").map_or("", Token::as_str),
+                    title.block.get_field_value("province").map_or("", Token::as_str),
+                );
+                for tier in [Tier::Empire, Tier::Kingdom, Tier::Duchy, Tier::County] {
+                    let _ = write!(output, ";{}", self.de_jure_at_tier(key, tier).unwrap_or(""));
+                }
+                output.push('\n');
+            }
+            output
+        } else {
+            let json = Value::Array(roots.iter().map(|title| title.to_json(&children)).collect());
+            to_string_pretty(&json)?
+        };
+        fs::write(path, output)?;
+        Ok(())
+    }
+
+    /// Do extra checks on the de jure hierarchy that are useful when maintaining a large map.
+    /// They are not part of the normal validation because they are slow and some of their
+    /// warnings describe choices that are valid in the game.
+    ///
+    /// `adjacent` is the set of bordering province pairs, with the lowest id first.
+    pub fn audit(&self, data: &Everything, adjacent: &TigerHashSet<(ProvId, ProvId)>) {
+        let children = self.children();
+
+        let mut bookmark_dates: Vec<Date> = data
+            .database
+            .iter_key_block(Item::Bookmark)
+            .filter_map(|(_, block)| block.get_field_date("start_date"))
+            .collect();
+        bookmark_dates.sort();
+        bookmark_dates.dedup();
+
+        for title in self.titles.values() {
+            title.audit_capital(self, &children);
+            if title.tier == Tier::County {
+                title.audit_county_contiguous(&children, adjacent);
+            }
+            if title.tier > Tier::Barony
+                && !bookmark_dates.is_empty()
+                && !bookmark_dates
+                    .iter()
+                    .any(|&date| data.title_history.has_holder(title.key.as_str(), date))
+            {
+                let msg = format!("{} has no holder at any bookmark", title.key);
+                let info =
+                    "this may be intended for titles that can only be created during the game";
+                tips(ErrorKey::History).msg(msg).info(info).loc(&title.key).push();
+            }
+        }
+
+        self.audit_neighbor_colors(data, adjacent);
+    }
+
+    /// Warn about titles whose map color is hard to tell apart from that of a neighboring title
+    /// of the same tier.
+    fn audit_neighbor_colors(&self, data: &Everything, adjacent: &TigerHashSet<(ProvId, ProvId)>) {
+        // The distance below which two colors look the same on the map, in the "redmean"
+        // approximation of perceived color difference. The maximum distance is about 765.
+        const MIN_DISTANCE: f64 = 24.0;
+
+        let mut neighbors = TigerHashSet::default();
+        for &(p1, p2) in adjacent {
+            let (Some(b1), Some(b2)) = (self.baronies.get(&p1), self.baronies.get(&p2)) else {
+                continue;
+            };
+            for tier in [Tier::County, Tier::Duchy, Tier::Kingdom, Tier::Empire] {
+                let t1 = self.de_jure_at_tier(b1.key.as_str(), tier);
+                let t2 = self.de_jure_at_tier(b2.key.as_str(), tier);
+                if let (Some(t1), Some(t2)) = (t1, t2) {
+                    if t1 != t2 {
+                        neighbors.insert((t1.min(t2), t1.max(t2)));
+                    }
+                }
+            }
+        }
+
+        let mut neighbors: Vec<_> = neighbors.into_iter().collect();
+        neighbors.sort_unstable();
+        for (t1, t2) in neighbors {
+            let title1 = &self.titles[t1];
+            let title2 = &self.titles[t2];
+            if let (Some(c1), Some(c2)) = (title1.color(data), title2.color(data)) {
+                if color_distance(c1, c2) < MIN_DISTANCE {
+                    let msg = format!("{t1} and its neighbor {t2} have nearly the same color");
+                    warn(ErrorKey::Colors)
+                        .weak()
+                        .msg(msg)
+                        .loc(title1.block.get_key("color").unwrap_or(&title1.key))
+                        .loc_msg(title2.block.get_key("color").unwrap_or(&title2.key), "neighbor")
+                        .push();
+                }
+            }
+        }
+    }
+}
+
+/// Approximate the perceived difference between two colors, using the "redmean" formula.
+fn color_distance(c1: [u8; 3], c2: [u8; 3]) -> f64 {
+    let rmean = (f64::from(c1[0]) + f64::from(c2[0])) / 2.0;
+    let dr = f64::from(c1[0]) - f64::from(c2[0]);
+    let dg = f64::from(c1[1]) - f64::from(c2[1]);
+    let db = f64::from(c1[2]) - f64::from(c2[2]);
+    ((2.0 + rmean / 256.0) * dr * dr + 4.0 * dg * dg + (2.0 + (255.0 - rmean) / 256.0) * db * db)
+        .sqrt()
 }
 
 impl FileHandler<Block> for Titles {
@@ -283,4 +448,92 @@ impl Title {
             None
         }
     }
+
+    /// Return the title's map color as RGB, looking up named colors if needed.
+    fn color(&self, data: &Everything) -> Option<[u8; 3]> {
+        match self.block.get_field("color")? {
+            BV::Value(token) => data
+                .get_key_block(Item::NamedColor, token.as_str())
+                .and_then(|(_, b)| color_to_rgb(b)),
+            BV::Block(block) => color_to_rgb(block),
+        }
+    }
+
+    fn to_json(&self, children: &TigerHashMap<&str, Vec<&Title>>) -> Value {
+        let mut json = json!({
+            "key": self.key.as_str(),
+            "tier": self.tier.to_string(),
+        });
+        if let Some(capital) = self.block.get_field_value("capital") {
+            json["capital"] = Value::from(capital.as_str());
+        }
+        if let Some(provid) = self.block.get_field_integer("province") {
+            json["province"] = Value::from(provid);
+        }
+        if let Some(vec) = children.get(self.key.as_str()) {
+            json["children"] =
+                Value::Array(vec.iter().map(|title| title.to_json(children)).collect());
+        }
+        json
+    }
+
+    /// Warn if this title's capital is not de jure part of the title.
+    fn audit_capital(&self, titles: &Titles, children: &TigerHashMap<&str, Vec<&Title>>) {
+        // Titles without de jure territory, such as titular titles, can have any capital.
+        if self.tier <= Tier::County || !children.contains_key(self.key.as_str()) {
+            return;
+        }
+        if let Some(capital) = self.block.get_field_value("capital") {
+            if titles.exists(capital.as_str())
+                && titles.de_jure_at_tier(capital.as_str(), self.tier) != Some(self.key.as_str())
+            {
+                let msg = format!("capital {capital} is not de jure part of {}", self.key);
+                warn(ErrorKey::DeJure).msg(msg).loc(capital).push();
+            }
+        }
+    }
+
+    /// Warn if the baronies of this county do not form one connected area on the map.
+    fn audit_county_contiguous(
+        &self,
+        children: &TigerHashMap<&str, Vec<&Title>>,
+        adjacent: &TigerHashSet<(ProvId, ProvId)>,
+    ) {
+        let Some(baronies) = children.get(self.key.as_str()) else {
+            return;
+        };
+        let provinces: Vec<ProvId> = baronies
+            .iter()
+            .filter_map(|b| b.block.get_field_integer("province"))
+            .filter_map(|provid| ProvId::try_from(provid).ok())
+            .collect();
+        let Some(&first) = provinces.first() else {
+            return;
+        };
+
+        let mut reached = vec![first];
+        let mut todo = vec![first];
+        while let Some(p1) = todo.pop() {
+            for &p2 in &provinces {
+                if !reached.contains(&p2) && adjacent.contains(&(p1.min(p2), p1.max(p2))) {
+                    reached.push(p2);
+                    todo.push(p2);
+                }
+            }
+        }
+
+        if reached.len() < provinces.len() {
+            let msg = format!("the baronies of {} are not all connected to each other", self.key);
+            let info = "this may be intended for counties spread over several islands";
+            let mut report = warn(ErrorKey::DeJure).weak().msg(msg).info(info).loc(&self.key);
+            for barony in baronies {
+                if let Some(provid) = barony.block.get_field_integer("province") {
+                    if ProvId::try_from(provid).is_ok_and(|provid| !reached.contains(&provid)) {
+                        report = report.loc_msg(&barony.key, "not connected");
+                    }
+                }
+            }
+            report.push();
+        }
+    }
 }
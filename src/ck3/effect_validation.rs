@@ -23,6 +23,7 @@ use crate::validate::{
     validate_optional_duration_int, ListType,
 };
 use crate::validator::{Builder, Validator, ValueValidator};
+use crate::variables::VariableKind;
 
 pub fn validate_add_activity_log_entry(
     key: &Token,
@@ -124,7 +125,7 @@ pub fn validate_add_hook(
 pub fn validate_add_opinion(
     _key: &Token,
     _block: &Block,
-    _data: &Everything,
+    data: &Everything,
     sc: &mut ScopeContext,
     mut vd: Validator,
     _tooltipped: Tooltipped,
@@ -133,7 +134,9 @@ pub fn validate_add_opinion(
     vd.req_field("target");
     vd.field_item("modifier", Item::OpinionModifier);
     vd.field_target("target", sc, Scopes::Character);
-    vd.field_script_value("opinion", sc); // undocumented
+    let min = data.get_defined_number("NCharacter|MIN_OPINION").unwrap_or(-100.0);
+    let max = data.get_defined_number("NCharacter|MAX_OPINION").unwrap_or(100.0);
+    vd.field_script_value_in_range("opinion", sc, min, max); // undocumented
     validate_optional_duration(&mut vd, sc);
 }
 
@@ -1388,12 +1391,14 @@ pub fn validate_add_character_flag(
     _tooltipped: Tooltipped,
 ) {
     match bv {
-        BV::Value(_token) => (),
+        BV::Value(token) => data.variables.write(VariableKind::CharacterFlag, token),
         BV::Block(block) => {
             let mut vd = Validator::new(block, data);
             vd.set_case_sensitive(false);
             vd.req_field("flag");
-            vd.multi_field_value("flag");
+            for flag in vd.multi_field_value("flag") {
+                data.variables.write(VariableKind::CharacterFlag, flag);
+            }
             validate_optional_duration(&mut vd, sc);
         }
     }
@@ -1402,14 +1407,16 @@ pub fn validate_add_character_flag(
 pub fn validate_add_dead_character_flag(
     _key: &Token,
     block: &Block,
-    _data: &Everything,
+    data: &Everything,
     sc: &mut ScopeContext,
     mut vd: Validator,
     _tooltipped: Tooltipped,
 ) {
     vd.set_case_sensitive(false);
     vd.req_field("flag");
-    vd.multi_field_value("flag");
+    for flag in vd.multi_field_value("flag") {
+        data.variables.write(VariableKind::CharacterFlag, flag);
+    }
     validate_mandatory_duration(block, &mut vd, sc);
 }
 
@@ -1469,13 +1476,16 @@ pub fn validate_close_view(
     sc: &mut ScopeContext,
     _tooltipped: Tooltipped,
 ) {
+    // The views are defined by the game engine, but they may be the names of windows.
     match bv {
-        BV::Value(_token) => (), // TODO
+        BV::Value(token) => data.mark_used(Item::WidgetName, token.as_str()),
         BV::Block(block) => {
             let mut vd = Validator::new(block, data);
             vd.set_case_sensitive(false);
             vd.req_field("view");
-            vd.field_value("view"); // TODO
+            if let Some(view) = vd.field_value("view") {
+                data.mark_used(Item::WidgetName, view.as_str());
+            }
             vd.field_target("player", sc, Scopes::Character);
         }
     }
@@ -1595,13 +1605,16 @@ pub fn validate_open_view(
     sc: &mut ScopeContext,
     _tooltipped: Tooltipped,
 ) {
+    // The views are defined by the game engine, but they may be the names of windows.
     match bv {
-        BV::Value(_token) => (), // TODO
+        BV::Value(token) => data.mark_used(Item::WidgetName, token.as_str()),
         BV::Block(block) => {
             let mut vd = Validator::new(block, data);
             vd.set_case_sensitive(false);
             vd.req_field("view");
-            vd.field_value("view"); // TODO
+            if let Some(view) = vd.field_value("view") {
+                data.mark_used(Item::WidgetName, view.as_str());
+            }
             vd.field_value("view_message"); // TODO
             vd.field_target("player", sc, Scopes::Character);
             if key.is("open_view_data") {
@@ -1636,13 +1649,15 @@ pub fn validate_remove_courtier_or_guest(
 pub fn validate_set_dead_character_variable(
     _key: &Token,
     block: &Block,
-    _data: &Everything,
+    data: &Everything,
     sc: &mut ScopeContext,
     mut vd: Validator,
     _tooltipped: Tooltipped,
 ) {
     vd.req_field("name");
-    vd.field_value("name");
+    if let Some(name) = vd.field_value("name") {
+        data.variables.write(VariableKind::Variable, name);
+    }
     vd.field_validated("value", |bv, data| match bv {
         BV::Value(token) => {
             validate_target_ok_this(token, data, sc, Scopes::all_but_none());
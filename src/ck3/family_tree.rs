@@ -0,0 +1,214 @@
+//! Export the family trees from `history/characters` as GEDCOM or Graphviz DOT files.
+//!
+//! GEDCOM files can be loaded into most genealogy programs, and DOT files can be rendered with
+//! the `dot` tool from Graphviz. Characters are grouped by their house in the DOT output.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::ck3::data::characters::{Character, Gender};
+use crate::date::Date;
+use crate::everything::Everything;
+use crate::helpers::{TigerHashMap, TigerHashSet};
+use crate::token::Token;
+
+/// Used to look up the last house and dynasty a character had.
+const LAST_DATE: Date = Date::new(9999, 1, 1);
+
+/// Write the family trees of all characters to `path`. The format is chosen by the extension,
+/// which must be `.ged` or `.dot`.
+pub fn export(data: &Everything, path: &Path) -> Result<()> {
+    let mut people: Vec<Person> = data.characters.iter().map(|ch| Person::new(ch, data)).collect();
+    people.sort_by(|a, b| a.birth.cmp(&b.birth).then_with(|| a.id.cmp(b.id)));
+
+    let output = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("ged") => gedcom(&people),
+        Some("dot" | "gv") => dot(&people),
+        _ => bail!("unknown family tree format for {}, expected .ged or .dot", path.display()),
+    };
+    fs::write(path, output)?;
+    Ok(())
+}
+
+/// The information about a character that goes into the family tree.
+struct Person<'a> {
+    id: &'a str,
+    name: &'a str,
+    gender: Gender,
+    father: Option<&'a str>,
+    mother: Option<&'a str>,
+    birth: Option<Date>,
+    death: Option<Date>,
+    house: Option<&'a str>,
+    dynasty: Option<&'a str>,
+}
+
+impl<'a> Person<'a> {
+    fn new(ch: &'a Character, data: &'a Everything) -> Self {
+        let key = ch.key();
+        Self {
+            id: key.as_str(),
+            name: ch.name().map_or("", Token::as_str),
+            gender: ch.gender(),
+            father: ch.father().map(Token::as_str),
+            mother: ch.mother().map(Token::as_str),
+            birth: ch.birth_date(),
+            death: ch.death_date(),
+            house: ch.get_house(LAST_DATE).map(Token::as_str),
+            dynasty: data.characters.get_dynasty(key, LAST_DATE, data).map(Token::as_str),
+        }
+    }
+
+    fn lifespan(&self) -> String {
+        let birth = self.birth.map(|d| d.to_string()).unwrap_or_default();
+        let death = self.death.map(|d| d.to_string()).unwrap_or_default();
+        format!("{birth} - {death}")
+    }
+}
+
+fn gedcom(people: &[Person]) -> String {
+    let mut out = String::new();
+    out.push_str("0 HEAD\n1 SOUR ck3-tiger\n1 GEDC\n2 VERS 5.5.1\n2 FORM LINEAGE-LINKED\n");
+    out.push_str("1 CHAR UTF-8\n");
+
+    let index: TigerHashMap<&str, usize> =
+        people.iter().enumerate().map(|(i, p)| (p.id, i + 1)).collect();
+    // Only parents that exist can be linked to.
+    let known = |id: Option<&str>| id.and_then(|id| index.get(id).copied());
+
+    // A family for every combination of parents that has children.
+    let mut families: Vec<(Option<usize>, Option<usize>, Vec<usize>)> = Vec::new();
+    let mut family_of: TigerHashMap<(Option<usize>, Option<usize>), usize> =
+        TigerHashMap::default();
+    let mut child_of: TigerHashMap<usize, usize> = TigerHashMap::default();
+    for person in people {
+        let parents = (known(person.father), known(person.mother));
+        if parents == (None, None) {
+            continue;
+        }
+        let fam = *family_of.entry(parents).or_insert_with(|| {
+            families.push((parents.0, parents.1, Vec::new()));
+            families.len()
+        });
+        families[fam - 1].2.push(index[person.id]);
+        child_of.insert(index[person.id], fam);
+    }
+    let mut spouse_in: TigerHashMap<usize, Vec<usize>> = TigerHashMap::default();
+    for (i, (father, mother, _)) in families.iter().enumerate() {
+        for parent in [father, mother].into_iter().flatten() {
+            spouse_in.entry(*parent).or_default().push(i + 1);
+        }
+    }
+
+    for person in people {
+        let i = index[person.id];
+        let _ = writeln!(out, "0 @I{i}@ INDI");
+        let _ = writeln!(out, "1 NAME {} /{}/", person.name, person.house.unwrap_or(""));
+        let sex = if person.gender == Gender::Male { "M" } else { "F" };
+        let _ = writeln!(out, "1 SEX {sex}");
+        if let Some(birth) = person.birth {
+            let _ = writeln!(out, "1 BIRT\n2 DATE {}", gedcom_date(birth));
+        }
+        if let Some(death) = person.death {
+            let _ = writeln!(out, "1 DEAT\n2 DATE {}", gedcom_date(death));
+        }
+        let _ = writeln!(out, "1 REFN {}", person.id);
+        if let Some(dynasty) = person.dynasty {
+            let _ = writeln!(out, "1 NOTE dynasty {dynasty}");
+        }
+        if let Some(fam) = child_of.get(&i) {
+            let _ = writeln!(out, "1 FAMC @F{fam}@");
+        }
+        for fam in spouse_in.get(&i).into_iter().flatten() {
+            let _ = writeln!(out, "1 FAMS @F{fam}@");
+        }
+    }
+
+    for (i, (father, mother, children)) in families.iter().enumerate() {
+        let _ = writeln!(out, "0 @F{}@ FAM", i + 1);
+        if let Some(father) = father {
+            let _ = writeln!(out, "1 HUSB @I{father}@");
+        }
+        if let Some(mother) = mother {
+            let _ = writeln!(out, "1 WIFE @I{mother}@");
+        }
+        for child in children {
+            let _ = writeln!(out, "1 CHIL @I{child}@");
+        }
+    }
+    out.push_str("0 TRLR\n");
+    out
+}
+
+fn gedcom_date(date: Date) -> String {
+    const MONTHS: [&str; 12] =
+        ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+    let (year, month, day) = date.ymd();
+    let month = MONTHS.get(usize::try_from(month - 1).unwrap_or(0)).unwrap_or(&"JAN");
+    if year < 0 {
+        format!("{day} {month} {} B.C.", -year)
+    } else {
+        format!("{day} {month} {year}")
+    }
+}
+
+fn dot(people: &[Person]) -> String {
+    let mut out = String::new();
+    out.push_str("digraph family_tree {\n    rankdir=TB;\n    node [shape=box];\n");
+
+    let mut by_house: TigerHashMap<&str, Vec<&Person>> = TigerHashMap::default();
+    for person in people {
+        by_house.entry(person.house.unwrap_or("")).or_default().push(person);
+    }
+    let mut houses: Vec<&str> = by_house.keys().copied().collect();
+    houses.sort_unstable();
+
+    for house in houses {
+        let indent = if house.is_empty() {
+            "    "
+        } else {
+            let house = dot_escape(house);
+            let _ =
+                writeln!(out, "    subgraph \"cluster_{house}\" {{\n        label=\"{house}\";");
+            "        "
+        };
+        for person in &by_house[house] {
+            let shape = if person.gender == Gender::Male { "box" } else { "ellipse" };
+            let _ = writeln!(
+                out,
+                "{indent}\"{}\" [label=\"{}\\n{}\\n{}\", shape={shape}];",
+                dot_escape(person.id),
+                dot_escape(person.name),
+                dot_escape(person.id),
+                person.lifespan()
+            );
+        }
+        if !house.is_empty() {
+            out.push_str("    }\n");
+        }
+    }
+
+    let known: TigerHashSet<&str> = people.iter().map(|p| p.id).collect();
+    for person in people {
+        for parent in [person.father, person.mother].into_iter().flatten() {
+            if known.contains(parent) {
+                let _ = writeln!(
+                    out,
+                    "    \"{}\" -> \"{}\";",
+                    dot_escape(parent),
+                    dot_escape(person.id)
+                );
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Escape `s` for use inside a quoted DOT string.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
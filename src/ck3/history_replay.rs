@@ -0,0 +1,241 @@
+//! Replay the CK3 history files up to each bookmark date, and check the resulting world state.
+//!
+//! The history files are otherwise validated one entry at a time. Some problems only show up when
+//! the entries are combined, such as a title whose holder died in an entry in another file.
+
+use crate::ck3::data::titles::Tier;
+use crate::date::Date;
+use crate::everything::Everything;
+use crate::helpers::{TigerHashMap, TigerHashSet};
+use crate::item::Item;
+use crate::report::{err, tips, warn, ErrorKey};
+use crate::token::Token;
+
+/// The age at which characters become adults.
+const ADULT_AGE: i16 = 16;
+
+/// Check the world state at the start date of every bookmark.
+pub fn check_bookmarks(data: &Everything) {
+    let mut bookmarks: Vec<(&Token, Date)> = data
+        .database
+        .iter_key_block(Item::Bookmark)
+        .filter_map(|(key, block)| block.get_field_date("start_date").map(|date| (key, date)))
+        .collect();
+    bookmarks.sort_by_key(|(key, date)| (*date, key.as_str()));
+
+    for (bookmark, date) in bookmarks {
+        WorldState::new(date, data).check(bookmark, data);
+    }
+}
+
+/// Who holds what at a specific date, according to the title histories.
+struct WorldState<'a> {
+    date: Date,
+    /// Title key to the id of its holder.
+    holders: TigerHashMap<&'static str, &'a Token>,
+    /// Title key to its tier and the key of its de facto liege title.
+    lieges: TigerHashMap<&'static str, (Tier, &'a Token)>,
+    /// Character id to the titles they hold.
+    held: TigerHashMap<&'static str, Vec<&'a Token>>,
+}
+
+impl<'a> WorldState<'a> {
+    fn new(date: Date, data: &'a Everything) -> Self {
+        let mut holders = TigerHashMap::default();
+        let mut lieges = TigerHashMap::default();
+        let mut held: TigerHashMap<&str, Vec<&Token>> = TigerHashMap::default();
+        for (key, tier) in data.title_history.iter_keys_and_tiers() {
+            let title = key.as_str();
+            if let Some(holder) = data.title_history.get_value_at_date(title, "holder", date) {
+                if !holder.is("0") {
+                    holders.insert(title, holder);
+                    held.entry(holder.as_str()).or_default().push(key);
+                }
+            }
+            if let Some(liege) = data.title_history.get_value_at_date(title, "liege", date) {
+                if !liege.is("0") {
+                    lieges.insert(title, (tier, liege));
+                }
+            }
+        }
+        Self { date, holders, lieges, held }
+    }
+
+    fn check(&self, bookmark: &Token, data: &Everything) {
+        let at = format!("at bookmark {bookmark} ({})", self.date);
+        self.check_holders(&at, data);
+        self.check_lieges(&at, data);
+        self.check_liege_cycles(&at);
+        self.check_capitals(&at, data);
+        self.check_spouses(&at, data);
+        self.check_provinces(&at, data);
+    }
+
+    fn check_holders(&self, at: &str, data: &Everything) {
+        for (title, holder) in &self.holders {
+            let Some(ch) = data.characters.get(holder.as_str()) else {
+                // Missing characters are reported by the title history validation.
+                continue;
+            };
+            if !ch.is_alive(self.date) {
+                let msg = format!("holder of {title} is not alive {at}");
+                err(ErrorKey::History).msg(msg).loc(*holder).push();
+            } else if let Some(birth) = ch.birth_date() {
+                let age = self.date.years_since(birth);
+                if age < ADULT_AGE {
+                    let msg = format!("holder of {title} is a child of {age} {at}");
+                    let info = "this is allowed, but the realm will start with a regency";
+                    tips(ErrorKey::History).weak().msg(msg).info(info).loc(*holder).push();
+                }
+            }
+        }
+    }
+
+    fn check_lieges(&self, at: &str, data: &Everything) {
+        for (title, (tier, liege)) in &self.lieges {
+            let Ok(liege_tier) = Tier::try_from(*liege) else {
+                continue;
+            };
+            if liege_tier <= *tier {
+                let msg =
+                    format!("{title} is a vassal of {liege} {at}, which is not a higher tier");
+                err(ErrorKey::TitleTier).msg(msg).loc(*liege).push();
+            }
+            if data.titles.exists(liege.as_str()) && !self.holders.contains_key(liege.as_str()) {
+                let msg = format!("{title} is a vassal of {liege}, which has no holder {at}");
+                err(ErrorKey::History).msg(msg).loc(*liege).push();
+            }
+        }
+    }
+
+    /// Look for characters who end up as their own liege through a chain of vassals.
+    fn check_liege_cycles(&self, at: &str) {
+        // Character id to the ids of their lieges, with the `liege` field that made it so.
+        let mut liege_of: TigerHashMap<&str, Vec<(&str, &Token)>> = TigerHashMap::default();
+        for (title, (_, liege)) in &self.lieges {
+            if let (Some(vassal), Some(lord)) =
+                (self.holders.get(title), self.holders.get(liege.as_str()))
+            {
+                if vassal.as_str() != lord.as_str() {
+                    liege_of.entry(vassal.as_str()).or_default().push((lord.as_str(), liege));
+                }
+            }
+        }
+
+        let mut reported = TigerHashSet::default();
+        let mut starts: Vec<&str> = liege_of.keys().copied().collect();
+        starts.sort_unstable();
+        for start in starts {
+            if reported.contains(start) {
+                continue;
+            }
+            if let Some(path) = find_cycle(start, &liege_of) {
+                let msg =
+                    format!("character {start} is their own liege through their vassals {at}");
+                let mut report = err(ErrorKey::Loop).msg(msg).loc(path[0].1);
+                for (ch, token) in &path[1..] {
+                    report = report.loc_msg(*token, format!("liege of {ch}"));
+                }
+                report.push();
+                reported.extend(path.iter().map(|(ch, _)| *ch));
+            }
+        }
+    }
+
+    fn check_capitals(&self, at: &str, data: &Everything) {
+        for (ch_id, titles) in &self.held {
+            let Some(ch) = data.characters.get(ch_id) else {
+                continue;
+            };
+            if let Some(capital) = ch.capital_at(self.date) {
+                if !titles.iter().any(|title| title.as_str() == capital.as_str()) {
+                    let msg =
+                        format!("capital of {ch_id} is {capital}, which they don't hold {at}");
+                    err(ErrorKey::History).msg(msg).loc(capital).push();
+                }
+            } else if let Some(title) = titles
+                .iter()
+                .filter(|title| Tier::try_from(**title).is_ok_and(|tier| tier >= Tier::County))
+                .min_by_key(|title| title.as_str())
+            {
+                let msg = format!("{ch_id} holds {title} but has no capital {at}");
+                let info = "set `capital` in their character history to choose it";
+                tips(ErrorKey::History)
+                    .weak()
+                    .msg(msg)
+                    .info(info)
+                    .loc(self.holders[title.as_str()])
+                    .push();
+            }
+        }
+    }
+
+    fn check_spouses(&self, at: &str, data: &Everything) {
+        for ch in data.characters.iter() {
+            let key = ch.key();
+            if !ch.is_alive(self.date) {
+                continue;
+            }
+            for spouse in ch.spouses_at(self.date) {
+                let Some(other) = data.characters.get(spouse.as_str()) else {
+                    continue;
+                };
+                if !other.is_alive(self.date) {
+                    let msg = format!("{key} is still married to {spouse} {at}, who is not alive");
+                    warn(ErrorKey::History).weak().msg(msg).loc(spouse).push();
+                } else if let Some(removed) = other.spouse_removed_at(key.as_str(), self.date) {
+                    if ch.spouse_added_at(spouse.as_str(), self.date).is_some_and(|d| d <= removed)
+                    {
+                        let msg = format!(
+                            "{key} is married to {spouse} {at}, but {spouse} removed {key} as spouse on {removed}"
+                        );
+                        warn(ErrorKey::History).msg(msg).loc(spouse).push();
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_provinces(&self, at: &str, data: &Everything) {
+        for (provid, barony) in data.titles.iter_county_capital_provinces() {
+            let loc = data.province_histories.key(provid).unwrap_or(barony);
+            if data.province_histories.get_value_at_date(provid, "culture", self.date).is_none() {
+                let msg =
+                    format!("county capital province {provid} ({barony}) has no culture {at}");
+                warn(ErrorKey::History).msg(msg).loc(loc).push();
+            }
+            if data.province_histories.get_value_at_date(provid, "faith", self.date).is_none()
+                && data
+                    .province_histories
+                    .get_value_at_date(provid, "religion", self.date)
+                    .is_none()
+            {
+                let msg = format!("county capital province {provid} ({barony}) has no faith {at}");
+                warn(ErrorKey::History).msg(msg).loc(loc).push();
+            }
+        }
+    }
+}
+
+/// Follow the liege relations from `start` and return the path back to `start` if there is one.
+/// The path is a list of characters with the `liege` field that connects them to the next one.
+fn find_cycle<'a>(
+    start: &'a str,
+    liege_of: &TigerHashMap<&'a str, Vec<(&'a str, &'a Token)>>,
+) -> Option<Vec<(&'a str, &'a Token)>> {
+    let mut seen = TigerHashSet::default();
+    let mut stack: Vec<(&str, Vec<(&str, &Token)>)> = vec![(start, Vec::new())];
+    while let Some((ch, path)) = stack.pop() {
+        for &(lord, token) in liege_of.get(ch).into_iter().flatten() {
+            let mut path = path.clone();
+            path.push((ch, token));
+            if lord == start {
+                return Some(path);
+            }
+            if seen.insert(lord) {
+                stack.push((lord, path));
+            }
+        }
+    }
+    None
+}
@@ -2,6 +2,8 @@
 
 pub mod data;
 pub mod effect_validation;
+pub mod family_tree;
+pub mod history_replay;
 pub mod modif;
 pub mod scopes;
 pub mod tables;
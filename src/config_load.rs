@@ -9,10 +9,13 @@ use strum::IntoEnumIterator;
 
 use crate::block::{Block, BlockItem, Comparator, Eq::*, Field, BV};
 use crate::helpers::stringify_list;
+use crate::item::Item;
+use crate::lint::{can_lint, LintCondition, LintRule};
 use crate::report::{
-    err, set_predicate, set_show_loaded_mods, set_show_vanilla, Confidence, ErrorKey, ErrorLoc,
-    FilterRule, PointedMessage, Severity,
+    err, is_plugin_key, set_predicate, set_show_loaded_mods, set_show_vanilla, Confidence,
+    ErrorKey, ErrorLoc, FilterRule, PointedMessage, Severity,
 };
+use crate::token::Token;
 
 /// Checks for legacy ignore blocks (that no longer work) and report an error if they are present.
 pub fn check_for_legacy_ignore(config: &Block) {
@@ -258,11 +261,25 @@ fn load_ignore_keys_in_files(bv: &BV) -> Option<FilterRule> {
     }
 }
 
+/// Report an error if `error_key` is a plugin key that none of the added plugins has, and return
+/// whether the filter can use it.
+fn check_plugin_key(error_key: ErrorKey, token: &Token) -> bool {
+    if matches!(error_key, ErrorKey::Plugin(_, _)) && !is_plugin_key(error_key) {
+        err(ErrorKey::Config)
+            .msg(format!("Unknown plugin key `{error_key}`"))
+            .info("plugin keys are written as `plugin.key`, and only the keys of the plugins that are added can be used")
+            .loc(token)
+            .push();
+        return false;
+    }
+    true
+}
+
 fn load_keys_array(array_block: &Block) -> Option<FilterRule> {
     let keys: Vec<_> = array_block.iter_values_warn()
         .filter_map(|token| {
-            if let Ok(error_key) = token.as_str().parse() {
-                Some(FilterRule::Key(error_key))
+            if let Some(error_key) = ErrorKey::from_name(token.as_str()) {
+                check_plugin_key(error_key, token).then_some(FilterRule::Key(error_key))
             } else {
                 err(ErrorKey::Config).strong()
                     .msg("Invalid key. In the output, keys are listed between parentheses on the first line of each report. For example, in `Warning(missing-item)`, the key is `missing-item`.")
@@ -353,8 +370,8 @@ fn load_rule_key(value: &BV) -> Option<FilterRule> {
             None
         }
         BV::Value(token) => {
-            if let Ok(error_key) = token.as_str().parse() {
-                Some(FilterRule::Key(error_key))
+            if let Some(error_key) = ErrorKey::from_name(token.as_str()) {
+                check_plugin_key(error_key, token).then_some(FilterRule::Key(error_key))
             } else {
                 err(ErrorKey::Config).msg(
                     "Invalid key. In the output, keys are listed between parentheses on the first line of each report. For example, in `Warning(missing-item)`, the key is `missing-item`.",
@@ -391,6 +408,198 @@ fn load_rule_text(bv: &BV) -> Option<FilterRule> {
     }
 }
 
+/// Load the custom lint rules from the `lint` sections of the config.
+pub fn load_lint_rules(config: &Block) -> Vec<LintRule> {
+    config.get_field_blocks("lint").into_iter().filter_map(load_lint_rule).collect()
+}
+
+/// Load a single `lint` section.
+fn load_lint_rule(block: &Block) -> Option<LintRule> {
+    for key in ["name", "match", "assert", "message", "severity"] {
+        assert_one_key(key, block);
+    }
+    let mut rule = LintRule {
+        loc: block.loc,
+        name: None,
+        items: Vec::new(),
+        matches: LintCondition::Always(true),
+        assert: LintCondition::Always(true),
+        message: None,
+        severity: Severity::Warning,
+    };
+    let mut has_assert = false;
+    for Field(key, _, bv) in block.iter_items().filter_map(BlockItem::expect_field) {
+        match key.as_str() {
+            "name" => rule.name = expect_lint_value(key, bv, "name = \"event-titles\"").cloned(),
+            "message" => {
+                rule.message = expect_lint_value(key, bv, "message = \"Events need a title\"")
+                    .map(Token::to_string);
+            }
+            "item" => {
+                let token = expect_lint_value(key, bv, "item = decision")?;
+                if let Some(itype) = Item::iter().find(|itype| token.is(itype.into())) {
+                    if !can_lint(itype) {
+                        let msg = format!("Lint rules cannot check {itype} items");
+                        let info = "only item types that are defined by a key and a block in the script files can be checked";
+                        err(ErrorKey::Config).msg(msg).info(info).loc(token).push();
+                        return None;
+                    }
+                    rule.items.push(itype);
+                } else {
+                    err(ErrorKey::Config)
+                        .msg("Unknown item type. Example usage: `item = decision`")
+                        .loc(token)
+                        .push();
+                    return None;
+                }
+            }
+            "match" => rule.matches = LintCondition::Conjunction(load_lint_conditions_from_bv(bv)?),
+            "assert" => {
+                rule.assert = LintCondition::Conjunction(load_lint_conditions_from_bv(bv)?);
+                has_assert = true;
+            }
+            "severity" => {
+                let token = expect_lint_value(key, bv, "severity = error")?;
+                if let Ok(severity) = token.as_str().to_ascii_lowercase().parse() {
+                    rule.severity = severity;
+                } else {
+                    err(ErrorKey::Config)
+                        .msg(format!(
+                            "Invalid Severity value. Valid values: {}",
+                            stringify_list(
+                                &Severity::iter().map(Severity::into).collect::<Vec<_>>()
+                            ),
+                        ))
+                        .loc(token)
+                        .push();
+                    return None;
+                }
+            }
+            _ => {
+                err(ErrorKey::Config).msg("Unexpected key").loc(key).push();
+                return None;
+            }
+        }
+    }
+    if !has_assert {
+        err(ErrorKey::Config)
+            .msg("This lint rule has no `assert` block. It will be ignored.")
+            .loc(block)
+            .push();
+        return None;
+    }
+    Some(rule)
+}
+
+/// Load a vector of lint conditions from a value, which should be a block.
+fn load_lint_conditions_from_bv(bv: &BV) -> Option<Vec<LintCondition>> {
+    match bv {
+        BV::Block(block) => Some(
+            block
+                .iter_items()
+                .filter_map(BlockItem::expect_field)
+                .filter_map(load_lint_condition)
+                .collect(),
+        ),
+        BV::Value(_) => {
+            let msg = "Expected a trigger block. Example usage: `assert = { has_field = title }`";
+            err(ErrorKey::Config).msg(msg).loc(bv).push();
+            None
+        }
+    }
+}
+
+/// Load a single lint condition.
+fn load_lint_condition(field: &Field) -> Option<LintCondition> {
+    let Field(key, cmp, bv) = field;
+    if !matches!(cmp, Comparator::Equals(Single)) {
+        err(ErrorKey::Config)
+            .msg(format!("Unexpected operator `{cmp}`, only `=` is valid here."))
+            .loc(key)
+            .push();
+        return None;
+    }
+    match key.as_str() {
+        "always" => match expect_lint_value(key, bv, "always = yes")?.as_str() {
+            "yes" => Some(LintCondition::Always(true)),
+            "no" => Some(LintCondition::Always(false)),
+            _ => {
+                err(ErrorKey::Config)
+                    .msg("`always` value not recognised. Valid values are `yes` and `no`.")
+                    .loc(bv)
+                    .push();
+                None
+            }
+        },
+        "key" => {
+            let token = expect_lint_value(key, bv, "key = \"my_mod.*\"")?;
+            Some(LintCondition::Key(token.to_string()))
+        }
+        "file" => {
+            let token = expect_lint_value(key, bv, "file = events/my_mod/")?;
+            Some(LintCondition::File(PathBuf::from(token.as_str())))
+        }
+        "has_field" => {
+            let token = expect_lint_value(key, bv, "has_field = ai_check_interval")?;
+            Some(LintCondition::HasField(token.to_string()))
+        }
+        "uses" => {
+            let token = expect_lint_value(key, bv, "uses = debug_log")?;
+            Some(LintCondition::Uses(token.to_string()))
+        }
+        "has_value" => {
+            let Some(block) = bv.get_block() else {
+                err(ErrorKey::Config)
+                    .msg("`has_value` should open a block. Example usage: `has_value = { hidden = yes }`")
+                    .loc(bv)
+                    .push();
+                return None;
+            };
+            let values = block
+                .iter_assignments_warn()
+                .map(|(name, pattern)| {
+                    LintCondition::HasValue(name.to_string(), pattern.to_string())
+                })
+                .collect();
+            Some(LintCondition::Conjunction(values))
+        }
+        "NOT" => {
+            let mut children = load_lint_conditions_from_bv(bv)?;
+            if children.len() == 1 {
+                Some(LintCondition::Negation(Box::new(children.remove(0))))
+            } else {
+                Some(LintCondition::Negation(Box::new(LintCondition::Disjunction(children))))
+            }
+        }
+        "AND" => Some(LintCondition::Conjunction(load_lint_conditions_from_bv(bv)?)),
+        "OR" => Some(LintCondition::Disjunction(load_lint_conditions_from_bv(bv)?)),
+        "NAND" => Some(LintCondition::Negation(Box::new(LintCondition::Conjunction(
+            load_lint_conditions_from_bv(bv)?,
+        )))),
+        "NOR" => Some(LintCondition::Negation(Box::new(LintCondition::Disjunction(
+            load_lint_conditions_from_bv(bv)?,
+        )))),
+        _ => {
+            err(ErrorKey::Config).msg("Unexpected key").loc(key).push();
+            None
+        }
+    }
+}
+
+/// Return the value of a field in a `lint` section, or report an error if it opens a block.
+fn expect_lint_value<'a>(key: &Token, bv: &'a BV, example: &str) -> Option<&'a Token> {
+    match bv {
+        BV::Value(token) => Some(token),
+        BV::Block(_) => {
+            err(ErrorKey::Config)
+                .msg(format!("`{key}` can't open a block. Example usage: `{example}`"))
+                .loc(bv)
+                .push();
+            None
+        }
+    }
+}
+
 /// Assert that the given key occurs at most once within the given block.
 /// If the assertion fails, an error report will be created. No other action will be taken.
 pub fn assert_one_key(assert_key: &str, block: &Block) {
@@ -4,6 +4,7 @@ use std::borrow::Cow;
 
 use crate::game::Game;
 use crate::helpers::{stringify_choices, TigerHashMap};
+use crate::item::Item;
 use crate::report::{err, warn, ErrorKey};
 use crate::scopes::Scopes;
 use crate::token::Token;
@@ -59,6 +60,10 @@ pub struct ScopeContext {
     /// `scope_override` config file feature. If `no_warn` is set then this `ScopeContext` will not
     /// emit any reports.
     no_warn: bool,
+
+    /// The event or on-action whose script is being validated with this `ScopeContext`, if any.
+    /// It is used to follow named scopes along chains of events.
+    origin: Option<(Item, Token)>,
 }
 
 #[derive(Clone, Debug)]
@@ -160,6 +165,7 @@ impl ScopeContext {
             is_unrooted: false,
             strict_scopes: true,
             no_warn: false,
+            origin: None,
         }
     }
 
@@ -185,6 +191,7 @@ impl ScopeContext {
             is_unrooted: true,
             strict_scopes: true,
             no_warn: false,
+            origin: None,
         }
     }
 
@@ -199,6 +206,79 @@ impl ScopeContext {
         self.strict_scopes = strict;
     }
 
+    /// Record that this `ScopeContext` is for the script of the event or on-action `key`.
+    pub fn set_origin(&mut self, itype: Item, key: &Token) {
+        self.origin = Some((itype, key.clone()));
+    }
+
+    /// Return the event or on-action whose script this `ScopeContext` is for.
+    /// See [`Self::set_origin`].
+    pub fn origin(&self) -> Option<&(Item, Token)> {
+        self.origin.as_ref()
+    }
+
+    /// Return the named scopes that have been set in this `ScopeContext`, with their scope types.
+    /// Named scopes that are expected to be supplied by the caller are not included.
+    pub fn defined_names(&self) -> Vec<(&str, Scopes)> {
+        self.names
+            .iter()
+            .filter(|(_, &idx)| self.is_input[idx].is_none())
+            .map(|(name, &idx)| (name.as_str(), self.resolve_named(idx).0))
+            .collect()
+    }
+
+    /// Return the named scopes that are expected to be supplied by the caller, with the scope
+    /// types they are expected to have and the token where each is first used.
+    pub fn input_names(&self) -> Vec<(&str, Scopes, &Token)> {
+        self.names
+            .iter()
+            .filter_map(|(name, &idx)| {
+                let token = self.is_input[idx].as_ref()?;
+                Some((name.as_str(), self.resolve_named(idx).0, token))
+            })
+            .collect()
+    }
+
+    /// Describe what is known about `this`, `root`, the `prev` chain and all the named scopes and
+    /// lists, one per line, with the reason why each has the scope types it has.
+    pub fn explain(&self) -> String {
+        fn describe((scopes, reason): (Scopes, &Reason)) -> String {
+            let loc = reason.token().loc;
+            format!("{scopes}, {} ({}:{})", reason.msg(), loc.pathname().display(), loc.line)
+        }
+
+        let mut lines = vec![
+            format!("this: {}", describe(self.scopes_reason())),
+            format!("root: {}", describe(self.resolve_root())),
+        ];
+        let mut depth = 0;
+        let mut ptr = &self.prev;
+        while let Some(entry) = ptr {
+            // An unrooted context has an extra level that stands for the unknown caller.
+            if !(self.is_unrooted && entry.prev.is_none()) {
+                let name = vec!["prev"; depth + 1].join(".");
+                lines.push(format!("{name}: {}", describe(self.scopes_reason_internal(depth))));
+            }
+            depth += 1;
+            ptr = &entry.prev;
+        }
+        let mut names: Vec<_> = self.names.iter().collect();
+        names.sort_unstable();
+        for (name, &idx) in names {
+            let input =
+                if self.is_input[idx].is_some() { ", expected from the caller" } else { "" };
+            lines.push(format!("scope:{name}: {}{input}", describe(self.resolve_named(idx))));
+        }
+        let mut list_names: Vec<_> = self.list_names.iter().collect();
+        list_names.sort_unstable();
+        for (name, &idx) in list_names {
+            let input =
+                if self.is_input[idx].is_some() { ", expected from the caller" } else { "" };
+            lines.push(format!("list {name}: {}{input}", describe(self.resolve_named(idx))));
+        }
+        lines.join("\n")
+    }
+
     /// Return whether this `ScopeContext` has strict scopes set to true.
     /// See [`Self::set_strict_scopes`].
     pub fn is_strict(&self) -> bool {
@@ -76,7 +76,7 @@ impl DbKind for Fontfiles {
 
         vd.multi_field_validated_block("group", |block, data| {
             let mut vd = Validator::new(block, data);
-            vd.field_list("languages"); // TODO
+            vd.field_list("languages");
             vd.field_list_items("files", Item::File);
         });
     }
@@ -2,17 +2,23 @@
 
 use std::mem::drop;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
 use std::sync::{Arc, RwLock};
 
 use crate::block::{Block, BlockItem, Field, BV};
 use crate::everything::Everything;
-use crate::fileset::{FileEntry, FileHandler};
-use crate::gui::{BuiltinWidget, GuiBlock, GuiBlockFrom};
+use crate::fileset::{FileEntry, FileHandler, FileKind};
+use crate::glyphs::TextWidths;
+use crate::gui::{
+    check_layout, check_text_widths, BuiltinWidget, DataContext, GuiBlock, GuiBlockFrom,
+};
 use crate::helpers::{dup_error, TigerHashMap, TigerHashSet};
 use crate::item::Item;
 use crate::lowercase::Lowercase;
 use crate::pdxfile::PdxFile;
-use crate::report::{err, fatal, untidy, warn, ErrorKey, Severity};
+use crate::report::{
+    err, fatal, untidy, warn, warn_abbreviated, warn_header, will_maybe_log, ErrorKey, Severity,
+};
 use crate::token::Token;
 use crate::validator::Validator;
 
@@ -30,6 +36,15 @@ pub struct Gui {
     // This is indexed by a (colorblindmode, textformatname) pair
     textformats_colorblind: TigerHashMap<(&'static str, &'static str), TextFormat>,
     widget_names: TigerHashSet<Token>,
+    /// Widget names that have been looked up during validation. Used for the --unused output.
+    used_names: RwLock<TigerHashSet<String>>,
+    /// The single-quoted strings in gui expressions, such as the arguments of `OpenGameView` and
+    /// `GetScriptedGui`. A window whose name is among them is probably opened somewhere.
+    quoted_strings: TigerHashSet<&'static str>,
+    /// The variables that are set or toggled by `GetVariableSystem`.
+    variables_set: TigerHashSet<&'static str>,
+    /// Used to estimate whether texts fit in their widgets.
+    text_widths: TextWidths,
 }
 
 impl Gui {
@@ -229,14 +244,247 @@ impl Gui {
         self.textformats.values().map(|item| &item.key)
     }
 
+    /// Find the value of `setting` in a text format such as the `default_format` of a widget.
+    /// `format` is like `#high;bold`, and the settings can be in it directly, like `fontsize:20`,
+    /// or in the `format` of the named textformats. The last one found wins.
+    pub fn format_setting(&self, format: &'static str, setting: &str) -> Option<&'static str> {
+        let find = |parts: &'static str| {
+            parts
+                .split(';')
+                .filter_map(|part| part.trim().strip_prefix(setting)?.strip_prefix(':'))
+                .next_back()
+        };
+        let mut found = None;
+        for part in format.strip_prefix('#').unwrap_or(format).split(';') {
+            let value = if part.contains(':') {
+                find(part)
+            } else {
+                self.textformats
+                    .get(part)
+                    .and_then(|textformat| textformat.block.get_field_value("format"))
+                    .and_then(|format| find(format.as_str()))
+            };
+            found = value.or(found);
+        }
+        found
+    }
+
+    pub fn text_widths(&self) -> &TextWidths {
+        &self.text_widths
+    }
+
     pub fn name_exists(&self, key: &str) -> bool {
+        self.mark_used(Item::WidgetName, key);
         self.widget_names.contains(key)
     }
 
+    pub fn mark_used(&self, itype: Item, key: &str) {
+        match itype {
+            Item::GuiTemplate => {
+                if let Some(template) = self.templates.get(key) {
+                    template.mark_used();
+                }
+            }
+            Item::GuiType => {
+                if let Some(guitype) = self.types.get(&Lowercase::new(key)) {
+                    guitype.mark_used();
+                }
+            }
+            Item::WidgetName => {
+                self.used_names.write().unwrap().insert(key.to_string());
+            }
+            _ => (),
+        }
+    }
+
+    /// Remember the quoted strings and variable system calls in a gui file's expressions.
+    fn scan_expressions(&mut self, block: &Block) {
+        for item in block.iter_items() {
+            match item {
+                BlockItem::Value(token) | BlockItem::Field(Field(_, _, BV::Value(token))) => {
+                    self.scan_expression(token);
+                }
+                BlockItem::Field(Field(_, _, BV::Block(block))) | BlockItem::Block(block) => {
+                    self.scan_expressions(block);
+                }
+            }
+        }
+    }
+
+    fn scan_expression(&mut self, token: &Token) {
+        let text = token.as_str();
+        if !text.contains('\'') {
+            return;
+        }
+        // Every other part is inside quotes.
+        for quoted in text.split('\'').skip(1).step_by(2) {
+            self.quoted_strings.insert(quoted);
+        }
+        for function in ["GetVariableSystem.Set('", "GetVariableSystem.Toggle('"] {
+            for (pos, _) in text.match_indices(function) {
+                let rest = &text[pos + function.len()..];
+                if let Some(end) = rest.find('\'') {
+                    self.variables_set.insert(&rest[..end]);
+                }
+            }
+        }
+    }
+
+    /// Report the gui templates, types, and windows that nothing refers to.
+    ///
+    /// Templates and types count as used only if they can be reached from a top-level widget that
+    /// is itself used, or if script refers to them. That way a whole tree of types that only
+    /// refer to each other is reported.
+    pub fn check_unused(&self, data: &Everything) {
+        let used_names = self.used_names.read().unwrap();
+        let mut windows = Vec::new();
+        let mut reached = Reached::default();
+        for widgets in self.files.values() {
+            for widget in widgets {
+                if self.is_unreferenced_window(widget, &used_names, data) {
+                    // Checked above that it has a name
+                    windows.push(widget.block.get_field_value("name").unwrap());
+                } else {
+                    self.reach_type(&widget.key, &mut reached);
+                    self.reach_block(&widget.block, &mut reached);
+                }
+            }
+        }
+        for template in self.templates.values() {
+            if template.used.load(Relaxed) {
+                self.reach_template(&template.key, &mut reached);
+            }
+        }
+        for guitype in self.types.values() {
+            if guitype.used.load(Relaxed) {
+                self.reach_type(&guitype.key, &mut reached);
+            }
+        }
+
+        let mut templates: Vec<&Token> = self
+            .templates
+            .values()
+            .filter(|template| !reached.templates.contains(template.key.as_str()))
+            .map(|template| &template.key)
+            .collect();
+        report_unused("Unused gui templates:\n", &mut templates);
+
+        let mut types: Vec<&Token> = self
+            .types
+            .iter()
+            .filter(|(key_lc, _)| !reached.types.contains(key_lc.as_str()))
+            .map(|(_, guitype)| &guitype.key)
+            .collect();
+        report_unused("Unused gui types:\n", &mut types);
+
+        report_unused("Unreferenced gui windows:\n", &mut windows);
+    }
+
+    /// Return whether `widget` is a named window in the mod that nothing seems to open.
+    fn is_unreferenced_window(
+        &self,
+        widget: &GuiWidget,
+        used_names: &TigerHashSet<String>,
+        data: &Everything,
+    ) -> bool {
+        // Windows in vanilla files, or in files that replace them, are opened by the game.
+        if widget.key.loc.kind != FileKind::Mod
+            || data.fileset.replaces_file(widget.key.loc.pathname())
+        {
+            return false;
+        }
+        let Some(name) = widget.block.get_field_value("name") else {
+            return false;
+        };
+        let toggled = widget.block.get_field_value("visible").is_some_and(|visible| {
+            self.variables_set
+                .iter()
+                .any(|var| visible.as_str().contains(&format!("GetVariableSystem.Exists('{var}')")))
+        });
+        !toggled
+            && !self.quoted_strings.contains(name.as_str())
+            && !used_names.contains(name.as_str())
+            && !data.item_exists(Item::ScriptedGui, name.as_str())
+    }
+
+    fn reach_type<'a>(&'a self, key: &Token, reached: &mut Reached<'a>) {
+        let key_lc = Lowercase::new(key.as_str());
+        if let Some((key_lc, guitype)) = self.types.get_key_value(&key_lc) {
+            if reached.types.insert(key_lc.as_str()) {
+                self.reach_type(&guitype.base, reached);
+                self.reach_block(&guitype.block, reached);
+            }
+        }
+    }
+
+    fn reach_template<'a>(&'a self, key: &Token, reached: &mut Reached<'a>) {
+        if let Some(template) = self.templates.get(key.as_str()) {
+            if reached.templates.insert(template.key.as_str()) {
+                self.reach_block(&template.block, reached);
+            }
+        }
+    }
+
+    /// Follow the types and templates that are used in `block`.
+    fn reach_block<'a>(&'a self, block: &Block, reached: &mut Reached<'a>) {
+        for item in block.iter_items() {
+            match item {
+                BlockItem::Field(Field(key, _, bv)) => {
+                    self.reach_type(key, reached);
+                    match bv {
+                        // `using = template`, or a widget property such as `item = template`
+                        BV::Value(value) => self.reach_template(value, reached),
+                        BV::Block(block) => self.reach_block(block, reached),
+                    }
+                }
+                BlockItem::Block(block) => self.reach_block(block, reached),
+                BlockItem::Value(_) => (),
+            }
+        }
+    }
+
     pub fn iter_names(&self) -> impl Iterator<Item = &Token> {
         self.widget_names.iter()
     }
 
+    /// Process a widget [`Block`] that was not expanded during loading, such as a recursive one.
+    pub fn expand_block(&self, from: GuiBlockFrom, block: &Block) -> Arc<GuiBlock> {
+        GuiBlock::from_block(from, block, &self.types, &self.templates)
+    }
+
+    pub fn expand_template(&self, key: &str) -> Option<Arc<GuiBlock>> {
+        self.templates.get(key).map(|template| template.gui_block(&self.types, &self.templates))
+    }
+
+    /// Return the widget named `name` in `.gui` file syntax, with all its types, templates and
+    /// blockoverrides expanded. Returns `None` if there is no such top-level widget.
+    pub fn dump_widget(&self, name: &str) -> Option<String> {
+        for widgets in self.files.values() {
+            for widget in widgets {
+                if widget.block.get_field_value("name").is_some_and(|n| n.is(name)) {
+                    let gui_block =
+                        self.expand_block(GuiBlockFrom::WidgetKey(&widget.key), &widget.block);
+                    let mut out = String::new();
+                    gui_block.dump(widget.key.as_str(), 0, &mut out);
+                    return Some(out);
+                }
+            }
+        }
+        None
+    }
+
+    /// Check that the static texts in the mod's top-level widgets fit in their widgets. This
+    /// measures the texts with the fonts, so it is done by [`Everything::check_fonts`].
+    pub fn check_text_widths(&self, data: &Everything) {
+        for widgets in self.files.values() {
+            for widget in widgets.iter().filter(|widget| widget.key.loc.kind == FileKind::Mod) {
+                let gui_block =
+                    self.expand_block(GuiBlockFrom::WidgetKey(&widget.key), &widget.block);
+                check_text_widths(&widget.key, &gui_block, data);
+            }
+        }
+    }
+
     pub fn validate(&self, data: &Everything) {
         for items in self.files.values() {
             for item in items {
@@ -291,6 +539,7 @@ impl FileHandler<Block> for Gui {
             LayerBody(Token),
         }
 
+        self.scan_expressions(&block);
         let mut expecting = Expecting::Widget;
 
         for item in block.drain() {
@@ -423,7 +672,8 @@ impl GuiWidget {
             &data.gui.types,
             &data.gui.templates,
         );
-        guiblock.validate(None, data);
+        guiblock.validate(None, &DataContext::default(), data);
+        check_layout(&self.key, &guiblock, data);
     }
 }
 
@@ -486,20 +736,29 @@ impl TextFormat {
 
 #[derive(Debug)]
 pub struct GuiTemplate {
-    #[allow(dead_code)] // key is not used, but don't want to remove the code for storing it
     key: Token,
     block: Block,
     gui_block: RwLock<Option<Arc<GuiBlock>>>,
+    /// Whether anything refers to this template. Used for the --unused output.
+    used: AtomicBool,
 }
 
 impl GuiTemplate {
     pub fn new(key: Token, block: Block) -> Self {
-        Self { key, block, gui_block: RwLock::new(None) }
+        Self { key, block, gui_block: RwLock::new(None), used: AtomicBool::new(false) }
+    }
+
+    pub fn mark_used(&self) {
+        self.used.store(true, Relaxed);
     }
 
     pub fn validate(&self, data: &Everything) {
         // unwrapping the Option is safe because they were all calculated during finalize
-        self.gui_block.read().unwrap().as_ref().unwrap().validate(None, data);
+        self.gui_block.read().unwrap().as_ref().unwrap().validate(
+            None,
+            &DataContext::unknown(),
+            data,
+        );
     }
 
     pub fn calculate_gui_block(
@@ -552,6 +811,8 @@ pub struct GuiType {
     #[allow(clippy::option_option)] // TODO
     builtin: RwLock<Option<Option<BuiltinWidget>>>,
     gui_block: RwLock<Option<Arc<GuiBlock>>>,
+    /// Whether anything refers to this type. Used for the --unused output.
+    used: AtomicBool,
 }
 
 impl GuiType {
@@ -567,9 +828,14 @@ impl GuiType {
             is_builtin_wrapper,
             builtin: RwLock::new(builtin),
             gui_block: RwLock::new(None),
+            used: AtomicBool::new(false),
         }
     }
 
+    pub fn mark_used(&self) {
+        self.used.store(true, Relaxed);
+    }
+
     pub fn validate(&self, data: &Everything) {
         data.verify_exists(Item::GuiType, &self.base);
         let base_lc = Lowercase::new(self.base.as_str());
@@ -580,7 +846,10 @@ impl GuiType {
                 .push();
         }
         // Unwrapping the Option is safe because they were all calculated during finalize
-        self.gui_block.read().unwrap().as_ref().unwrap().validate(None, data);
+        let gui_block = self.gui_block.read().unwrap();
+        let gui_block = gui_block.as_ref().unwrap();
+        // The layout is checked only from top-level widgets, which include their types.
+        gui_block.validate(None, &DataContext::unknown(), data);
     }
 
     pub fn calculate_builtin(
@@ -675,3 +944,25 @@ impl GuiLayer {
         vd.field_value("priority");
     }
 }
+
+/// The gui types and templates found while walking from the used widgets.
+#[derive(Default)]
+struct Reached<'a> {
+    types: TigerHashSet<&'a str>,
+    templates: TigerHashSet<&'a str>,
+}
+
+fn report_unused(header: &str, tokens: &mut [&Token]) {
+    tokens.sort_unstable_by_key(|token| &token.loc);
+    let mut printed_header = false;
+    for token in tokens {
+        if !printed_header && will_maybe_log(*token, ErrorKey::UnusedGui) {
+            warn_header(ErrorKey::UnusedGui, header);
+            printed_header = true;
+        }
+        warn_abbreviated(*token, ErrorKey::UnusedGui);
+    }
+    if printed_header {
+        warn_header(ErrorKey::UnusedGui, "\n");
+    }
+}
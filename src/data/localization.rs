@@ -3,6 +3,7 @@
 use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::ffi::OsStr;
+use std::fmt::Write as _;
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
@@ -14,7 +15,7 @@ use crate::block::Block;
 #[cfg(feature = "ck3")]
 use crate::ck3::tables::localization::{BUILTIN_MACROS_CK3, COMPLEX_TOOLTIPS_CK3};
 use crate::context::ScopeContext;
-use crate::datatype::{validate_datatypes, CodeChain, Datatype};
+use crate::datatype::{validate_datatypes, validate_format, CodeChain, Datatype};
 use crate::everything::Everything;
 use crate::fileset::{FileEntry, FileHandler, FileKind};
 use crate::game::Game;
@@ -28,6 +29,7 @@ use crate::report::{
     err, report, warn, warn_abbreviated, warn_header, will_maybe_log, ErrorKey, Severity,
 };
 use crate::scopes::Scopes;
+use crate::spelling::Spellchecker;
 use crate::token::Token;
 #[cfg(feature = "vic3")]
 use crate::vic3::tables::localization::BUILTIN_MACROS_VIC3;
@@ -43,6 +45,8 @@ pub struct Localization {
     mod_langs: Vec<&'static str>,
     /// Database of all localizations, indexed first by language and then by localization key.
     locas: TigerHashMap<&'static str, TigerHashMap<String, LocaEntry>>,
+    /// Dictionaries for spellchecking, if configured.
+    spellchecker: Option<Spellchecker>,
 }
 
 /// List of languages that are supported by the game engine.
@@ -189,9 +193,9 @@ pub enum LocaValue {
     // at those.
     Macro(Vec<MacroValue>),
     Concat(Vec<LocaValue>),
-    #[allow(dead_code)] // the Token is only used for ck3
     Text(Token),
-    Markup,
+    // The token is the markup without the `#`, such as `bold` or `color:{1,1,1}`.
+    Markup(Token),
     MarkupEnd,
     Tooltip(Token),
     // Tag, key, value. Tag can influence how tooltip is looked up. If tag is `GAME_TRAIT`,
@@ -215,6 +219,302 @@ pub enum MacroValue {
     Keyword(Token),
 }
 
+/// The parts of a localization value that should be the same in every translation of it.
+#[derive(Debug, Default)]
+struct Placeholders {
+    macros: Vec<String>,
+    code: Vec<String>,
+    icons: Vec<String>,
+    tooltips: Vec<String>,
+    markup: usize,
+    markup_end: usize,
+}
+
+impl Placeholders {
+    /// Returns `None` if the value could not be parsed.
+    fn from_entry(entry: &LocaEntry) -> Option<Self> {
+        let mut placeholders = Self::default();
+        if let LocaValue::Macro(v) = &entry.value {
+            // Leave the macros in as plain text, and parse the rest of the value around them.
+            let mut tokens = Vec::new();
+            for macrovalue in v {
+                match macrovalue {
+                    MacroValue::Text(token) => tokens.push(token),
+                    MacroValue::Keyword(keyword) => {
+                        placeholders.macros.push(format!("${keyword}$"));
+                        tokens.push(keyword);
+                    }
+                }
+            }
+            if !tokens.is_empty() {
+                placeholders.collect(&ValueParser::new(tokens).parse())?;
+            }
+        } else {
+            placeholders.collect(&entry.value)?;
+        }
+        for v in [
+            &mut placeholders.macros,
+            &mut placeholders.code,
+            &mut placeholders.icons,
+            &mut placeholders.tooltips,
+        ] {
+            v.sort_unstable();
+        }
+        Some(placeholders)
+    }
+
+    fn collect(&mut self, value: &LocaValue) -> Option<()> {
+        match value {
+            LocaValue::Concat(v) => {
+                for value in v {
+                    self.collect(value)?;
+                }
+            }
+            LocaValue::Code(chain, _) => self.code.push(format!("[{chain}]")),
+            LocaValue::Icon(token) => self.icons.push(format!("@{token}!")),
+            LocaValue::Tooltip(token) | LocaValue::ComplexTooltip(_, token) => {
+                self.tooltips.push(token.to_string());
+            }
+            LocaValue::Markup(_) => self.markup += 1,
+            LocaValue::MarkupEnd => self.markup_end += 1,
+            LocaValue::Error => return None,
+            LocaValue::Macro(_) | LocaValue::Text(_) => (),
+        }
+        Some(())
+    }
+}
+
+/// The output formats for [`Localization::preview`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreviewFormat {
+    Text,
+    Html,
+    /// Only the text, without code, icons or markup. Used to measure how wide a text is.
+    Plain,
+}
+
+/// How deep macros are expanded before giving up. This protects against loops.
+const MAX_PREVIEW_DEPTH: usize = 20;
+
+/// Renders localization values for [`Localization::preview`].
+struct Renderer<'a> {
+    format: PreviewFormat,
+    lang: &'static str,
+    hash: &'a TigerHashMap<String, LocaEntry>,
+    data: &'a Everything,
+    output: String,
+    /// The number of HTML tags opened by markup that still have to be closed.
+    open: usize,
+}
+
+impl Renderer<'_> {
+    fn render_entry(&mut self, entry: &LocaEntry, depth: usize) {
+        if let LocaValue::Macro(_) = entry.value {
+            let mut tokens = Vec::new();
+            self.expand_macros(entry, depth, &mut tokens);
+            let tokens: Vec<&Token> = tokens.iter().collect();
+            if !tokens.is_empty() {
+                self.render(&ValueParser::new(tokens).parse());
+            }
+        } else {
+            self.render(&entry.value);
+        }
+    }
+
+    /// Replace the `$key$` macros in `entry` with the values of those keys. Unknown keys are
+    /// left as they are.
+    fn expand_macros(&self, entry: &LocaEntry, depth: usize, tokens: &mut Vec<Token>) {
+        if let LocaValue::Macro(v) = &entry.value {
+            for macrovalue in v {
+                match macrovalue {
+                    MacroValue::Text(token) => tokens.push(token.clone()),
+                    MacroValue::Keyword(keyword) => match self.hash.get(keyword.as_str()) {
+                        Some(entry) if depth < MAX_PREVIEW_DEPTH => {
+                            self.expand_macros(entry, depth + 1, tokens);
+                        }
+                        _ => tokens.push(Token::new(&format!("${keyword}$"), keyword.loc)),
+                    },
+                }
+            }
+        } else if let Some(orig) = &entry.orig {
+            tokens.push(orig.clone());
+        }
+    }
+
+    fn render(&mut self, value: &LocaValue) {
+        match value {
+            LocaValue::Concat(v) => {
+                for value in v {
+                    self.render(value);
+                }
+            }
+            LocaValue::Text(token) => self.push_text(token.as_str()),
+            LocaValue::Markup(token) => {
+                if self.format == PreviewFormat::Html {
+                    let classes: Vec<&str> = token
+                        .as_str()
+                        .split(';')
+                        .filter_map(|part| part.split(':').next())
+                        .filter(|name| !name.is_empty() && !name.eq_ignore_ascii_case("tooltip"))
+                        .collect();
+                    let _ = write!(self.output, "<span class=\"{}\">", classes.join(" "));
+                    self.open += 1;
+                }
+            }
+            LocaValue::MarkupEnd => {
+                if self.format == PreviewFormat::Html && self.open > 0 {
+                    self.output.push_str("</span>");
+                    self.open -= 1;
+                }
+            }
+            LocaValue::Icon(token) => match self.format {
+                PreviewFormat::Text => {
+                    let _ = write!(self.output, "({token})");
+                }
+                PreviewFormat::Html => {
+                    let _ = write!(self.output, "<span class=\"icon\">{token}</span>");
+                }
+                PreviewFormat::Plain => (),
+            },
+            LocaValue::Code(chain, format) => self.render_code(chain, format.as_ref()),
+            LocaValue::Macro(v) => {
+                // Only happens in values that could not be expanded.
+                for macrovalue in v {
+                    match macrovalue {
+                        MacroValue::Text(token) => self.push_text(token.as_str()),
+                        MacroValue::Keyword(keyword) => self.push_text(&format!("${keyword}$")),
+                    }
+                }
+            }
+            LocaValue::Tooltip(_) | LocaValue::ComplexTooltip(_, _) | LocaValue::Error => (),
+        }
+    }
+
+    #[allow(unused_variables)] // format is only used for ck3
+    fn render_code(&mut self, chain: &CodeChain, format: Option<&Token>) {
+        // Game concepts are shown with their display names.
+        #[cfg(feature = "ck3")]
+        if Game::is_ck3() && format.is_some_and(|f| f.as_str().contains(['E', 'e'])) {
+            if let Some(name) = chain.as_gameconcept() {
+                let open = self.open;
+                if self.format == PreviewFormat::Html {
+                    self.output.push_str("<span class=\"concept\">");
+                    self.open += 1;
+                }
+                match self.hash.get(&format!("game_concept_{name}")) {
+                    Some(entry) => self.render_entry(entry, 1),
+                    None => self.push_text(name.as_str()),
+                }
+                while self.open > open {
+                    self.output.push_str("</span>");
+                    self.open -= 1;
+                }
+                return;
+            }
+        }
+
+        if self.format == PreviewFormat::Plain {
+            return;
+        }
+        let placeholder = match chain.codes.split_last() {
+            Some((last, [])) => format!("<{}>", describe_function(last.name.as_str())),
+            Some((last, init)) => {
+                let init = CodeChain { codes: init.to_vec() };
+                let key = &chain.codes[0].name;
+                let mut sc = ScopeContext::new_unrooted(Scopes::all(), key);
+                sc.set_strict_scopes(false);
+                let dtype = validate_datatypes(
+                    &init,
+                    self.data,
+                    &mut sc,
+                    Datatype::Unknown,
+                    self.lang,
+                    None,
+                    true,
+                );
+                let name = describe_function(last.name.as_str());
+                if dtype == Datatype::Unknown {
+                    format!("<{name}>")
+                } else {
+                    format!("<{dtype} {name}>")
+                }
+            }
+            None => return,
+        };
+        match self.format {
+            PreviewFormat::Text | PreviewFormat::Plain => self.output.push_str(&placeholder),
+            PreviewFormat::Html => {
+                self.output.push_str("<span class=\"code\">");
+                self.push_text(&placeholder);
+                self.output.push_str("</span>");
+            }
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        match self.format {
+            PreviewFormat::Text | PreviewFormat::Plain => self.output.push_str(text),
+            PreviewFormat::Html => {
+                for c in text.chars() {
+                    match c {
+                        '&' => self.output.push_str("&amp;"),
+                        '<' => self.output.push_str("&lt;"),
+                        '>' => self.output.push_str("&gt;"),
+                        '"' => self.output.push_str("&quot;"),
+                        '\n' => self.output.push_str("<br>\n"),
+                        _ => self.output.push(c),
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(mut self) -> String {
+        for _ in 0..self.open {
+            self.output.push_str("</span>");
+        }
+        self.output
+    }
+}
+
+/// Turn a datafunction name like `GetFirstName` into a description like `first name`.
+fn describe_function(name: &str) -> String {
+    let name = name.strip_prefix("Get").filter(|s| !s.is_empty()).unwrap_or(name);
+    let mut description = String::new();
+    for c in name.chars() {
+        if c.is_uppercase() && !description.is_empty() {
+            description.push(' ');
+        }
+        description.extend(c.to_lowercase());
+    }
+    description
+}
+
+fn collect_text(value: &LocaValue, texts: &mut Vec<Token>) {
+    match value {
+        LocaValue::Concat(v) => {
+            for value in v {
+                collect_text(value, texts);
+            }
+        }
+        LocaValue::Text(token) => texts.push(token.clone()),
+        _ => (),
+    }
+}
+
+/// Return the items of sorted list `a` that are not matched by an item in sorted list `b`.
+fn sorted_difference<'a>(a: &'a [String], b: &[String]) -> Vec<&'a str> {
+    let mut result = Vec::new();
+    let mut b = b.iter().peekable();
+    for item in a {
+        while b.next_if(|other| *other < item).is_some() {}
+        if b.next_if(|other| *other == item).is_none() {
+            result.push(item.as_str());
+        }
+    }
+    result
+}
+
 fn get_file_lang(filename: &OsStr) -> Option<&'static str> {
     // Deliberate discrepancy here between the check and the error msg below.
     // `l_{}` anywhere in the filename works, but `_l_{}.yml` is still recommended.
@@ -236,6 +536,11 @@ impl Localization {
         true
     }
 
+    /// The languages that the mod has localization for.
+    pub fn mod_langs(&self) -> &[&'static str] {
+        &self.mod_langs
+    }
+
     pub fn iter_keys(&self) -> impl Iterator<Item = &Token> {
         self.mod_langs
             .iter()
@@ -362,7 +667,6 @@ impl Localization {
                     Self::check_loca_code(value, data, sc, lang);
                 }
             }
-            // TODO: validate the formatting codes
             LocaValue::Code(chain, format) => {
                 // |E is the formatting used for game concepts in ck3
                 #[cfg(feature = "ck3")]
@@ -381,7 +685,7 @@ impl Localization {
 
                 // TODO: datatype is not really Unknown here, it should be a CString or CFixedPoint or some kind of number.
                 // But we can't express that yet.
-                validate_datatypes(
+                let dtype = validate_datatypes(
                     chain,
                     data,
                     sc,
@@ -390,6 +694,9 @@ impl Localization {
                     format.as_ref(),
                     false,
                 );
+                if let Some(format) = format {
+                    validate_format(format, dtype, data);
+                }
             }
             LocaValue::Tooltip(token) => {
                 // TODO: should this be validated with validate_localization_sc ? (remember to avoid infinite loops)
@@ -515,6 +822,129 @@ impl Localization {
                 }
             }
         });
+        self.check_translations();
+        if let Some(spellchecker) = &self.spellchecker {
+            spellchecker.check(&self.mod_texts());
+        }
+    }
+
+    /// Compare the translations of each key against the english version, and warn about
+    /// translations that lose or gain placeholders such as `$macros$`, `[code]` and `@icons!`.
+    pub fn check_translations(&self) {
+        let Some(english) = self.locas.get("english") else {
+            return;
+        };
+        for lang in &self.mod_langs {
+            if *lang == "english" {
+                continue;
+            }
+            let Some(hash) = self.locas.get(lang) else {
+                continue;
+            };
+            let mut entries: Vec<&LocaEntry> = hash.values().collect();
+            entries.sort_unstable();
+            for entry in entries {
+                if let Some(orig) = english.get(entry.key.as_str()) {
+                    Self::check_translation(entry, orig, lang);
+                }
+            }
+        }
+    }
+
+    fn check_translation(entry: &LocaEntry, english: &LocaEntry, lang: &str) {
+        let (Some(ours), Some(theirs)) =
+            (Placeholders::from_entry(entry), Placeholders::from_entry(english))
+        else {
+            return;
+        };
+        let key = &entry.key;
+        for (what, ours, theirs, weak) in [
+            ("", &ours.macros, &theirs.macros, false),
+            ("", &ours.code, &theirs.code, true),
+            ("", &ours.icons, &theirs.icons, false),
+            ("tooltip ", &ours.tooltips, &theirs.tooltips, false),
+        ] {
+            for (diff, verb) in [
+                (sorted_difference(theirs, ours), "lacks"),
+                (sorted_difference(ours, theirs), "adds"),
+            ] {
+                if !diff.is_empty() {
+                    let msg = format!(
+                        "{lang} translation of {key} {verb} {what}{}",
+                        stringify_list(&diff)
+                    );
+                    let mut report = warn(ErrorKey::Localization);
+                    if weak {
+                        // Translations sometimes need different code for grammatical reasons.
+                        report = report.weak();
+                    }
+                    report.msg(msg).loc(key).loc_msg(&english.key, "english version").push();
+                }
+            }
+        }
+        if ours.markup != ours.markup_end && theirs.markup == theirs.markup_end {
+            let msg = format!("{lang} translation of {key} has unbalanced #markup");
+            let info = format!(
+                "it opens {} markup spans and closes {}, unlike the english version",
+                ours.markup, ours.markup_end
+            );
+            warn(ErrorKey::Markup)
+                .msg(msg)
+                .info(info)
+                .loc(key)
+                .loc_msg(&english.key, "english version")
+                .push();
+        }
+    }
+
+    /// Return the plain text parts of the mod's own localization entries, by language.
+    /// Code, icons, tooltips and markup are left out.
+    pub fn mod_texts(&self) -> Vec<(&'static str, Vec<Token>)> {
+        let mut result = Vec::new();
+        for lang in &self.mod_langs {
+            let Some(hash) = self.locas.get(lang) else {
+                continue;
+            };
+            let mut entries: Vec<&LocaEntry> =
+                hash.values().filter(|e| e.key.loc.kind == FileKind::Mod).collect();
+            entries.sort_unstable();
+            let mut texts = Vec::new();
+            for entry in entries {
+                if let LocaValue::Macro(v) = &entry.value {
+                    let tokens: Vec<&Token> = v
+                        .iter()
+                        .filter_map(|macrovalue| match macrovalue {
+                            MacroValue::Text(token) => Some(token),
+                            MacroValue::Keyword(_) => None,
+                        })
+                        .collect();
+                    if !tokens.is_empty() {
+                        collect_text(&ValueParser::new(tokens).parse(), &mut texts);
+                    }
+                } else {
+                    collect_text(&entry.value, &mut texts);
+                }
+            }
+            result.push((*lang, texts));
+        }
+        result
+    }
+
+    /// Render the localization `key` in language `lang` as it would roughly look in the game.
+    /// Macros are expanded, code is shown as a placeholder for the kind of value it produces,
+    /// and icons are shown by name. Returns `None` if the key does not exist in that language.
+    pub fn preview(
+        &self,
+        key: &str,
+        lang: &str,
+        format: PreviewFormat,
+        data: &Everything,
+    ) -> Option<String> {
+        let (lang, hash) = self.locas.get_key_value(lang)?;
+        let entry = hash.get(key)?;
+        let mut renderer = Renderer { format, lang, hash, data, output: String::new(), open: 0 };
+        renderer.render_entry(entry, 0);
+        Some(renderer.finish())
     }
 
     pub fn mark_category_used(&self, prefix: &str) {
@@ -603,6 +1033,10 @@ impl FileHandler<(&'static str, Vec<LocaEntry>)> for Localization {
             }
             self.check_langs = langs;
         }
+
+        if let Some(block) = config.get_field_block("spellcheck") {
+            self.spellchecker = Some(Spellchecker::from_config(block));
+        }
     }
 
     fn subpath(&self) -> PathBuf {
@@ -693,6 +1127,7 @@ impl Default for Localization {
             check_langs: Vec::from(KNOWN_LANGUAGES),
             locas: TigerHashMap::default(),
             mod_langs: Vec::default(),
+            spellchecker: None,
         }
     }
 }
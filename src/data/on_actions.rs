@@ -119,9 +119,11 @@ impl OnAction {
         } else {
             sc = ScopeContext::new(Scopes::non_primitive(), &self.key);
             sc.set_strict_scopes(false);
+            sc.set_origin(Item::OnAction, &self.key);
         }
 
         validate_on_action(&self.block, data, &mut sc);
+        data.event_chains.record_inputs(Item::OnAction, &self.key, &self.block, &sc);
     }
 }
 
@@ -199,6 +201,7 @@ pub fn validate_on_action(block: &Block, data: &Everything, sc: &mut ScopeContex
         vd.multi_field_validated_block_sc("delay", sc, validate_duration);
         for token in vd.values() {
             data.verify_exists(Item::OnAction, token);
+            data.event_chains.record_call(Item::OnAction, token, sc);
         }
         count += 1;
         #[cfg(feature = "ck3")] // Verified: this is only a problem in CK3
@@ -220,6 +223,7 @@ pub fn validate_on_action(block: &Block, data: &Everything, sc: &mut ScopeContex
                 continue;
             }
             data.verify_exists(Item::OnAction, token);
+            data.event_chains.record_call(Item::OnAction, token, sc);
         }
         count += 1;
         #[cfg(feature = "ck3")] // Verified: this is only a problem in CK3
@@ -236,6 +240,7 @@ pub fn validate_on_action(block: &Block, data: &Everything, sc: &mut ScopeContex
         let mut vd = Validator::new(b, data);
         for token in vd.values() {
             data.verify_exists(Item::OnAction, token);
+            data.event_chains.record_call(Item::OnAction, token, sc);
         }
         count += 1;
         #[cfg(feature = "ck3")] // Verified: this is only a problem in CK3
@@ -47,6 +47,10 @@ impl ScriptValues {
         self.script_values.values().map(|item| &item.key)
     }
 
+    pub fn get_bv(&self, key: &str) -> Option<&BV> {
+        self.script_values.get(key).map(|item| &item.bv)
+    }
+
     pub fn validate(&self, data: &Everything) {
         for item in self.script_values.values() {
             item.validate(data);
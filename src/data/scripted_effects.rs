@@ -116,6 +116,10 @@ impl Effect {
         Self { key, block, cache: MacroCache::default(), scope_override }
     }
 
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+
     pub fn validate(&self, data: &Everything) {
         if self.block.source.is_none() {
             let mut sc = ScopeContext::new_unrooted(Scopes::all(), &self.key);
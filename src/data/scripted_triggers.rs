@@ -120,6 +120,10 @@ impl Trigger {
         Self { key, block, cache: MacroCache::default(), scope_override }
     }
 
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+
     pub fn validate(&self, data: &Everything) {
         // We could let triggers get "naturally" validated by being called from other places,
         // but we want to also validate triggers that aren't called from anywhere yet.
@@ -22,6 +22,7 @@ use crate::report::err;
 use crate::report::{warn, ErrorKey};
 use crate::scopes::Scopes;
 use crate::token::Token;
+use crate::variables::{VariableKind, VariableUse};
 
 // Load the game-specific datatype definitions
 #[cfg(feature = "ck3")]
@@ -233,6 +234,31 @@ impl CodeChain {
     }
 }
 
+impl Display for CodeChain {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        for (i, code) in self.codes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", code.name)?;
+            if !code.arguments.is_empty() {
+                write!(f, "(")?;
+                for (j, arg) in code.arguments.iter().enumerate() {
+                    if j > 0 {
+                        write!(f, ",")?;
+                    }
+                    match arg {
+                        CodeArg::Chain(chain) => write!(f, "{chain}")?,
+                        CodeArg::Literal(token) => write!(f, "'{token}'")?,
+                    }
+                }
+                write!(f, ")")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// [`Arg`] is the counterpart to [`CodeArg`]. Where `CodeArg` represents an actual argument given
 /// in a codechain string, the `Arg` represents what kind of argument is expected by a promote or
 /// function.
@@ -352,6 +378,8 @@ fn validate_argument(
 /// * `expect_promote` is true iff the chain is expected to end on a promote rather than on a function.
 ///   Promotes and functions are very similar but they are defined separately in the datafunction tables
 ///   and usually only a function can end a chain.
+///
+/// Returns the datatype of the chain's result, or `Datatype::Unknown` if it couldn't be determined.
 pub fn validate_datatypes(
     chain: &CodeChain,
     data: &Everything,
@@ -360,7 +388,24 @@ pub fn validate_datatypes(
     lang: &'static str,
     format: Option<&Token>,
     expect_promote: bool,
-) {
+) -> Datatype {
+    validate_datatypes_from(None, chain, data, sc, expect_type, lang, format, expect_promote)
+}
+
+/// Like [`validate_datatypes`], but if `start` is given, the first code of the chain is taken to
+/// be a promote with that type, and validation continues from there. This is for chains like
+/// `[Character.GetName]` in the gui, whose first code refers to an inherited datacontext.
+#[allow(clippy::too_many_arguments)]
+pub fn validate_datatypes_from(
+    start: Option<Datatype>,
+    chain: &CodeChain,
+    data: &Everything,
+    sc: &mut ScopeContext,
+    expect_type: Datatype,
+    lang: &'static str,
+    format: Option<&Token>,
+    expect_promote: bool,
+) -> Datatype {
     let mut curtype = Datatype::Unknown;
     #[allow(unused_mut)] // imperator does not need the mut
     let mut codes = Cow::from(&chain.codes[..]);
@@ -368,6 +413,12 @@ pub fn validate_datatypes(
     let mut macro_count = 0;
     // Have to loop with `while` instead of `for` because the array can mutate during the loop because of macro substitution
     let mut i = 0;
+    if let Some(start) = start {
+        if codes.len() > 1 {
+            curtype = start;
+            i = 1;
+        }
+    }
     while i < codes.len() {
         #[cfg(any(feature = "ck3", feature = "vic3"))]
         if Game::is_ck3() || Game::is_vic3() {
@@ -378,11 +429,11 @@ pub fn validate_datatypes(
                         let msg =
                             format!("substituted data bindings {macro_count} times, giving up");
                         err(ErrorKey::Macro).msg(msg).loc(&codes[i].name).push();
-                        return;
+                        return Datatype::Unknown;
                     }
                     codes.to_mut().splice(i..=i, replacement.codes);
                 } else {
-                    return;
+                    return Datatype::Unknown;
                 }
             }
         }
@@ -396,7 +447,7 @@ pub fn validate_datatypes(
         if code.name.is("") {
             // TODO: verify if the game engine is okay with this
             warn(ErrorKey::Datafunctions).msg("empty fragment").loc(&code.name).push();
-            return;
+            return Datatype::Unknown;
         }
 
         let lookup_gf = lookup_global_function(code.name.as_str());
@@ -433,7 +484,7 @@ pub fn validate_datatypes(
                 LookupResult::WrongType => {
                     let msg = format!("{} cannot follow a {curtype} promote", code.name);
                     warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                    return;
+                    return Datatype::Unknown;
                 }
                 LookupResult::NotFound => (),
             }
@@ -447,7 +498,7 @@ pub fn validate_datatypes(
                 LookupResult::WrongType => {
                     let msg = format!("{} cannot follow a {curtype} promote", code.name);
                     warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                    return;
+                    return Datatype::Unknown;
                 }
                 LookupResult::NotFound => (),
             }
@@ -459,33 +510,33 @@ pub fn validate_datatypes(
             if is_first && (p_found || f_found) && !gp_found && !gf_found {
                 let msg = format!("{} cannot be the first in a chain", code.name);
                 warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                return;
+                return Datatype::Unknown;
             }
             if is_last && (gp_found || p_found) && !gf_found && !f_found && !expect_promote {
                 let msg = format!("{} cannot be last in a chain", code.name);
                 warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                return;
+                return Datatype::Unknown;
             }
             if expect_promote && (gf_found || f_found) {
                 let msg = format!("{} cannot be used in this field", code.name);
                 warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                return;
+                return Datatype::Unknown;
             }
             if !is_first && (gp_found || gf_found) && !p_found && !f_found {
                 let msg = format!("{} must be the first in a chain", code.name);
                 warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                return;
+                return Datatype::Unknown;
             }
             if !is_last && (gf_found || f_found) && !gp_found && !p_found {
                 let msg = format!("{} must be last in the chain", code.name);
                 warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                return;
+                return Datatype::Unknown;
             }
             // A catch-all condition if none of the above match
             if gp_found || gf_found || p_found || f_found {
                 let msg = format!("{} is improperly used here", code.name);
                 warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                return;
+                return Datatype::Unknown;
             }
         }
 
@@ -602,7 +653,7 @@ pub fn validate_datatypes(
             } else {
                 warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
             }
-            return;
+            return Datatype::Unknown;
         }
 
         // This `if let` skips this check if args is `Args::Unknown`
@@ -615,7 +666,7 @@ pub fn validate_datatypes(
                     code.arguments.len()
                 );
                 warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                return;
+                return Datatype::Unknown;
             }
         }
 
@@ -683,6 +734,29 @@ pub fn validate_datatypes(
             }
         }
 
+        if (code.name.is("GetVariable")
+            || code.name.is("GetLocalVariable")
+            || code.name.is("GetGlobalVariable")
+            || code.name.is("Var"))
+            && code.arguments.len() == 1
+        {
+            if let CodeArg::Literal(ref token) = code.arguments[0] {
+                let kind = VariableKind::from_key(code.name.as_str(), false);
+                data.variables.read(kind, token);
+                if let Some(next) = codes.get(i + 1) {
+                    if next.name.is("GetValue") || next.name.is("GetValueWithDefault") {
+                        data.variables.use_as(kind, token, VariableUse::Number);
+                    } else if let LookupResult::Found(_, rtype) =
+                        lookup_promote(next.name.as_str(), Datatype::Scope)
+                    {
+                        if scope_from_datatype(rtype).is_some() {
+                            data.variables.use_as(kind, token, VariableUse::Scope);
+                        }
+                    }
+                }
+            }
+        }
+
         if let Args::Args(a) = args {
             for (i, arg) in a.iter().enumerate() {
                 // Handle |E that contain a SelectLocalization that chooses between two gameconcepts
@@ -712,18 +786,59 @@ pub fn validate_datatypes(
                     let msg =
                         format!("{} returns {curtype} but a scope type is needed here", code.name);
                     warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                    return;
+                    return Datatype::Unknown;
                 }
             } else {
                 let msg =
                     format!("{} returns {curtype} but a {expect_type} is needed here", code.name);
                 warn(ErrorKey::Datafunctions).msg(msg).loc(&code.name).push();
-                return;
+                return Datatype::Unknown;
             }
         }
 
         i += 1;
     }
+    curtype
+}
+
+/// Format codes that only make sense for numbers: the number of decimals, percentages, and signs
+/// with colors.
+const NUMERIC_FORMAT_CODES: &[char] =
+    &['0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '%', '+', '-', '=', '*'];
+
+/// Format codes that change the case of the first letter.
+const CASE_FORMAT_CODES: &[char] = &['U', 'L'];
+
+/// Validate the formatting code that follows the `|` in a `[...]` expression, such as the `+1` in
+/// `[GetValue|+1]`. `dtype` is the datatype of the expression's result, which may be `Unknown`.
+///
+/// Letters that are not built in are text formats, usually colors, which must be defined in the
+/// `textformatting` of the gui files.
+pub fn validate_format(format: &Token, dtype: Datatype, data: &Everything) {
+    let mut numeric = None;
+    for c in format.as_str().chars() {
+        if NUMERIC_FORMAT_CODES.contains(&c) {
+            numeric.get_or_insert(c);
+        } else if CASE_FORMAT_CODES.contains(&c) || (Game::is_ck3() && (c == 'E' || c == 'e')) {
+            // E is the game concept link in ck3
+        } else if c.is_alphabetic() {
+            if !data.item_exists(Item::TextFormat, c.encode_utf8(&mut [0; 4])) {
+                let msg = format!("unknown format code `{c}` in `|{format}`");
+                let info = "letters in format codes must be text formats, such as the color codes";
+                warn(ErrorKey::Localization).msg(msg).info(info).loc(format).push();
+            }
+        } else {
+            let msg = format!("unknown format code `{c}` in `|{format}`");
+            warn(ErrorKey::Localization).msg(msg).loc(format).push();
+        }
+    }
+    if let Some(c) = numeric {
+        if matches!(dtype, Datatype::CString | Datatype::CUTF8String) {
+            let msg =
+                format!("format code `{c}` is for numbers, but this expression gives {dtype}");
+            warn(ErrorKey::Datafunctions).msg(msg).loc(format).push();
+        }
+    }
 }
 
 fn lookup_global_promote(lookup_name: &str) -> Option<(Args, Datatype)> {
@@ -748,6 +863,23 @@ fn lookup_global_promote(lookup_name: &str) -> Option<(Args, Datatype)> {
     None
 }
 
+/// Return the datatype if `name` is a datatype that is used directly as a global promote, which
+/// takes its value from the gui datacontext.
+pub fn datacontext_promote(name: &str) -> Option<Datatype> {
+    let global_promotes_map = match Game::game() {
+        #[cfg(feature = "ck3")]
+        Game::Ck3 => &crate::ck3::tables::datafunctions::GLOBAL_PROMOTES_MAP,
+        #[cfg(feature = "vic3")]
+        Game::Vic3 => &crate::vic3::tables::datafunctions::GLOBAL_PROMOTES_MAP,
+        #[cfg(feature = "imperator")]
+        Game::Imperator => &crate::imperator::tables::datafunctions::GLOBAL_PROMOTES_MAP,
+    };
+    if global_promotes_map.contains_key(name) {
+        return None;
+    }
+    Datatype::from_str(name).ok()
+}
+
 fn lookup_global_function(lookup_name: &str) -> Option<(Args, Datatype)> {
     let global_functions_map = match Game::game() {
         #[cfg(feature = "ck3")]
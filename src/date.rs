@@ -13,9 +13,36 @@ pub struct Date {
 }
 
 impl Date {
-    pub fn new(year: i16, month: i8, day: i8) -> Self {
+    pub const fn new(year: i16, month: i8, day: i8) -> Self {
         Date { year, month, day }
     }
+
+    #[allow(dead_code)] // Not used by all games
+    pub fn ymd(self) -> (i16, i8, i8) {
+        (self.year, self.month, self.day)
+    }
+
+    /// Return the number of whole years from `earlier` to this date, such as a character's age
+    /// if `earlier` is their birth date. The result is negative if `earlier` is later.
+    #[allow(dead_code)] // Not used by all games
+    pub fn years_since(self, earlier: Date) -> i16 {
+        let mut years = self.year - earlier.year;
+        if (self.month, self.day) < (earlier.month, earlier.day) {
+            years -= 1;
+        }
+        years
+    }
+
+    /// Return the date that is `months` later than this one. The day of the month is kept as is.
+    #[allow(dead_code)] // Not used by all games
+    pub fn add_months(self, months: i16) -> Date {
+        let total = i32::from(self.year) * 12 + i32::from(self.month - 1) + i32::from(months);
+        #[allow(clippy::cast_possible_truncation)] // month is always in 0..12
+        let month = total.rem_euclid(12) as i8 + 1;
+        #[allow(clippy::cast_possible_truncation)]
+        let year = total.div_euclid(12) as i16;
+        Date { year, month, day: self.day }
+    }
 }
 
 impl FromStr for Date {
@@ -29,12 +29,18 @@ pub struct Db {
     flags: Vec<TigerHashSet<Token>>,
     /// Lowercased registry of database items and flags, for case insensitive lookups
     items_lc: Vec<TigerHashMap<Lowercase<'static>, &'static str>>,
+    /// Items of the types declared in schema files, indexed by the name of their type.
+    custom: TigerHashMap<&'static str, TigerHashMap<&'static str, DbEntry>>,
 }
 
 impl Default for Db {
     fn default() -> Self {
-        let mut db =
-            Self { database: Vec::default(), flags: Vec::default(), items_lc: Vec::default() };
+        let mut db = Self {
+            database: Vec::default(),
+            flags: Vec::default(),
+            items_lc: Vec::default(),
+            custom: TigerHashMap::default(),
+        };
         for _ in Item::iter() {
             db.database.push(TigerHashMap::default());
             db.flags.push(TigerHashSet::default());
@@ -79,6 +85,27 @@ impl Db {
         self.database[item as usize].insert(key.as_str(), DbEntry { key, block, kind });
     }
 
+    /// Add an item of the type `itype` that was declared in a schema file.
+    pub fn add_custom(
+        &mut self,
+        itype: &'static str,
+        key: Token,
+        block: Block,
+        kind: Box<dyn DbKind>,
+    ) {
+        let items = self.custom.entry(itype).or_default();
+        if let Some(other) = items.get(key.as_str()) {
+            if other.key.loc.kind >= key.loc.kind {
+                if other.block.equivalent(&block) {
+                    exact_dup_error(&key, &other.key, itype);
+                } else {
+                    dup_error(&key, &other.key, itype);
+                }
+            }
+        }
+        items.insert(key.as_str(), DbEntry { key, block, kind });
+    }
+
     pub fn add_flag(&mut self, item: Item, key: Token) {
         self.items_lc[item as usize].insert(Lowercase::new(key.as_str()), key.as_str());
         self.flags[item as usize].insert(key);
@@ -106,12 +133,21 @@ impl Db {
                 entry.kind.validate(&entry.key, &entry.block, data);
             });
         });
+        self.custom.par_iter().for_each(|(_, hash)| {
+            hash.par_iter().for_each(|(_, entry)| {
+                entry.kind.validate(&entry.key, &entry.block, data);
+            });
+        });
     }
 
     pub fn exists(&self, item: Item, key: &str) -> bool {
         self.database[item as usize].contains_key(key) || self.flags[item as usize].contains(key)
     }
 
+    pub fn custom_exists(&self, itype: &str, key: &str) -> bool {
+        self.custom.get(itype).is_some_and(|items| items.contains_key(key))
+    }
+
     pub fn exists_lc(&self, item: Item, key: &Lowercase) -> bool {
         self.items_lc[item as usize].contains_key(key)
     }
@@ -194,7 +230,6 @@ impl Db {
         }
     }
 
-    #[cfg(not(feature = "imperator"))]
     pub fn iter_key_block(&self, itype: Item) -> impl Iterator<Item = (&Token, &Block)> {
         self.database[itype as usize].values().map(|entry| (&entry.key, &entry.block))
     }
@@ -0,0 +1,241 @@
+//! Support for validating a mod as if only some of the DLCs were installed.
+//!
+//! The DLCs that are not in the `dlc = { installed = { ... } }` list of the config file are left out
+//! of the [`Fileset`]. References from the mod to items that only those DLCs define are then
+//! reported, unless the reference is guarded by a `has_dlc_feature` check, or `has_dlc` for
+//! Imperator. A `has_game_rule` check also guards a reference if the config lists the game rule
+//! setting among the DLC's features, for game rules that are only offered with that DLC.
+
+use std::path::{Path, PathBuf};
+
+use crate::block::{Block, BlockItem, BV};
+use crate::fileset::{FileKind, Fileset};
+use crate::game::Game;
+use crate::helpers::TigerHashMap;
+use crate::item::Item;
+use crate::pathtable::PathTableIndex;
+use crate::pdxfile::PdxFile;
+use crate::report::{err, ErrorKey};
+use crate::token::Token;
+
+/// Triggers that check for a DLC. Imperator uses `has_dlc` where the other games use
+/// `has_dlc_feature`.
+const DLC_TRIGGERS: &[&str] = &["has_dlc_feature", "has_dlc"];
+
+/// The trigger that checks for a game rule setting.
+const GAME_RULE_TRIGGER: &str = "has_game_rule";
+
+/// Fields whose contents decide whether the block they are in is used at all.
+const CONDITION_FIELDS: &[&str] = &[
+    "limit",
+    "trigger",
+    "potential",
+    "is_shown",
+    "is_valid",
+    "is_valid_showing_failures_only",
+    "is_possible",
+    "can_pick",
+    "allow",
+];
+
+/// The items and files that are only available in DLCs that are not installed.
+#[derive(Debug, Default)]
+pub struct ExcludedDlcs {
+    /// Keys defined at the top level of the excluded DLCs' script files, with the file they are in
+    /// and the DLC it belongs to.
+    keys: TigerHashMap<&'static str, Vec<(PathBuf, String)>>,
+    /// Paths of the excluded DLCs' files, with the DLC they belong to.
+    files: TigerHashMap<PathBuf, String>,
+    /// The `has_dlc_feature` values and game rule settings that guard each excluded DLC's items,
+    /// if configured.
+    features: TigerHashMap<&'static str, Vec<&'static str>>,
+    /// Parts of the mod's files that are only used if a DLC feature is present.
+    guards: Vec<Guard>,
+}
+
+/// A range of lines in a file that is inside a `has_dlc_feature` or `has_game_rule` check.
+#[derive(Debug)]
+struct Guard {
+    idx: PathTableIndex,
+    first_line: u32,
+    last_line: u32,
+    feature: Feature,
+}
+
+/// The value of a check that can guard DLC items.
+#[derive(Debug, Clone, Copy)]
+enum Feature {
+    Dlc(&'static str),
+    GameRule(&'static str),
+}
+
+impl Feature {
+    fn from_check(key: &Token, value: &Token) -> Option<Self> {
+        if DLC_TRIGGERS.contains(&key.as_str()) {
+            Some(Feature::Dlc(value.as_str()))
+        } else if key.is(GAME_RULE_TRIGGER) {
+            Some(Feature::GameRule(value.as_str()))
+        } else {
+            None
+        }
+    }
+
+    /// Whether this check guards the items of a DLC with the configured `features`, if any.
+    /// A game rule only counts if it is configured, because most game rules have nothing to do
+    /// with DLCs.
+    fn guards(self, features: Option<&Vec<&'static str>>) -> bool {
+        match self {
+            Feature::Dlc(feature) => features.map_or(true, |features| features.contains(&feature)),
+            Feature::GameRule(setting) => {
+                features.is_some_and(|features| features.contains(&setting))
+            }
+        }
+    }
+}
+
+impl ExcludedDlcs {
+    pub fn new(fileset: &Fileset, config: &Block) -> Self {
+        let mut excluded = Self::default();
+        let excluded_files = fileset.excluded_dlc_files();
+        if excluded_files.is_empty() {
+            return excluded;
+        }
+
+        for (entry, label) in excluded_files {
+            excluded.files.insert(entry.path().to_path_buf(), label.clone());
+            if entry.filename().to_string_lossy().ends_with(".txt") {
+                if let Some(block) = PdxFile::read_optional_bom(entry) {
+                    for (key, _) in block.iter_definitions() {
+                        let file = entry.path().to_path_buf();
+                        excluded.keys.entry(key.as_str()).or_default().push((file, label.clone()));
+                    }
+                }
+            }
+        }
+
+        if let Some(block) = config.get_field_block("dlc") {
+            if let Some(block) = block.get_field_block("features") {
+                for (key, block) in block.iter_definitions() {
+                    let features = block.iter_values().map(Token::as_str).collect();
+                    excluded.features.insert(key.as_str(), features);
+                }
+            }
+        }
+
+        for entry in fileset.get_files_under(Path::new("")) {
+            if entry.kind() == FileKind::Mod && entry.filename().to_string_lossy().ends_with(".txt")
+            {
+                if let Some(block) = PdxFile::read_optional_bom(entry) {
+                    collect_guards(&block, &mut excluded.guards);
+                }
+            }
+        }
+        excluded
+    }
+
+    /// Check if the missing item `key` is defined by an excluded DLC. If it is, and `token` is not
+    /// inside a matching `has_dlc_feature` check, report it. Returns true if the item was found in an
+    /// excluded DLC, in which case there is no need for another report.
+    pub fn check_missing(&self, itype: Item, key: &str, token: &Token) -> bool {
+        let dlc = if itype == Item::File {
+            self.files.get(Path::new(key))
+        } else {
+            let path = itype.path();
+            self.keys.get(key).and_then(|v| {
+                v.iter()
+                    .find(|(file, _)| !path.is_empty() && file.starts_with(path))
+                    .map(|(_, label)| label)
+            })
+        };
+        let Some(dlc) = dlc else {
+            return false;
+        };
+        if !self.is_guarded(token, dlc) {
+            let msg = format!("{itype} {key} is only defined in DLC {dlc}, which is not installed");
+            let trigger = if Game::is_imperator() { "has_dlc" } else { "has_dlc_feature" };
+            let info = format!(
+                "players without this DLC will be missing the item; check for the DLC with `{trigger}`"
+            );
+            err(ErrorKey::MissingItem).msg(msg).info(info).loc(token).push();
+        }
+        true
+    }
+
+    fn is_guarded(&self, token: &Token, dlc: &str) -> bool {
+        let features = self.features.get(dlc);
+        self.guards.iter().any(|guard| {
+            guard.idx == token.loc.idx
+                && (guard.first_line..=guard.last_line).contains(&token.loc.line)
+                && guard.feature.guards(features)
+        })
+    }
+}
+
+/// Record the blocks that are guarded by `has_dlc_feature` or `has_game_rule` checks, either
+/// directly or in one of their [`CONDITION_FIELDS`].
+fn collect_guards(block: &Block, guards: &mut Vec<Guard>) {
+    let mut features = Vec::new();
+    for item in block.iter_items() {
+        match item {
+            BlockItem::Field(field) => match field.bv() {
+                BV::Value(value) => {
+                    if let Some(feature) = Feature::from_check(field.key(), value) {
+                        features.push(feature);
+                    }
+                }
+                BV::Block(b) => {
+                    if CONDITION_FIELDS.contains(&field.key().as_str()) {
+                        find_features(b, &mut features);
+                    }
+                    collect_guards(b, guards);
+                }
+            },
+            BlockItem::Block(b) => collect_guards(b, guards),
+            BlockItem::Value(_) => (),
+        }
+    }
+    if !features.is_empty() {
+        let last_line = last_line(block);
+        for feature in features {
+            guards.push(Guard {
+                idx: block.loc.idx,
+                first_line: block.loc.line,
+                last_line,
+                feature,
+            });
+        }
+    }
+}
+
+/// Find all the `has_dlc_feature` and `has_game_rule` checks in a trigger block, except negated
+/// ones.
+fn find_features(block: &Block, features: &mut Vec<Feature>) {
+    for (key, bv) in block.iter_assignments_and_definitions() {
+        match bv {
+            BV::Value(value) => {
+                if let Some(feature) = Feature::from_check(key, value) {
+                    features.push(feature);
+                }
+            }
+            BV::Block(b) if !key.is("NOT") && !key.is("NOR") => find_features(b, features),
+            BV::Block(_) => (),
+        }
+    }
+}
+
+/// Return the last line that has anything from `block` on it.
+fn last_line(block: &Block) -> u32 {
+    let mut line = block.loc.line;
+    for item in block.iter_items() {
+        let item_line = match item {
+            BlockItem::Value(token) => token.loc.line,
+            BlockItem::Block(b) => last_line(b),
+            BlockItem::Field(field) => match field.bv() {
+                BV::Value(token) => token.loc.line,
+                BV::Block(b) => last_line(b),
+            },
+        };
+        line = line.max(item_line);
+    }
+    line
+}
@@ -10,7 +10,7 @@ use crate::item::Item;
 use crate::lowercase::Lowercase;
 use crate::report::{err, fatal, tips, warn, ErrorKey};
 use crate::scopes::{scope_iterator, Scopes};
-use crate::script_value::validate_script_value;
+use crate::script_value::{script_value_bounds, validate_script_value};
 use crate::token::Token;
 use crate::tooltipped::Tooltipped;
 #[cfg(any(feature = "ck3", feature = "imperator"))]
@@ -121,6 +121,7 @@ pub fn validate_effect_field(
     sc: &mut ScopeContext,
     tooltipped: Tooltipped,
 ) {
+    data.scope_probe.record(key, sc);
     if let Some(effect) = data.get_effect(key) {
         match bv {
             BV::Value(token) => {
@@ -140,6 +141,7 @@ pub fn validate_effect_field(
                         .loc(block)
                         .push();
                 } else {
+                    data.macro_calls.record(&effect.key, key, block);
                     let mut vec = Vec::new();
                     let mut vd = Validator::new(block, data);
                     for parm in &parms {
@@ -205,18 +207,19 @@ pub fn validate_effect_field(
                 }
             }
             Effect::ScriptValue | Effect::NonNegativeValue => {
-                if let Some(token) = bv.get_value() {
-                    if let Some(number) = token.get_number() {
-                        if matches!(effect, Effect::NonNegativeValue) && number < 0.0 {
-                            if key.is("add_gold") {
-                                let msg = "add_gold does not take negative numbers";
-                                let info = "try remove_short_term_gold instead";
-                                warn(ErrorKey::Range).msg(msg).info(info).loc(token).push();
-                            } else {
-                                let msg = format!("{key} does not take negative numbers");
-                                warn(ErrorKey::Range).msg(msg).loc(token).push();
-                            }
-                        }
+                if matches!(effect, Effect::NonNegativeValue) {
+                    let bounds = script_value_bounds(bv, data);
+                    if bounds.min.is_finite() && bounds.min < 0.0 {
+                        let msg = if bounds.max < 0.0 {
+                            format!("{key} does not take negative numbers")
+                        } else {
+                            format!(
+                                "{key} does not take negative numbers, but this can be {bounds}"
+                            )
+                        };
+                        let info =
+                            key.is("add_gold").then_some("try remove_short_term_gold instead");
+                        warn(ErrorKey::Range).msg(msg).opt_info(info).loc(bv).push();
                     }
                 }
                 validate_script_value(bv, data, sc);
@@ -454,7 +457,7 @@ pub fn validate_effect_control(
 
     if caller == "random" {
         vd.req_field("chance");
-        vd.field_script_value("chance", sc);
+        vd.field_script_value_in_range("chance", sc, 0.0, 100.0);
     } else {
         vd.ban_field("chance", || "`random`");
     }
@@ -578,7 +581,7 @@ pub enum Effect {
     /// The effect takes a script value, which can be a literal number or a named script value or an
     /// inline script value block.
     ScriptValue,
-    /// Just like [`Effect::ScriptValue`], but warns if the argument is a negative literal number.
+    /// Just like [`Effect::ScriptValue`], but warns if the argument can be a negative number.
     #[allow(dead_code)]
     NonNegativeValue,
     /// The effect takes a literal date.
@@ -11,12 +11,13 @@ use crate::item::Item;
 use crate::lowercase::Lowercase;
 use crate::report::{err, warn, ErrorKey, Severity};
 use crate::scopes::Scopes;
-use crate::script_value::validate_script_value;
+use crate::script_value::{script_value_bounds, validate_script_value, Bounds};
 use crate::token::Token;
 use crate::tooltipped::Tooltipped;
 use crate::trigger::{validate_target_ok_this, validate_trigger_key_bv};
 use crate::validate::validate_optional_duration;
 use crate::validator::{Validator, ValueValidator};
+use crate::variables::{VariableKind, VariableUse};
 
 #[allow(dead_code)] // No longer used by CK3
 pub fn validate_add_to_list(
@@ -31,16 +32,20 @@ pub fn validate_add_to_list(
 
 /// A specific validator for the three `add_to_variable_list` effects (`global`, `local`, and default).
 pub fn validate_add_to_variable_list(
-    _key: &Token,
+    key: &Token,
     _block: &Block,
-    _data: &Everything,
+    data: &Everything,
     sc: &mut ScopeContext,
     mut vd: Validator,
     _tooltipped: Tooltipped,
 ) {
     vd.req_field("name");
     vd.req_field("target");
-    vd.field_value("name");
+    if let Some(name) = vd.field_value("name") {
+        if key.starts_with("add_") {
+            data.variables.write(VariableKind::from_key(key.as_str(), true), name);
+        }
+    }
     vd.field_target_ok_this("target", sc, Scopes::all_but_none());
     #[cfg(feature = "ck3")]
     validate_optional_duration(&mut vd, sc);
@@ -48,15 +53,15 @@ pub fn validate_add_to_variable_list(
 
 /// A specific validator for the three `change_variable` effects (`global`, `local`, and default).
 pub fn validate_change_variable(
-    _key: &Token,
+    key: &Token,
     _block: &Block,
-    _data: &Everything,
+    data: &Everything,
     sc: &mut ScopeContext,
     mut vd: Validator,
     _tooltipped: Tooltipped,
 ) {
     vd.req_field("name");
-    vd.field_value("name");
+    validate_number_variable_name(key, &mut vd, data);
     vd.field_script_value("add", sc);
     vd.field_script_value("subtract", sc);
     vd.field_script_value("multiply", sc);
@@ -68,15 +73,15 @@ pub fn validate_change_variable(
 
 /// A specific validator for the three `clamp_variable` effects (`global`, `local`, and default).
 pub fn validate_clamp_variable(
-    _key: &Token,
+    key: &Token,
     _block: &Block,
-    _data: &Everything,
+    data: &Everything,
     sc: &mut ScopeContext,
     mut vd: Validator,
     _tooltipped: Tooltipped,
 ) {
     vd.req_field("name");
-    vd.field_value("name");
+    validate_number_variable_name(key, &mut vd, data);
     vd.field_script_value("min", sc);
     vd.field_script_value("max", sc);
 }
@@ -106,11 +111,37 @@ pub fn validate_random_list(
                 let msg = "fractions are discarded in `random_list` weights";
                 warn(ErrorKey::Range).strong().msg(msg).loc(key).push();
             }
+            if n >= 0.0 {
+                check_weight_modifiers(key, n, block, data);
+            }
             validate_effect_control(&caller, block, data, sc, tooltipped);
         }
     });
 }
 
+/// Warn if the `modifier` blocks in a `random_list` branch can make its weight negative.
+fn check_weight_modifiers(key: &Token, weight: f64, block: &Block, data: &Everything) {
+    let mut bounds = Bounds::exact(weight);
+    for modifier in block.get_field_blocks("modifier") {
+        // Each modifier only applies if its triggers are true.
+        let mut applied = bounds;
+        for (key, bv) in modifier.iter_assignments_and_definitions() {
+            if key.is("add") {
+                applied = applied.add(script_value_bounds(bv, data));
+            } else if key.is("factor") {
+                applied = applied.multiply(script_value_bounds(bv, data));
+            }
+        }
+        bounds = bounds.union(applied);
+    }
+    if bounds.min.is_finite() && bounds.min < 0.0 {
+        let msg =
+            "the modifiers can make this weight negative, which makes the whole `random_list` fail";
+        let info = format!("the weight can be {bounds}");
+        warn(ErrorKey::Range).msg(msg).info(info).loc(key).push();
+    }
+}
+
 pub fn validate_remove_from_list(
     _key: &Token,
     mut vd: ValueValidator,
@@ -123,16 +154,16 @@ pub fn validate_remove_from_list(
 
 /// A specific validator for the three `round_variable` effects (`global`, `local`, and default).
 pub fn validate_round_variable(
-    _key: &Token,
+    key: &Token,
     _block: &Block,
-    _data: &Everything,
+    data: &Everything,
     sc: &mut ScopeContext,
     mut vd: Validator,
     _tooltipped: Tooltipped,
 ) {
     vd.req_field("name");
     vd.req_field("nearest");
-    vd.field_value("name");
+    validate_number_variable_name(key, &mut vd, data);
     vd.field_script_value("nearest", sc);
 }
 
@@ -167,30 +198,55 @@ pub fn validate_save_scope_value(
 
 /// A specific validator for the three `set_variable` effects (`global`, `local`, and default).
 pub fn validate_set_variable(
-    _key: &Token,
+    key: &Token,
     bv: &BV,
     data: &Everything,
     sc: &mut ScopeContext,
     _tooltipped: Tooltipped,
 ) {
+    let kind = VariableKind::from_key(key.as_str(), false);
     match bv {
-        BV::Value(_token) => (),
+        BV::Value(token) => data.variables.write(kind, token),
         BV::Block(block) => {
             let mut vd = Validator::new(block, data);
             vd.set_case_sensitive(false);
             vd.req_field("name");
-            vd.field_value("name");
-            vd.field_validated("value", |bv, data| match bv {
-                BV::Value(token) => {
-                    validate_target_ok_this(token, data, sc, Scopes::all_but_none());
+            let name = vd.field_value("name").cloned();
+            if let Some(name) = &name {
+                data.variables.write(kind, name);
+            }
+            vd.field_validated("value", |bv, data| {
+                let how = match bv {
+                    BV::Value(token) => VariableUse::from_scopes(validate_target_ok_this(
+                        token,
+                        data,
+                        sc,
+                        Scopes::all_but_none(),
+                    )),
+                    BV::Block(_) => {
+                        validate_script_value(bv, data, sc);
+                        Some(VariableUse::Number)
+                    }
+                };
+                if let (Some(name), Some(how)) = (&name, how) {
+                    data.variables.use_as(kind, name, how);
                 }
-                BV::Block(_) => validate_script_value(bv, data, sc),
             });
             validate_optional_duration(&mut vd, sc);
         }
     }
 }
 
+/// Validate the `name` field of an effect that does arithmetic on a variable, and record that
+/// the variable is set and holds a number.
+fn validate_number_variable_name(key: &Token, vd: &mut Validator, data: &Everything) {
+    if let Some(name) = vd.field_value("name") {
+        let kind = VariableKind::from_key(key.as_str(), false);
+        data.variables.write(kind, name);
+        data.variables.use_as(kind, name, VariableUse::Number);
+    }
+}
+
 /// A specific validator for the `switch` effect, which has a unique syntax.
 pub fn validate_switch(
     key: &Token,
@@ -264,6 +320,9 @@ pub fn validate_trigger_event(
             if let Some(token) = block.get_field_value("id") {
                 data.check_event_scope(token, sc);
             }
+            if let Some(token) = block.get_field_value("on_action") {
+                data.event_chains.record_call(Item::OnAction, token, sc);
+            }
         }
     }
 }
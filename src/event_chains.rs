@@ -0,0 +1,268 @@
+//! Tracking of named scopes along chains of events.
+//!
+//! Events are validated one at a time, without knowing which named scopes their callers have set.
+//! During validation, every call of an event or on-action is recorded along with the named scopes
+//! that are set at that point, and every event and on-action records which named scopes it
+//! expects its caller to set. Afterward, when asked for with `--check-event-chains`, the named
+//! scopes are followed along the calls to find the ones that an event uses but that one of its
+//! callers doesn't provide.
+
+use std::sync::Mutex;
+
+use crate::block::{Block, Comparator, Eq::Question, Field, BV};
+use crate::context::ScopeContext;
+use crate::helpers::{TigerHashMap, TigerHashSet};
+use crate::item::Item;
+use crate::report::{warn, ErrorKey};
+use crate::scopes::Scopes;
+use crate::token::Token;
+
+/// An event or on-action.
+type Node = (Item, &'static str);
+
+/// The named scopes that are available somewhere, with their scope types, or `None` if it's not
+/// known which ones are available.
+type Available = Option<TigerHashMap<String, Scopes>>;
+
+/// How many callers back to show when a named scope is missing.
+const MAX_CHAIN_SHOWN: usize = 4;
+
+#[derive(Debug)]
+struct Call {
+    /// The event or on-action that is called.
+    callee: Node,
+    /// The token naming the callee at the call site.
+    token: Token,
+    /// The event or on-action that makes the call, or `None` if the caller's named scopes are all
+    /// known at the call site.
+    caller: Option<Node>,
+    /// The named scopes that are set at the call site.
+    names: TigerHashMap<String, Scopes>,
+}
+
+#[derive(Debug)]
+struct Input {
+    name: String,
+    /// The scope types the callee expects this named scope to have.
+    scopes: Scopes,
+    /// Where the callee first uses it.
+    token: Token,
+    /// Whether the callee checks that it exists before using it.
+    optional: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct EventChains {
+    calls: Mutex<Vec<Call>>,
+    inputs: Mutex<TigerHashMap<Node, Vec<Input>>>,
+}
+
+impl EventChains {
+    /// Record that the event or on-action `token` is called here, with the named scopes in `sc`.
+    pub fn record_call(&self, itype: Item, token: &Token, sc: &ScopeContext) {
+        let caller = sc.origin().map(|(itype, key)| (*itype, key.as_str()));
+        if caller.is_none() && !sc.is_strict() {
+            // Called from somewhere like a scripted effect, where the available named scopes
+            // depend on its own callers. Nothing can be concluded from such calls.
+            return;
+        }
+        let names = sc
+            .defined_names()
+            .into_iter()
+            .map(|(name, scopes)| (name.to_string(), scopes))
+            .collect();
+        let call = Call { callee: (itype, token.as_str()), token: token.clone(), caller, names };
+        self.calls.lock().unwrap().push(call);
+    }
+
+    /// Record the named scopes that the event or on-action `key` expects its callers to set,
+    /// after its script in `block` has been validated with `sc`.
+    ///
+    /// Named scopes that the script checks with `exists` or `?=` are taken to be optional.
+    pub fn record_inputs(&self, itype: Item, key: &Token, block: &Block, sc: &ScopeContext) {
+        let mut optional = TigerHashSet::default();
+        optional_names(block, &mut optional);
+        let inputs = sc
+            .input_names()
+            .into_iter()
+            .map(|(name, scopes, token)| Input {
+                name: name.to_string(),
+                scopes,
+                token: token.clone(),
+                optional: optional.contains(name),
+            })
+            .collect();
+        self.inputs.lock().unwrap().insert((itype, key.as_str()), inputs);
+    }
+
+    /// Follow the named scopes along all the recorded calls, and warn about named scopes that an
+    /// event expects but that are not set by one of its callers or have the wrong scope type.
+    pub fn check(&self) {
+        let calls = self.calls.lock().unwrap();
+        let inputs = self.inputs.lock().unwrap();
+
+        let mut incoming: TigerHashMap<Node, Vec<&Call>> = TigerHashMap::default();
+        for call in calls.iter() {
+            incoming.entry(call.callee).or_default().push(call);
+        }
+        // The calls were recorded from several threads. Put them in file order so that the
+        // reports, and the callers shown in them, are the same on every run.
+        for calls in incoming.values_mut() {
+            calls.sort_by_key(|call| &call.token.loc);
+        }
+
+        // The named scopes available on entry to each event or on-action. Nodes that are not in
+        // this map could get any named scope from their callers.
+        //
+        // Nodes without recorded callers are called in ways that are not known, for example by
+        // the game engine. Assume they get the named scopes they use, and nothing else.
+        let mut entry: TigerHashMap<Node, TigerHashMap<String, Scopes>> = inputs
+            .iter()
+            .filter(|(node, _)| !incoming.contains_key(node))
+            .map(|(node, inputs)| {
+                (*node, inputs.iter().map(|input| (input.name.clone(), input.scopes)).collect())
+            })
+            .collect();
+        loop {
+            let mut changed = false;
+            for (node, calls) in &incoming {
+                let mut available: Available = None;
+                for call in calls {
+                    available = intersect(available, available_at(call, &entry));
+                }
+                if let Some(available) = available {
+                    if entry.get(node) != Some(&available) {
+                        entry.insert(*node, available);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut nodes: Vec<_> = inputs.keys().collect();
+        nodes.sort_unstable();
+        for node in nodes {
+            let Some(calls) = incoming.get(node) else {
+                continue;
+            };
+            for input in inputs[node].iter().filter(|input| !input.optional) {
+                for call in calls {
+                    let available = available_at(call, &entry);
+                    let scopes = call
+                        .names
+                        .get(&input.name)
+                        .or_else(|| available.as_ref().and_then(|a| a.get(&input.name)));
+                    if let Some(&scopes) = scopes {
+                        if !scopes.intersects(input.scopes) {
+                            let msg = format!(
+                                "scope:{} is {scopes} when `{}` is called here, but it is used as {}",
+                                input.name, node.1, input.scopes
+                            );
+                            warn(ErrorKey::Scopes)
+                                .msg(msg)
+                                .loc(&input.token)
+                                .loc_msg(&call.token, "called here")
+                                .push();
+                        }
+                    } else if available.is_some() {
+                        let msg = format!(
+                            "scope:{} is not set when `{}` is called here",
+                            input.name, node.1
+                        );
+                        let mut builder = warn(ErrorKey::StrictScopes)
+                            .msg(msg)
+                            .loc(&input.token)
+                            .loc_msg(&call.token, "called here");
+                        for token in missing_chain(call, &input.name, &incoming, &entry) {
+                            builder = builder.loc_msg(token, "which is called here");
+                        }
+                        builder.push();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The named scopes available in the callee when it is called by `call`.
+fn available_at(
+    call: &Call,
+    entry: &TigerHashMap<Node, TigerHashMap<String, Scopes>>,
+) -> Available {
+    let mut available = match call.caller {
+        Some(caller) => entry.get(&caller)?.clone(),
+        None => TigerHashMap::default(),
+    };
+    available.extend(call.names.iter().map(|(name, scopes)| (name.clone(), *scopes)));
+    Some(available)
+}
+
+/// Keep only the named scopes that are available in both, with the scope types from either.
+fn intersect(a: Available, b: Available) -> Available {
+    match (a, b) {
+        (None, x) | (x, None) => x,
+        (Some(mut a), Some(b)) => {
+            a.retain(|name, _| b.contains_key(name));
+            for (name, scopes) in &mut a {
+                *scopes |= b[name];
+            }
+            Some(a)
+        }
+    }
+}
+
+/// Follow the callers of `call` back to show where the named scope `name` went missing.
+fn missing_chain<'a>(
+    call: &'a Call,
+    name: &str,
+    incoming: &'a TigerHashMap<Node, Vec<&Call>>,
+    entry: &TigerHashMap<Node, TigerHashMap<String, Scopes>>,
+) -> Vec<&'a Token> {
+    let mut chain = Vec::new();
+    let mut seen = TigerHashSet::default();
+    let mut caller = call.caller;
+    while let Some(node) = caller {
+        if chain.len() >= MAX_CHAIN_SHOWN || !seen.insert(node) {
+            break;
+        }
+        let next = incoming.get(&node).and_then(|calls| {
+            calls.iter().find(|call| {
+                available_at(call, entry).is_some_and(|available| !available.contains_key(name))
+            })
+        });
+        let Some(next) = next else {
+            break;
+        };
+        chain.push(&next.token);
+        caller = next.caller;
+    }
+    chain
+}
+
+/// Collect the named scopes that are checked with `exists = scope:name` or `scope:name ?= ...`
+/// anywhere in `block`.
+fn optional_names(block: &Block, names: &mut TigerHashSet<&'static str>) {
+    for Field(key, cmp, bv) in block.iter_fields() {
+        if key.is("exists") {
+            if let Some(name) = bv.get_value().and_then(scope_name) {
+                names.insert(name);
+            }
+        } else if cmp == &Comparator::Equals(Question) {
+            if let Some(name) = scope_name(key) {
+                names.insert(name);
+            }
+        }
+        if let BV::Block(block) = bv {
+            optional_names(block, names);
+        }
+    }
+}
+
+/// Return `name` if `token` is a scope chain starting with `scope:name`.
+fn scope_name(token: &Token) -> Option<&'static str> {
+    let rest = token.as_str().strip_prefix("scope:")?;
+    Some(rest.split_once('.').map_or(rest, |(name, _)| name))
+}
@@ -6,6 +6,7 @@
 use std::borrow::Cow;
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 #[cfg(feature = "ck3")]
 use std::sync::RwLock;
 
@@ -36,7 +37,7 @@ use crate::ck3::data::{
 };
 #[cfg(feature = "ck3")]
 use crate::ck3::tables::misc::*;
-use crate::config_load::{check_for_legacy_ignore, load_filter};
+use crate::config_load::{check_for_legacy_ignore, load_filter, load_lint_rules};
 use crate::context::ScopeContext;
 #[cfg(any(feature = "ck3", feature = "vic3"))]
 use crate::data::data_binding::DataBindings;
@@ -45,7 +46,7 @@ use crate::data::{
     coa::Coas,
     defines::Defines,
     gui::Gui,
-    localization::Localization,
+    localization::{Localization, PreviewFormat},
     music::Musics,
     on_actions::OnActions,
     script_values::ScriptValues,
@@ -56,8 +57,11 @@ use crate::data::{
 };
 use crate::db::{Db, DbKind};
 use crate::dds::DdsFiles;
+use crate::dlc::ExcludedDlcs;
+use crate::event_chains::EventChains;
 use crate::fileset::{FileEntry, FileKind, Fileset};
 use crate::game::Game;
+use crate::glyphs::check_glyph_coverage;
 #[cfg(feature = "ck3")]
 use crate::helpers::TigerHashSet;
 #[cfg(feature = "imperator")]
@@ -67,16 +71,22 @@ use crate::imperator::data::{
 #[cfg(feature = "imperator")]
 use crate::imperator::tables::misc::*;
 use crate::item::{Item, ItemLoader};
+use crate::lint::LintRule;
 use crate::lowercase::Lowercase;
+use crate::macro_report::{MacroCalls, MacroDefinition};
 use crate::macros::MACRO_MAP;
 #[cfg(feature = "vic3")]
 use crate::parse::json::parse_json_file;
 use crate::pdxfile::PdxFile;
-#[cfg(feature = "ck3")]
-use crate::report::err;
-use crate::report::{report, set_output_style, ErrorKey, OutputStyle, Severity};
+use crate::plugin::{Plugin, PluginLoader};
+use crate::report::{
+    add_plugin_keys, err, report, set_output_style, ErrorKey, OutputStyle, Severity,
+};
 use crate::rivers::Rivers;
+use crate::schema::{load_schemas, ItemSchema};
+use crate::scope_probe::ScopeProbe;
 use crate::token::{Loc, Token};
+use crate::variables::Variables;
 #[cfg(feature = "vic3")]
 use crate::vic3::data::{
     buy_packages::BuyPackage, events::Vic3Events, history::History, provinces::Vic3Provinces,
@@ -118,6 +128,9 @@ pub struct Everything {
     /// Tracks all the files (vanilla and mods) that are relevant to the current validation.
     pub(crate) fileset: Fileset,
 
+    /// The items and files from DLCs that are left out by the `dlc` config.
+    pub(crate) excluded_dlcs: ExcludedDlcs,
+
     /// Tracks specifically the .dds files, and their formats and sizes.
     pub(crate) dds: DdsFiles,
 
@@ -200,6 +213,29 @@ pub struct Everything {
 
     #[cfg(feature = "ck3")]
     pub(crate) wars: Wars,
+
+    /// Where script variables and flags are set and read, filled in during validation.
+    pub(crate) variables: Variables,
+
+    /// The calls between events and on-actions and the named scopes they pass along, filled in
+    /// during validation.
+    pub(crate) event_chains: EventChains,
+
+    /// The scope types recorded at one location for [`Everything::explain_scopes`].
+    pub(crate) scope_probe: ScopeProbe,
+
+    /// The calls of scripted effects and triggers with arguments, recorded for
+    /// [`Everything::macro_report`].
+    pub(crate) macro_calls: MacroCalls,
+
+    /// The checks added by validators built on this library.
+    plugins: Vec<Box<dyn Plugin>>,
+
+    /// The item types declared in the schema files named in the config.
+    schemas: Vec<Arc<ItemSchema>>,
+
+    /// The custom lint rules from the `lint` sections of the config.
+    lint_rules: Vec<LintRule>,
 }
 
 impl Everything {
@@ -245,9 +281,13 @@ impl Everything {
 
         fileset.scan_all()?;
         fileset.finalize();
+        let excluded_dlcs = ExcludedDlcs::new(&fileset, &config);
+        let schemas = load_schemas(&config);
+        let lint_rules = load_lint_rules(&config);
 
         Ok(Everything {
             fileset,
+            excluded_dlcs,
             dds: DdsFiles::default(),
             config,
             #[cfg(feature = "ck3")]
@@ -307,6 +347,13 @@ impl Everything {
             history: History::default(),
             #[cfg(feature = "ck3")]
             wars: Wars::default(),
+            variables: Variables::default(),
+            event_chains: EventChains::default(),
+            scope_probe: ScopeProbe::default(),
+            macro_calls: MacroCalls::default(),
+            plugins: Vec::new(),
+            schemas,
+            lint_rules,
         })
     }
 
@@ -315,6 +362,14 @@ impl Everything {
         PdxFile::read_optional_bom(&entry)
     }
 
+    /// Add a plugin with checks of its own. This must be done before
+    /// [`Everything::load_config_filtering_rules`], so that the config's filters can name the
+    /// plugin's error keys, and before [`Everything::load_all`].
+    pub fn add_plugin(&mut self, plugin: Box<dyn Plugin>) {
+        add_plugin_keys(plugin.name(), plugin.keys());
+        self.plugins.push(plugin);
+    }
+
     pub fn load_config_filtering_rules(&self) {
         check_for_legacy_ignore(&self.config);
         load_filter(&self.config);
@@ -414,6 +469,21 @@ impl Everything {
         });
 
         self.load_all_normal_pdx_files();
+        self.load_all_custom_items();
+    }
+
+    fn load_all_custom_items(&mut self) {
+        for schema in &self.schemas {
+            self.fileset.add_custom_dir(schema.path().clone());
+            let blocks = self.fileset.filter_map_under(schema.path(), |entry| {
+                if entry.filename().to_string_lossy().ends_with(".txt") {
+                    PdxFile::read(entry)
+                } else {
+                    None
+                }
+            });
+            schema.load(&mut self.database, blocks);
+        }
     }
 
     #[cfg(feature = "ck3")]
@@ -465,6 +535,9 @@ impl Everything {
             Game::Imperator => self.load_all_imperator(),
         }
         self.database.add_subitems();
+        for plugin in &mut self.plugins {
+            plugin.load(&mut PluginLoader::new(&self.fileset, &mut self.database));
+        }
     }
 
     fn validate_all_generic<'a>(&'a self, s: &Scope<'a>) {
@@ -534,6 +607,12 @@ impl Everything {
         self.database.validate(self);
 
         self.localization.validate_pass2(self);
+        for rule in &self.lint_rules {
+            rule.check(self);
+        }
+        for plugin in &self.plugins {
+            plugin.validate(self);
+        }
     }
 
     pub fn check_rivers(&mut self) {
@@ -542,6 +621,32 @@ impl Everything {
         rivers.validate(self);
     }
 
+    /// Like [`Everything::check_rivers`], but also write images to `dir` that highlight the
+    /// problems found in the map images. These are meant to be layered over the originals in an
+    /// image editor.
+    ///
+    /// The images are `tiger-rivers-debug.png` and, for CK3, `tiger-provinces-debug.png`. Each
+    /// image is written even if the other one could not be, and the errors for both are returned.
+    pub fn check_rivers_with_debug_images(&mut self, dir: &Path) -> Vec<anyhow::Error> {
+        let mut errors = Vec::new();
+        let mut rivers = Rivers::default();
+        rivers.set_debug_image();
+        self.fileset.handle(&mut rivers);
+        rivers.validate(self);
+        let path = dir.join("tiger-rivers-debug.png");
+        if let Err(e) = rivers.write_debug_png(&path) {
+            errors.push(e.context(format!("could not write {}", path.display())));
+        }
+        #[cfg(feature = "ck3")]
+        if Game::is_ck3() {
+            let path = dir.join("tiger-provinces-debug.png");
+            if let Err(e) = self.provinces_ck3.write_debug_png(self, &path) {
+                errors.push(e.context(format!("could not write {}", path.display())));
+            }
+        }
+        errors
+    }
+
     #[cfg(feature = "ck3")]
     pub fn check_pod(&mut self) {
         self.province_histories.check_pod_faiths(self, &self.titles);
@@ -549,9 +654,120 @@ impl Everything {
         self.localization.check_pod_loca(self);
     }
 
+    /// Do the extra de jure hierarchy checks from [`Titles::audit`].
+    #[cfg(feature = "ck3")]
+    pub fn check_titles_audit(&self) -> Result<()> {
+        let adjacent = self.provinces_ck3.compute_adjacency(self)?;
+        self.titles.audit(self, &adjacent);
+        Ok(())
+    }
+
+    /// Replay the history files up to each bookmark date and check the resulting world state.
+    #[cfg(feature = "ck3")]
+    pub fn check_bookmark_history(&self) {
+        crate::ck3::history_replay::check_bookmarks(self);
+    }
+
+    /// Write the de jure hierarchy of all landed titles to `path`, as JSON or as CSV if the
+    /// filename ends in `.csv`.
+    #[cfg(feature = "ck3")]
+    pub fn export_titles(&self, path: &Path) -> Result<()> {
+        self.titles.export(path)
+    }
+
+    /// Write the family trees of all historical characters to `path`, as GEDCOM if the filename
+    /// ends in `.ged` or as Graphviz DOT if it ends in `.dot`.
+    #[cfg(feature = "ck3")]
+    pub fn export_family_trees(&self, path: &Path) -> Result<()> {
+        crate::ck3::family_tree::export(self, path)
+    }
+
+    /// Render the localization `key` in language `lang`, with macros expanded and code shown as
+    /// placeholders. Returns `None` if the key does not exist in that language.
+    pub fn preview_localization(
+        &self,
+        key: &str,
+        lang: &str,
+        format: PreviewFormat,
+    ) -> Option<String> {
+        self.localization.preview(key, lang, format, self)
+    }
+
+    /// Simulate the tooltip that the game shows for the event options, decision or character
+    /// interaction `key`, in language `lang`. Returns `None` if there is no such item.
+    pub fn simulate_tooltip(&self, key: &str, option: Option<&str>, lang: &str) -> Option<String> {
+        crate::tooltip::simulate(key, option, lang, self)
+    }
+
+    /// Validate everything and show the scope types that were inferred for the effects, triggers
+    /// and script values on line `line` of the file `path`, which is relative to the game or mod
+    /// directory. Returns `None` if nothing was validated there.
+    pub fn explain_scopes(&mut self, path: PathBuf, line: u32) -> Option<String> {
+        self.scope_probe.set_target(path, line);
+        self.validate_all();
+        let explanations = self.scope_probe.explanations();
+        if explanations.is_empty() {
+            None
+        } else {
+            Some(explanations.join("\n\n"))
+        }
+    }
+
+    /// Validate everything and list, for each scripted effect and trigger in the mod that has
+    /// parameters, the arguments passed to it at each call site and the reports from each
+    /// expansion. The reports are taken out of the report storage. Returns `None` if the mod has
+    /// no such scripted effects or triggers.
+    pub fn macro_report(&mut self) -> Option<String> {
+        self.macro_calls.enable();
+        self.validate_all();
+        let reports = crate::report::take_reports();
+        let effects = self.effects.iter_keys().filter_map(|key| self.effects.get(key.as_str()));
+        let triggers = self.triggers.iter_keys().filter_map(|key| self.triggers.get(key.as_str()));
+        let definitions = effects
+            .map(|effect| MacroDefinition {
+                what: "scripted effect",
+                key: &effect.key,
+                parms: effect.macro_parms(),
+            })
+            .chain(triggers.map(|trigger| MacroDefinition {
+                what: "scripted trigger",
+                key: &trigger.key,
+                parms: trigger.macro_parms(),
+            }))
+            .collect();
+        self.macro_calls.report(definitions, &reports)
+    }
+
+    /// Show the top-level gui widget called `name`, usually a window, with all its types,
+    /// templates and blockoverrides expanded. Returns `None` if there is no such widget.
+    pub fn dump_gui_widget(&self, name: &str) -> Option<String> {
+        self.gui.dump_widget(name)
+    }
+
+    /// Report script variables and flags that are read but never set, set but never read, or used
+    /// both as numbers and as scopes. Must be called after [`Everything::validate_all`].
+    pub fn check_variables(&self) {
+        self.variables.check();
+    }
+
+    /// Report named scopes that events and on-actions use but that some of their callers don't
+    /// set, or set with the wrong scope type. Must be called after [`Everything::validate_all`].
+    pub fn check_event_chains(&self) {
+        self.event_chains.check();
+    }
+
+    /// Report localization text that the fonts for its language cannot show, and static gui
+    /// texts from the mod that don't fit in their widgets. This reads all the font files, so it
+    /// is not part of [`Everything::validate_all`].
+    pub fn check_fonts(&self) {
+        check_glyph_coverage(self);
+        self.gui.check_text_widths(self);
+    }
+
     pub fn check_unused(&mut self) {
         self.localization.check_unused(self);
         self.fileset.check_unused_dds(self);
+        self.gui.check_unused(self);
     }
 
     pub(crate) fn item_has_property(&self, itype: Item, key: &str, property: &str) -> bool {
@@ -654,7 +870,7 @@ impl Everything {
         }
     }
 
-    pub(crate) fn item_exists(&self, itype: Item, key: &str) -> bool {
+    pub fn item_exists(&self, itype: Item, key: &str) -> bool {
         match itype {
             Item::Asset => self.assets.asset_exists(key),
             Item::BlendShape => self.assets.blend_shape_exists(key),
@@ -749,14 +965,34 @@ impl Everything {
         match itype {
             Item::File => self.fileset.mark_used(key),
             Item::Localization => self.localization.mark_used(key),
+            Item::GuiTemplate | Item::GuiType | Item::WidgetName => self.gui.mark_used(itype, key),
             _ => (),
         }
     }
 
-    pub(crate) fn verify_exists(&self, itype: Item, token: &Token) {
+    pub fn verify_exists(&self, itype: Item, token: &Token) {
         self.verify_exists_implied(itype, token.as_str(), token);
     }
 
+    /// Whether `key` is the key of an item of the type `itype` that was declared in a schema file
+    /// or loaded by a plugin.
+    pub fn custom_item_exists(&self, itype: &str, key: &str) -> bool {
+        self.database.custom_exists(itype, key)
+    }
+
+    /// Report an error if `token` is not the key of an item of the type `itype` that was declared
+    /// in a schema file or loaded by a plugin.
+    pub fn verify_exists_custom(&self, itype: &str, token: &Token) {
+        if !self.database.custom_exists(itype, token.as_str()) {
+            let path = self.schemas.iter().find(|schema| schema.name() == itype).map(|s| s.path());
+            let msg = match path {
+                Some(path) => format!("{itype} {token} not defined in {}", path.display()),
+                None => format!("{itype} {token} not defined"),
+            };
+            err(ErrorKey::MissingItem).msg(msg).loc(token).push();
+        }
+    }
+
     pub(crate) fn verify_exists_max_sev(&self, itype: Item, token: &Token, max_sev: Severity) {
         self.verify_exists_implied_max_sev(itype, token.as_str(), token, max_sev);
     }
@@ -770,7 +1006,12 @@ impl Everything {
     ) {
         match itype {
             Item::Entry => self.fileset.verify_entry_exists(key, token, max_sev),
-            Item::File => self.fileset.verify_exists_implied(key, token, max_sev),
+            Item::File => {
+                if self.fileset.exists(key) || !self.excluded_dlcs.check_missing(itype, key, token)
+                {
+                    self.fileset.verify_exists_implied(key, token, max_sev);
+                }
+            }
             Item::Localization => self.localization.verify_exists_implied(key, token, max_sev),
             Item::Music => self.music.verify_exists_implied(key, token, max_sev),
             Item::Province => match Game::game() {
@@ -797,7 +1038,9 @@ impl Everything {
                 }
             }
             _ => {
-                if !self.item_exists(itype, key) {
+                if !self.item_exists(itype, key)
+                    && !self.excluded_dlcs.check_missing(itype, key, token)
+                {
                     let path = itype.path();
                     let msg = if path.is_empty() {
                         format!("unknown {itype} {key}")
@@ -865,10 +1108,27 @@ impl Everything {
         self.database.get_item(itype, key)
     }
 
-    pub(crate) fn get_key_block(&self, itype: Item, key: &str) -> Option<(&Token, &Block)> {
+    pub fn get_key_block(&self, itype: Item, key: &str) -> Option<(&Token, &Block)> {
         self.database.get_key_block(itype, key)
     }
 
+    /// Return the block that defines the event `key`, if there is one.
+    pub(crate) fn get_event_block<'a>(&'a self, key: &'a str) -> Option<&'a Block> {
+        match Game::game() {
+            #[cfg(feature = "ck3")]
+            Game::Ck3 => self.events_ck3.get_event(key).map(crate::ck3::data::events::Event::block),
+            #[cfg(feature = "vic3")]
+            Game::Vic3 => {
+                self.events_vic3.get_event(key).map(crate::vic3::data::events::Event::block)
+            }
+            #[cfg(feature = "imperator")]
+            Game::Imperator => self
+                .events_imperator
+                .get_event(key)
+                .map(crate::imperator::data::events::Event::block),
+        }
+    }
+
     pub(crate) fn get_trigger(&self, key: &Token) -> Option<&Trigger> {
         #[cfg(feature = "ck3")]
         if Game::is_ck3() {
@@ -898,6 +1158,7 @@ impl Everything {
     }
 
     pub(crate) fn check_event_scope(&self, token: &Token, sc: &mut ScopeContext) {
+        self.event_chains.record_call(Item::Event, token, sc);
         match Game::game() {
             #[cfg(feature = "ck3")]
             Game::Ck3 => self.events_ck3.check_scope(token, sc),
@@ -913,6 +1174,12 @@ impl Everything {
         self.defines.get_bv(key).and_then(BV::get_value)
     }
 
+    /// Get a numeric define, or `None` if it's missing or not a number.
+    #[cfg(feature = "ck3")]
+    pub(crate) fn get_defined_number(&self, key: &str) -> Option<f64> {
+        self.get_defined_string(key).and_then(Token::get_number)
+    }
+
     #[cfg(feature = "ck3")] // happens not to be used by vic3
     pub(crate) fn get_defined_array(&self, key: &str) -> Option<&Block> {
         self.defines.get_bv(key).and_then(BV::get_block)
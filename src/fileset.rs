@@ -73,6 +73,8 @@ pub struct FileEntry {
 }
 
 impl FileEntry {
+    /// # Panics
+    /// Panics if `path` has no filename.
     pub fn new(path: PathBuf, kind: FileKind, fullpath: PathBuf) -> Self {
         assert!(path.file_name().is_some());
         Self { path, kind, idx: None, fullpath }
@@ -220,6 +222,12 @@ pub struct Fileset {
     /// DLC directories to be loaded after vanilla, in order.
     loaded_dlcs: Vec<LoadedMod>,
 
+    /// The DLC directories to load, from the `dlc` config. All of them if `None`.
+    installed_dlcs: Option<Vec<String>>,
+
+    /// The files of the DLCs that are not in `installed_dlcs`, with the name of their DLC.
+    excluded_dlc_files: Vec<(FileEntry, String)>,
+
     /// The ck3-tiger config.
     config: Option<Block>,
 
@@ -236,11 +244,18 @@ pub struct Fileset {
     /// All filenames from `ordered_files`, for quick lookup.
     filenames: TigerHashSet<PathBuf>,
 
+    /// Filenames of mod files that replace a file from vanilla or another mod.
+    replaced: TigerHashSet<PathBuf>,
+
     /// All directories that have been looked up, for quick lookup.
     directories: RwLock<TigerHashSet<PathBuf>>,
 
     /// Filenames that have been looked up during validation. Used to filter the --unused output.
     used: RwLock<TigerHashSet<String>>,
+
+    /// Directories that are read by plugins or declared in schema files, which are not checked for
+    /// being unexpected.
+    custom_dirs: RwLock<Vec<PathBuf>>,
 }
 
 impl Fileset {
@@ -256,13 +271,17 @@ impl Fileset {
             the_mod: LoadedMod::new_main_mod(mod_root, replace_paths),
             loaded_mods: Vec::new(),
             loaded_dlcs: Vec::new(),
+            installed_dlcs: None,
+            excluded_dlc_files: Vec::new(),
             config: None,
             files: Vec::new(),
             ordered_files: Vec::new(),
             filename_tokens: Vec::new(),
             filenames: TigerHashSet::default(),
+            replaced: TigerHashSet::default(),
             directories: RwLock::new(TigerHashSet::default()),
             used: RwLock::new(TigerHashSet::default()),
+            custom_dirs: RwLock::new(Vec::new()),
         }
     }
 
@@ -327,6 +346,11 @@ impl Fileset {
                 }
             }
         }
+        if let Some(block) = config.get_field_block("dlc") {
+            if let Some(block) = block.get_field_block("installed") {
+                self.installed_dlcs = Some(block.iter_values().map(ToString::to_string).collect());
+            }
+        }
         self.config = Some(config);
         Ok(())
     }
@@ -399,9 +423,15 @@ impl Fileset {
                         entry.path().to_path_buf(),
                         Vec::new(),
                     );
+                    let start = self.files.len();
                     self.scan(dlc.root(), dlc.kind()).map_err(|e| {
                         FilesError::VanillaUnreadable { path: dlc.root().to_path_buf(), source: e }
                     })?;
+                    if self.installed_dlcs.as_ref().is_some_and(|dlcs| !dlcs.contains(&label)) {
+                        let excluded = self.files.split_off(start);
+                        self.excluded_dlc_files
+                            .extend(excluded.into_iter().map(|entry| (entry, label.clone())));
+                    }
                     self.loaded_dlcs.push(dlc);
                     add_loaded_dlc_root(label);
                 }
@@ -429,6 +459,9 @@ impl Fileset {
         for entry in self.files.drain(..) {
             if let Some(prev) = self.ordered_files.last_mut() {
                 if entry.path == prev.path {
+                    if entry.kind == FileKind::Mod {
+                        self.replaced.insert(entry.path.clone());
+                    }
                     *prev = entry;
                 } else {
                     self.ordered_files.push(entry);
@@ -446,6 +479,12 @@ impl Fileset {
         }
     }
 
+    /// Return the files of the DLCs that were left out because of the `dlc` config, with the name
+    /// of the DLC directory they came from.
+    pub fn excluded_dlc_files(&self) -> &[(FileEntry, String)] {
+        &self.excluded_dlc_files
+    }
+
     pub fn get_files_under<'a>(&'a self, subpath: &'a Path) -> &[FileEntry] {
         let start = self.ordered_files.partition_point(|entry| entry.path < subpath);
         let end = start
@@ -475,17 +514,36 @@ impl Fileset {
         handler.finalize();
     }
 
+    /// Record that the files under `subpath` are read by a plugin or declared in a schema file.
+    pub fn add_custom_dir(&self, subpath: PathBuf) {
+        self.custom_dirs.write().unwrap().push(subpath);
+    }
+
     pub fn mark_used(&self, file: &str) {
         let file = file.strip_prefix('/').unwrap_or(file);
         self.used.write().unwrap().insert(file.to_string());
     }
 
+    /// Return whether the mod's file at `path` replaces a file from vanilla or another mod.
+    pub fn replaces_file(&self, path: &Path) -> bool {
+        self.replaced.contains(path)
+    }
+
     pub fn exists(&self, key: &str) -> bool {
         let key = key.strip_prefix('/').unwrap_or(key);
         let filepath = PathBuf::from(key);
         self.filenames.contains(&filepath)
     }
 
+    pub fn get_entry(&self, key: &str) -> Option<&FileEntry> {
+        let key = key.strip_prefix('/').unwrap_or(key);
+        let filepath = Path::new(key);
+        self.ordered_files
+            .binary_search_by_key(&filepath, |entry| entry.path.as_path())
+            .ok()
+            .map(|idx| &self.ordered_files[idx])
+    }
+
     pub fn iter_keys(&self) -> impl Iterator<Item = &Token> {
         self.filename_tokens.iter()
     }
@@ -607,6 +665,9 @@ impl Fileset {
                     continue 'outer;
                 }
             }
+            if self.custom_dirs.read().unwrap().iter().any(|dir| entry.path.starts_with(dir)) {
+                continue;
+            }
 
             for valid in common_dirs {
                 if <&str as AsRef<Path>>::as_ref(valid) == dirname {
@@ -0,0 +1,420 @@
+//! Check that the fonts the game uses for each language have glyphs for all the characters in
+//! that language's localization, and estimate how wide texts will be in those fonts.
+//!
+//! Only the character map (`cmap` table) and the horizontal metrics of the `.ttf` and `.otf` files
+//! are read. A character counts as covered if any of the font files configured for the language
+//! has it.
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::block::Block;
+use crate::everything::Everything;
+use crate::fileset::FileEntry;
+use crate::helpers::{TigerHashMap, TigerHashSet};
+use crate::item::Item;
+use crate::pathtable::PathTableIndex;
+use crate::report::{err, warn, ErrorKey};
+use crate::token::Token;
+
+const SFNT_HEADER_SIZE: usize = 12;
+const TABLE_RECORD_SIZE: usize = 16;
+const CMAP_RECORD_SIZE: usize = 8;
+
+fn from_be16(buffer: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buffer.get(offset..offset + 2)?;
+    Some((u32::from(bytes[0]) << 8) | u32::from(bytes[1]))
+}
+
+fn from_be32(buffer: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buffer.get(offset..offset + 4)?;
+    Some(
+        (u32::from(bytes[0]) << 24)
+            | (u32::from(bytes[1]) << 16)
+            | (u32::from(bytes[2]) << 8)
+            | u32::from(bytes[3]),
+    )
+}
+
+fn to_usize(value: u32) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
+
+/// Check the mod's localization against the fonts defined in `fontfiles` entries.
+pub fn check_glyph_coverage(data: &Everything) {
+    let mut needed = Vec::new();
+    for (lang, texts) in data.localization.mod_texts() {
+        // Every font can be assumed to have the ASCII characters.
+        if texts.iter().any(|text| !text.as_str().is_ascii()) {
+            needed.push((lang, texts));
+        }
+    }
+    if needed.is_empty() {
+        return;
+    }
+
+    let mut fonts = FontCache::default();
+    for (lang, texts) in needed {
+        let files = font_files_for(lang, None, data);
+        if files.is_empty() {
+            continue;
+        }
+        for file in &files {
+            fonts.load(file, data);
+        }
+        let coverage: Vec<&FontInfo> = files.iter().filter_map(|file| fonts.get(file)).collect();
+        if coverage.is_empty() {
+            continue;
+        }
+        check_language(lang, &texts, &coverage);
+    }
+}
+
+/// Return the font files that the `fontfiles` entries list for `lang`. Groups without a
+/// `languages` list apply to all languages. If `font` is given, only the `fontfiles` used by that
+/// font are considered.
+fn font_files_for(lang: &str, font: Option<&str>, data: &Everything) -> Vec<Token> {
+    let fontfiles: Option<Vec<&Token>> = font.map(|font| {
+        data.database
+            .get_key_block(Item::Font, font)
+            .map(|(_, block)| {
+                block
+                    .get_field_blocks("fontstyle")
+                    .into_iter()
+                    .filter_map(|style| style.get_field_value("fontfiles"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    });
+    let mut files = Vec::new();
+    for (key, block) in data.database.iter_key_block(Item::Fontfiles) {
+        if fontfiles.as_ref().is_some_and(|names| !names.iter().any(|name| name.is(key.as_str()))) {
+            continue;
+        }
+        for group in block.get_field_blocks("group") {
+            if group_has_language(group, lang) {
+                if let Some(list) = group.get_field_list("files") {
+                    files.extend(list);
+                }
+            }
+        }
+    }
+    files
+}
+
+fn group_has_language(group: &Block, lang: &str) -> bool {
+    let Some(languages) = group.get_field_block("languages") else {
+        return true;
+    };
+    languages
+        .iter_values()
+        .any(|value| value.as_str().strip_prefix("l_").unwrap_or(value.as_str()) == lang)
+}
+
+fn check_language(lang: &str, texts: &[Token], coverage: &[&FontInfo]) {
+    let covered = |c: char| {
+        c.is_ascii() || c.is_control() || coverage.iter().any(|font| font.glyphs.contains_key(&c))
+    };
+
+    // Group the texts by file, so that files in the wrong script can be reported as a whole.
+    let mut by_file: TigerHashMap<PathTableIndex, Vec<&Token>> = TigerHashMap::default();
+    let mut order = Vec::new();
+    for text in texts {
+        let idx = text.loc.idx;
+        by_file
+            .entry(idx)
+            .or_insert_with(|| {
+                order.push(idx);
+                Vec::new()
+            })
+            .push(text);
+    }
+
+    let mut reported = TigerHashSet::default();
+    for idx in order {
+        let texts = &by_file[&idx];
+        let mut letters = 0;
+        let mut missing_letters = 0;
+        for c in texts.iter().flat_map(|text| text.as_str().chars()) {
+            if c.is_alphabetic() {
+                letters += 1;
+                if !covered(c) {
+                    missing_letters += 1;
+                }
+            }
+        }
+        if missing_letters * 2 > letters {
+            let msg = format!(
+                "most of the text in this file is in a script that the {lang} fonts do not support"
+            );
+            let info =
+                "the file may be in the wrong language, or in the wrong localization directory";
+            err(ErrorKey::MissingGlyph).msg(msg).info(info).loc(texts[0]).push();
+            continue;
+        }
+        for text in texts {
+            for c in text.as_str().chars() {
+                if !covered(c) && reported.insert(c) {
+                    let msg = format!(
+                        "character `{c}` (U+{:04X}) is not in any of the {lang} fonts",
+                        u32::from(c)
+                    );
+                    let info = "it will be shown as an empty box";
+                    warn(ErrorKey::MissingGlyph).msg(msg).info(info).loc(*text).push();
+                }
+            }
+        }
+    }
+}
+
+/// Estimates the widths of texts in the fonts of each language.
+#[derive(Debug, Default)]
+pub struct TextWidths {
+    /// The fonts to use for each language and font name, in order of preference.
+    /// Filled in on first use, so that `measure` only holds the locks for lookups.
+    fonts_for: RwLock<TigerHashMap<(&'static str, Option<&'static str>), FontList>>,
+    files: Mutex<FontCache>,
+}
+
+type FontList = Arc<Vec<Arc<FontInfo>>>;
+
+impl TextWidths {
+    /// Estimate the width of `text` in language `lang`, in units of the font size. `font` is the
+    /// name of a font from the `.font` files, if known. Returns `None` if there are no usable
+    /// fonts for the language.
+    pub fn measure(
+        &self,
+        text: &str,
+        lang: &'static str,
+        font: Option<&'static str>,
+        data: &Everything,
+    ) -> Option<f64> {
+        let infos = self.fonts(lang, font, data);
+        if infos.is_empty() {
+            return None;
+        }
+        // Characters that are not in the fonts are shown as boxes, about as wide as an `n`.
+        let fallback = infos.iter().find_map(|info| info.advance('n')).unwrap_or(0.5);
+        let mut width = 0.0;
+        for c in text.chars().filter(|c| !c.is_control()) {
+            width += infos.iter().find_map(|info| info.advance(c)).unwrap_or(fallback);
+        }
+        Some(width)
+    }
+
+    fn fonts(&self, lang: &'static str, font: Option<&'static str>, data: &Everything) -> FontList {
+        if let Some(infos) = self.fonts_for.read().unwrap().get(&(lang, font)) {
+            return Arc::clone(infos);
+        }
+        let files = font_files_for(lang, font, data);
+        let infos = {
+            let mut cache = self.files.lock().unwrap();
+            for file in &files {
+                cache.load(file, data);
+            }
+            files.iter().filter_map(|file| cache.get_arc(file)).collect()
+        };
+        let mut fonts_for = self.fonts_for.write().unwrap();
+        Arc::clone(fonts_for.entry((lang, font)).or_insert_with(|| Arc::new(infos)))
+    }
+}
+
+/// What is known about a font file.
+#[derive(Debug, Default)]
+struct FontInfo {
+    /// The glyph index of each character the font has.
+    glyphs: TigerHashMap<char, u32>,
+    /// The advance widths of the glyphs, indexed by glyph. Glyphs past the end have the same
+    /// width as the last one.
+    advances: Vec<u32>,
+    units_per_em: u32,
+}
+
+impl FontInfo {
+    /// The advance width of a character, as a fraction of the font size.
+    fn advance(&self, c: char) -> Option<f64> {
+        let glyph = to_usize(*self.glyphs.get(&c)?);
+        let advance = self.advances.get(glyph).or(self.advances.last())?;
+        Some(f64::from(*advance) / f64::from(self.units_per_em.max(1)))
+    }
+}
+
+/// The font files that have been read so far.
+#[derive(Debug, Default)]
+struct FontCache {
+    /// Font file path to what is known about it, or `None` if it could not be read.
+    fonts: TigerHashMap<&'static str, Option<Arc<FontInfo>>>,
+}
+
+impl FontCache {
+    fn get(&self, file: &Token) -> Option<&FontInfo> {
+        self.fonts.get(file.as_str()).and_then(Option::as_deref)
+    }
+
+    fn get_arc(&self, file: &Token) -> Option<Arc<FontInfo>> {
+        self.fonts.get(file.as_str()).and_then(Option::clone)
+    }
+
+    fn load(&mut self, file: &Token, data: &Everything) {
+        self.fonts.entry(file.as_str()).or_insert_with(|| {
+            // Missing files are reported by the `fontfiles` validation.
+            let entry = data.fileset.get_entry(file.as_str())?;
+            match read_font(entry) {
+                Ok(Some(info)) => Some(Arc::new(info)),
+                Ok(None) => {
+                    let msg = "could not find a unicode character map in this font";
+                    warn(ErrorKey::FontFormat).msg(msg).loc(entry).push();
+                    None
+                }
+                Err(e) => {
+                    let msg = format!("could not read font: {e:#}");
+                    err(ErrorKey::FontFormat).msg(msg).loc(entry).push();
+                    None
+                }
+            }
+        });
+    }
+}
+
+/// Read the character map and glyph widths of a `.ttf` or `.otf` file.
+/// Returns `Ok(None)` if the file is not in a format that is understood.
+fn read_font(entry: &FileEntry) -> Result<Option<FontInfo>> {
+    let mut f = File::open(entry.fullpath())?;
+    let mut header = [0; SFNT_HEADER_SIZE];
+    f.read_exact(&mut header)?;
+
+    // For font collections, use the first font.
+    if header.starts_with(b"ttcf") {
+        let mut offset = [0; 4];
+        f.read_exact(&mut offset)?;
+        f.seek(SeekFrom::Start(u64::from(from_be32(&offset, 0).unwrap_or(0))))?;
+        f.read_exact(&mut header)?;
+    }
+    if !matches!(&header[..4], b"\x00\x01\x00\x00" | b"OTTO" | b"true") {
+        return Ok(None);
+    }
+
+    let num_tables = to_usize(from_be16(&header, 4).unwrap_or(0));
+    let mut records = vec![0; num_tables * TABLE_RECORD_SIZE];
+    f.read_exact(&mut records)?;
+    let Some(cmap) = read_table(&mut f, &records, b"cmap")? else {
+        return Ok(None);
+    };
+    let Some(glyphs) = parse_cmap(&cmap) else {
+        return Ok(None);
+    };
+    let mut info = FontInfo { glyphs, advances: Vec::new(), units_per_em: 1000 };
+    if let Some(head) = read_table(&mut f, &records, b"head")? {
+        info.units_per_em = from_be16(&head, 18).unwrap_or(1000);
+    }
+    if let (Some(hhea), Some(hmtx)) =
+        (read_table(&mut f, &records, b"hhea")?, read_table(&mut f, &records, b"hmtx")?)
+    {
+        let num_metrics = to_usize(from_be16(&hhea, 34).unwrap_or(0));
+        info.advances = (0..num_metrics).map_while(|i| from_be16(&hmtx, i * 4)).collect();
+    }
+    Ok(Some(info))
+}
+
+/// Read the font table with the given tag, if it exists.
+fn read_table(f: &mut File, records: &[u8], tag: &[u8]) -> Result<Option<Vec<u8>>> {
+    let record = records.chunks_exact(TABLE_RECORD_SIZE).find(|record| record.starts_with(tag));
+    let Some(record) = record else {
+        return Ok(None);
+    };
+    let (Some(offset), Some(length)) = (from_be32(record, 8), from_be32(record, 12)) else {
+        return Ok(None);
+    };
+    // A corrupt length could make us allocate gigabytes, so check it against the file size.
+    if u64::from(offset) + u64::from(length) > f.metadata()?.len() {
+        let msg =
+            format!("table {} extends past the end of the file", String::from_utf8_lossy(tag));
+        return Err(Error::new(ErrorKind::InvalidData, msg));
+    }
+    // Table offsets are from the start of the file, even in font collections.
+    f.seek(SeekFrom::Start(u64::from(offset)))?;
+    let mut table = vec![0; to_usize(length)];
+    f.read_exact(&mut table)?;
+    Ok(Some(table))
+}
+
+/// Parse a `cmap` table and collect the characters from its unicode subtables.
+fn parse_cmap(table: &[u8]) -> Option<TigerHashMap<char, u32>> {
+    let num_subtables = to_usize(from_be16(table, 2)?);
+    let mut chars = TigerHashMap::default();
+    let mut found = false;
+    for i in 0..num_subtables {
+        let record = 4 + i * CMAP_RECORD_SIZE;
+        let platform = from_be16(table, record)?;
+        let encoding = from_be16(table, record + 2)?;
+        // Platform 0 is unicode, and platform 3 (windows) with encoding 1 or 10 is unicode too.
+        if platform != 0 && !(platform == 3 && (encoding == 1 || encoding == 10)) {
+            continue;
+        }
+        let subtable = table.get(to_usize(from_be32(table, record + 4)?)..)?;
+        match from_be16(subtable, 0)? {
+            4 => found |= parse_format4(subtable, &mut chars).is_some(),
+            12 => found |= parse_format12(subtable, &mut chars).is_some(),
+            _ => (),
+        }
+    }
+    found.then_some(chars)
+}
+
+/// Format 4 maps the characters of the basic multilingual plane in segments.
+fn parse_format4(subtable: &[u8], chars: &mut TigerHashMap<char, u32>) -> Option<()> {
+    let seg_count_x2 = to_usize(from_be16(subtable, 6)?);
+    let end_codes = 14;
+    let start_codes = end_codes + seg_count_x2 + 2;
+    let id_deltas = start_codes + seg_count_x2;
+    let id_range_offsets = id_deltas + seg_count_x2;
+    for seg in (0..seg_count_x2).step_by(2) {
+        let end = from_be16(subtable, end_codes + seg)?;
+        let start = from_be16(subtable, start_codes + seg)?;
+        let delta = from_be16(subtable, id_deltas + seg)?;
+        let range_offset = to_usize(from_be16(subtable, id_range_offsets + seg)?);
+        for code in start..=end.min(0xFFFE) {
+            let glyph = if range_offset == 0 {
+                (code + delta) & 0xFFFF
+            } else {
+                let addr = id_range_offsets + seg + range_offset + 2 * to_usize(code - start);
+                match from_be16(subtable, addr) {
+                    Some(0) | None => 0,
+                    Some(glyph) => (glyph + delta) & 0xFFFF,
+                }
+            };
+            if glyph != 0 {
+                if let Some(c) = char::from_u32(code) {
+                    chars.insert(c, glyph);
+                }
+            }
+        }
+    }
+    Some(())
+}
+
+/// Format 12 maps ranges of characters from all planes to consecutive glyphs.
+fn parse_format12(subtable: &[u8], chars: &mut TigerHashMap<char, u32>) -> Option<()> {
+    let num_groups = to_usize(from_be32(subtable, 12)?);
+    for i in 0..num_groups {
+        let group = 16 + i * 12;
+        let start = from_be32(subtable, group)?;
+        let end = from_be32(subtable, group + 4)?.min(0x10_FFFF);
+        let start_glyph = from_be32(subtable, group + 8)?;
+        // Skip malformed groups whose glyph numbers would overflow.
+        if start > end || start_glyph.checked_add(end - start).is_none() {
+            continue;
+        }
+        for code in start..=end {
+            // Glyph 0 is the "missing character" box.
+            let glyph = start_glyph + (code - start);
+            if glyph != 0 {
+                if let Some(c) = char::from_u32(code) {
+                    chars.insert(c, glyph);
+                }
+            }
+        }
+    }
+    Some(())
+}
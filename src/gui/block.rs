@@ -1,9 +1,11 @@
+use std::fmt::Write as _;
 use std::sync::Arc;
 
 use crate::block::{Block, BlockItem, Comparator, Eq::Single, Field, BV};
 use crate::data::gui::{GuiTemplate, GuiType};
+use crate::datatype::Datatype;
 use crate::everything::Everything;
-use crate::gui::validate::validate_property;
+use crate::gui::validate::{validate_datatype_field_in, validate_property, DataContext};
 use crate::gui::{BuiltinWidget, GuiValidation, PropertyContainer, WidgetProperty};
 use crate::helpers::{TigerHashMap, TigerHashSet};
 use crate::lowercase::Lowercase;
@@ -15,8 +17,8 @@ use crate::token::Token;
 enum GuiItem {
     /// A property assignment.
     Property(WidgetProperty, Token, BV),
-    /// A contained widget.
-    Widget(Arc<GuiBlock>),
+    /// A contained widget, with the key that declared it.
+    Widget(Token, Arc<GuiBlock>),
     /// A property which contains other properties. It can have `Subst` blocks too.
     ComplexProperty(Arc<GuiBlock>),
     /// A property which contains a widget. It can have Subst blocks too.
@@ -78,6 +80,11 @@ impl GuiBlock {
             BlockOverrideBody(&'a Token),
         }
         let mut state = Expecting::Field;
+        // The blockoverrides of this block and of the templates it uses, in order of appearance.
+        // They are applied after the whole block has been read, so that they can override blocks in
+        // contained widgets that come after them. The bool says whether it was in this block
+        // itself rather than in a template.
+        let mut overrides = Vec::new();
 
         // Blank slate to work on
         let mut gui = Self {
@@ -128,7 +135,17 @@ impl GuiBlock {
                         } else if key_lc == "using" {
                             if let Some(value) = bv.expect_value() {
                                 if let Some(template) = templates.get(value.as_str()) {
-                                    gui.inline(&template.gui_block(types, templates));
+                                    let template = template.gui_block(types, templates);
+                                    for item in &template.items {
+                                        if let GuiItem::Override(name, gui_block) = item {
+                                            overrides.push((
+                                                name.clone(),
+                                                Arc::clone(gui_block),
+                                                false,
+                                            ));
+                                        }
+                                    }
+                                    gui.inline(&template);
                                 } else {
                                     untidy(ErrorKey::Gui).msg("template not found").loc(key).push();
                                 }
@@ -143,6 +160,7 @@ impl GuiBlock {
                                         types,
                                         templates,
                                     );
+                                    gui.substnames.extend(guiblock.substnames.iter().cloned());
                                     gui.items.push(GuiItem::ComplexProperty(guiblock));
                                 }
                             } else if validation == GuiValidation::Widget {
@@ -161,9 +179,15 @@ impl GuiBlock {
                                             types,
                                             templates,
                                         );
+                                        gui.substnames.extend(guiblock.substnames.iter().cloned());
                                         gui.items.push(GuiItem::WidgetProperty(guiblock));
                                     }
                                     _ => {
+                                        // Blockoverrides are not applied inside these, but they
+                                        // can still be the target of one.
+                                        if let Some(block) = bv.get_block() {
+                                            collect_substnames(block, &mut gui.substnames);
+                                        }
                                         gui.items.push(GuiItem::Property(
                                             prop,
                                             key.clone(),
@@ -185,7 +209,7 @@ impl GuiBlock {
                                     templates,
                                 );
                                 gui.substnames.extend(guiblock.substnames.iter().cloned());
-                                gui.items.push(GuiItem::Widget(guiblock));
+                                gui.items.push(GuiItem::Widget(key.clone(), guiblock));
                             }
                         } else if let Ok(builtin) = BuiltinWidget::try_from(&key_lc) {
                             // If we got here, then it must be a builtin but not for the current game
@@ -235,42 +259,54 @@ impl GuiBlock {
                 }
                 Expecting::BlockOverrideBody(name) => {
                     if let Some(block) = item.expect_block() {
-                        if gui.substnames.contains(name.as_str()) {
-                            let guiblock = GuiBlock::from_block(
-                                GuiBlockFrom::NoParent,
-                                block,
-                                types,
-                                templates,
-                            );
-                            gui.apply_override(name, &guiblock);
-                            gui.items.push(GuiItem::Override(name.clone(), guiblock));
-                        } else if !matches!(from, GuiBlockFrom::Template | GuiBlockFrom::NoParent) {
-                            // TODO: can't do this until we search Widget fields for overrides as well.
-                            // let msg = format!("did not find block for blockoverride `{name}`");
-                            // err(ErrorKey::Gui).msg(msg).loc(name).push();
-                        }
+                        let guiblock =
+                            GuiBlock::from_block(GuiBlockFrom::NoParent, block, types, templates);
+                        // Keep the override around so that it can be applied again when this
+                        // block is a template that gets inlined.
+                        gui.items.push(GuiItem::Override(name.clone(), Arc::clone(&guiblock)));
+                        overrides.push((name.clone(), guiblock, true));
                     }
                     state = Expecting::Field;
                 }
             }
         }
+
+        // A blockoverride in this block itself wins over one with the same name from a template,
+        // wherever the `using` is. Each override applied to a block replaces the earlier ones with
+        // the same name, so the last one applied would win. Skip the template's one instead.
+        let direct_names: TigerHashSet<&str> = overrides
+            .iter()
+            .filter(|(_, _, direct)| *direct)
+            .map(|(name, _, _)| name.as_str())
+            .collect();
+        for (name, guiblock, direct) in &overrides {
+            if !direct && direct_names.contains(name.as_str()) {
+                continue;
+            }
+            if !gui.apply_override(name, guiblock)
+                && *direct
+                && !matches!(from, GuiBlockFrom::Template | GuiBlockFrom::NoParent)
+            {
+                let msg = format!("did not find block for blockoverride `{name}`");
+                let info = "there is no `block` with this name in the widget, its type, its templates, or its children";
+                warn(ErrorKey::Gui).weak().msg(msg).info(info).loc(name).push();
+            }
+        }
         Arc::new(gui)
     }
 
+    /// Add the items of a template to this block. The template's blockoverrides are not applied
+    /// here; that is up to the caller.
     pub fn inline(&mut self, other: &Arc<GuiBlock>) {
         self.substnames.extend(other.substnames.iter().cloned());
-        for item in &other.items {
-            if let GuiItem::Override(name, gui_block) = item {
-                self.apply_override(name, gui_block);
-            }
-            self.items.push(item.clone());
-        }
+        self.items.extend(other.items.iter().cloned());
     }
 
-    // TODO: return an indication of whether the blockoverride found a block
-    pub fn apply_override(&mut self, name: &Token, overrideblock: &Arc<GuiBlock>) {
+    /// Replace the contents of all `block`s called `name` in this block and its children with
+    /// `overrideblock`. Returns whether any such block was found.
+    pub fn apply_override(&mut self, name: &Token, overrideblock: &Arc<GuiBlock>) -> bool {
         if !self.substnames.contains(name.as_str()) {
-            return;
+            return false;
         }
 
         self.substnames.extend(overrideblock.substnames.iter().cloned());
@@ -282,7 +318,7 @@ impl GuiBlock {
         for item in &mut self.items {
             match item {
                 GuiItem::Property(_, _, _) | GuiItem::Override(_, _) => (),
-                GuiItem::Widget(gui)
+                GuiItem::Widget(_, gui)
                 | GuiItem::ComplexProperty(gui)
                 | GuiItem::WidgetProperty(gui) => {
                     *gui = Self::apply_override_arc(gui, name, overrideblock);
@@ -290,10 +326,13 @@ impl GuiBlock {
                 GuiItem::Subst(substname, gui) => {
                     if name.is(substname) {
                         *gui = Arc::clone(overrideblock);
+                    } else {
+                        *gui = Self::apply_override_arc(gui, name, overrideblock);
                     }
                 }
             }
         }
+        true
     }
 
     // TODO: this could maybe be made more efficient by checking substnames before the call,
@@ -308,34 +347,247 @@ impl GuiBlock {
         }
 
         let gui_mut = Arc::make_mut(gui); // clones the inner GuiBlock if needed
-        gui_mut.apply_override(name, overrideblock);
+        _ = gui_mut.apply_override(name, overrideblock);
         Arc::clone(gui)
     }
 
     /// Validate the property fields of this [`GuiBlock`] and all its contents.
     ///
     /// `container` is extra information to be used if `self.container` is `None`.
-    pub fn validate(&self, container: Option<PropertyContainer>, data: &Everything) {
+    /// `context` is the set of datacontexts established by its parents.
+    pub fn validate(
+        &self,
+        container: Option<PropertyContainer>,
+        context: &DataContext,
+        data: &Everything,
+    ) {
+        // The datacontexts set here apply to all the other fields, so evaluate them first.
+        let mut inner = context.clone();
+        self.collect_datacontexts(context, &mut inner, data);
+        self.validate_items(container, &inner, data);
+    }
+
+    /// Add the types of the `datacontext` fields of this block and its base types to `inner`.
+    fn collect_datacontexts(
+        &self,
+        outer: &DataContext,
+        inner: &mut DataContext,
+        data: &Everything,
+    ) {
+        if let Some(base) = &self.base {
+            base.collect_datacontexts(outer, inner, data);
+        }
+        for item in &self.items {
+            match item {
+                GuiItem::Property(WidgetProperty::datacontext, key, bv) => {
+                    inner.add(validate_datatype_field_in(
+                        Datatype::Unknown,
+                        key,
+                        bv,
+                        data,
+                        true,
+                        outer,
+                    ));
+                }
+                GuiItem::Subst(_, gui_block) => {
+                    gui_block.collect_datacontexts(outer, inner, data);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn validate_items(
+        &self,
+        container: Option<PropertyContainer>,
+        context: &DataContext,
+        data: &Everything,
+    ) {
         let container = self.container.or(container);
         if let Some(base) = &self.base {
-            base.validate(container, data);
+            base.validate_items(container, context, data);
         }
 
         for item in &self.items {
             match item {
+                // Datacontexts were already validated, and overrides were already applied.
+                GuiItem::Property(WidgetProperty::datacontext, _, _) | GuiItem::Override(_, _) => {}
                 GuiItem::Property(prop, key, bv) => {
-                    validate_property(*prop, container, key, bv, data);
+                    validate_property(*prop, container, key, bv, data, context);
                 }
                 GuiItem::Subst(_, gui_block) => {
-                    gui_block.validate(container, data);
+                    gui_block.validate_items(container, context, data);
+                }
+                GuiItem::Widget(_, gui_block) | GuiItem::ComplexProperty(gui_block) => {
+                    gui_block.validate(None, context, data);
+                }
+                GuiItem::WidgetProperty(gui_block) => {
+                    if gui_block.container
+                        == Some(PropertyContainer::WidgetProperty(WidgetProperty::item))
+                    {
+                        // The widgets of a datamodel get each element of the model as datacontext.
+                        let mut context = context.clone();
+                        context.open();
+                        gui_block.validate(None, &context, data);
+                    } else {
+                        gui_block.validate(None, context, data);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Count the widgets directly contained in this block, including the ones from its base types.
+    pub fn count_widgets(&self) -> usize {
+        self.widgets().len()
+    }
+
+    /// The ultimate builtin type of this widget, if known.
+    pub fn builtin(&self) -> Option<BuiltinWidget> {
+        match self.container {
+            Some(PropertyContainer::BuiltinWidget(builtin)) => Some(builtin),
+            _ => None,
+        }
+    }
+
+    /// The property fields of this block, including the ones from its base types and its
+    /// `block`s, in the order in which they take effect.
+    pub fn properties(&self) -> Vec<(WidgetProperty, &Token, &BV)> {
+        let mut vec = self.base.as_ref().map_or_else(Vec::new, |base| base.properties());
+        for item in &self.items {
+            match item {
+                GuiItem::Property(prop, key, bv) => vec.push((*prop, key, bv)),
+                GuiItem::Subst(_, gui_block) => vec.extend(gui_block.properties()),
+                _ => (),
+            }
+        }
+        vec
+    }
+
+    /// The widgets directly contained in this block, including the ones from its base types and
+    /// its `block`s, together with the keys that declared them.
+    pub fn widgets(&self) -> Vec<(&Token, &Arc<GuiBlock>)> {
+        let mut vec = self.base.as_ref().map_or_else(Vec::new, |base| base.widgets());
+        for item in &self.items {
+            match item {
+                GuiItem::Widget(key, gui_block) => vec.push((key, gui_block)),
+                GuiItem::Subst(_, gui_block) => vec.extend(gui_block.widgets()),
+                _ => (),
+            }
+        }
+        vec
+    }
+
+    /// Write this block, with its types, templates and blockoverrides fully expanded, to `out` in
+    /// `.gui` file syntax. This is meant to help modders see what the game will make of a widget.
+    pub fn dump(&self, key: &str, indent: usize, out: &mut String) {
+        let pad = "\t".repeat(indent);
+        let _ = writeln!(out, "{pad}{key} = {{");
+        self.dump_items(indent + 1, out);
+        let _ = writeln!(out, "{pad}}}");
+    }
+
+    fn dump_items(&self, indent: usize, out: &mut String) {
+        let pad = "\t".repeat(indent);
+        if let Some(base) = &self.base {
+            base.dump_items(indent, out);
+        }
+        for item in &self.items {
+            match item {
+                GuiItem::Property(_, key, bv) => dump_bv(key.as_str(), bv, indent, out),
+                GuiItem::Widget(key, gui_block) => gui_block.dump(key.as_str(), indent, out),
+                GuiItem::ComplexProperty(gui_block) | GuiItem::WidgetProperty(gui_block) => {
+                    let key = match gui_block.container {
+                        Some(
+                            PropertyContainer::ComplexProperty(prop)
+                            | PropertyContainer::WidgetProperty(prop),
+                        ) => prop.to_string(),
+                        _ => "<unknown>".to_owned(),
+                    };
+                    gui_block.dump(&key, indent, out);
                 }
-                GuiItem::Widget(gui_block)
-                | GuiItem::ComplexProperty(gui_block)
-                | GuiItem::WidgetProperty(gui_block) => {
-                    gui_block.validate(None, data);
+                GuiItem::Subst(name, gui_block) => {
+                    let _ = writeln!(out, "{pad}# block \"{name}\"");
+                    gui_block.dump_items(indent, out);
                 }
                 GuiItem::Override(_, _) => (),
             }
         }
     }
 }
+
+/// Add the names of all `block`s in a [`Block`] and its sub-blocks to `substnames`.
+fn collect_substnames(block: &Block, substnames: &mut TigerHashSet<String>) {
+    let mut expecting_name = false;
+    for item in block.iter_items() {
+        match item {
+            BlockItem::Value(token) => {
+                if expecting_name {
+                    substnames.insert(token.to_string());
+                }
+                expecting_name = token.lowercase_is("block");
+            }
+            BlockItem::Field(Field(key, _, bv)) => {
+                expecting_name = false;
+                match bv {
+                    BV::Value(value) if key.lowercase_is("block") => {
+                        substnames.insert(value.to_string());
+                    }
+                    BV::Value(_) => (),
+                    BV::Block(block) => collect_substnames(block, substnames),
+                }
+            }
+            BlockItem::Block(block) => {
+                expecting_name = false;
+                collect_substnames(block, substnames);
+            }
+        }
+    }
+}
+
+/// Write a field from a [`Block`] to `out` in `.gui` file syntax.
+fn dump_bv(key: &str, bv: &BV, indent: usize, out: &mut String) {
+    let pad = "\t".repeat(indent);
+    match bv {
+        BV::Value(value) => {
+            let _ = writeln!(out, "{pad}{key} = {}", quote_if_needed(value.as_str()));
+        }
+        BV::Block(block) if block.iter_items().all(|item| matches!(item, BlockItem::Value(_))) => {
+            let values: Vec<String> =
+                block.iter_values().map(|value| quote_if_needed(value.as_str())).collect();
+            let _ = writeln!(out, "{pad}{key} = {{ {} }}", values.join(" "));
+        }
+        BV::Block(block) => {
+            let _ = writeln!(out, "{pad}{key} = {{");
+            dump_block_items(block, indent + 1, out);
+            let _ = writeln!(out, "{pad}}}");
+        }
+    }
+}
+
+fn dump_block_items(block: &Block, indent: usize, out: &mut String) {
+    let pad = "\t".repeat(indent);
+    for item in block.iter_items() {
+        match item {
+            BlockItem::Value(value) => {
+                let _ = writeln!(out, "{pad}{}", quote_if_needed(value.as_str()));
+            }
+            BlockItem::Field(Field(key, _, bv)) => dump_bv(key.as_str(), bv, indent, out),
+            BlockItem::Block(block) => {
+                let _ = writeln!(out, "{pad}{{");
+                dump_block_items(block, indent + 1, out);
+                let _ = writeln!(out, "{pad}}}");
+            }
+        }
+    }
+}
+
+fn quote_if_needed(value: &str) -> String {
+    if !value.is_empty()
+        && value.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '%'))
+    {
+        value.to_owned()
+    } else {
+        format!("\"{value}\"")
+    }
+}
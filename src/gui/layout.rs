@@ -0,0 +1,357 @@
+//! Approximate layout of static widgets, to catch widgets that end up where nobody can see them.
+//!
+//! Widgets inside layout containers such as `hbox` are placed by the game, so only their sizes are
+//! checked. Anything computed by datatype expressions is ignored.
+//!
+//! Static texts are measured with the fonts of each language, to catch translations that don't fit.
+//! This reads the font files, so it is done separately from the other checks, by
+//! [`Everything::check_fonts`].
+
+#[cfg(feature = "ck3")]
+use crate::block::Block;
+use crate::block::BV;
+use crate::data::localization::PreviewFormat;
+use crate::everything::Everything;
+use crate::gui::{BuiltinWidget, GuiBlock, WidgetProperty};
+use crate::lowercase::Lowercase;
+use crate::report::{warn, ErrorKey};
+use crate::token::Token;
+
+/// The screen size that top-level widgets are laid out in.
+const SCREEN_SIZE: (f64, f64) = (1920.0, 1080.0);
+
+/// A length in a `size` or `position` property.
+#[derive(Debug, Clone, Copy)]
+enum Length {
+    Pixels(f64),
+    Percent(f64),
+}
+
+impl Length {
+    fn parse(token: &Token) -> Option<Self> {
+        if let Some(percent) = token.as_str().strip_suffix('%') {
+            percent.parse().ok().map(Length::Percent)
+        } else {
+            token.get_number().map(Length::Pixels)
+        }
+    }
+
+    fn resolve(self, parent: Option<f64>) -> Option<f64> {
+        match self {
+            Length::Pixels(pixels) => Some(pixels),
+            Length::Percent(percent) => parent.map(|parent| parent * percent / 100.0),
+        }
+    }
+}
+
+/// Where a widget ended up relative to its parent.
+#[derive(Debug, Default)]
+struct Placement {
+    size: Option<(f64, f64)>,
+    position: Option<(f64, f64)>,
+    /// The point on the parent that the widget is attached to, as fractions of its size.
+    parentanchor: Option<(f64, f64)>,
+    /// The point on the widget that is attached to the parent, as fractions of its size.
+    widgetanchor: Option<(f64, f64)>,
+    resizeparent: bool,
+}
+
+/// Which reports [`check_widget`] makes. The widgets are laid out the same way for both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Check {
+    /// Sizes, positions and frame textures.
+    Layout,
+    /// Static texts that are too wide for their widgets.
+    TextWidths,
+}
+
+/// Check the sizes and positions of `gui_block` and all the widgets it contains.
+/// `key` is the widget type or builtin that `gui_block` was declared with.
+pub fn check_layout(key: &Token, gui_block: &GuiBlock, data: &Everything) {
+    check_widget(key, gui_block, Some(SCREEN_SIZE), Check::Layout, data);
+}
+
+/// Check that the static texts in `gui_block` and all the widgets it contains fit in their
+/// widgets, in each of the mod's languages.
+pub fn check_text_widths(key: &Token, gui_block: &GuiBlock, data: &Everything) {
+    check_widget(key, gui_block, Some(SCREEN_SIZE), Check::TextWidths, data);
+}
+
+fn check_widget(
+    key: &Token,
+    gui_block: &GuiBlock,
+    parent_size: Option<(f64, f64)>,
+    check: Check,
+    data: &Everything,
+) -> Placement {
+    let mut placement = Placement::default();
+    let mut minimumsize = None;
+    let mut maximumsize = None;
+    #[cfg(feature = "ck3")]
+    let mut texture = None;
+    #[cfg(feature = "ck3")]
+    let mut framesize = None;
+    #[cfg(feature = "ck3")]
+    let mut frame = None;
+    let mut text = TextSettings::default();
+
+    // Later fields override earlier ones, so just keep the last one of each.
+    for (prop, key, bv) in gui_block.properties() {
+        match prop {
+            WidgetProperty::size => {
+                placement.size = None;
+                if let Some((w, h)) = two_lengths(bv) {
+                    for length in [w, h] {
+                        if check == Check::Layout
+                            && matches!(length, Length::Pixels(n) | Length::Percent(n) if n < 0.0)
+                        {
+                            let msg = "negative size";
+                            warn(ErrorKey::Gui).msg(msg).loc(key).push();
+                        }
+                    }
+                    let (pw, ph) = parent_size.unzip();
+                    placement.size = w.resolve(pw).zip(h.resolve(ph));
+                }
+            }
+            WidgetProperty::position => {
+                placement.position = None;
+                if let Some((x, y)) = two_lengths(bv) {
+                    let (pw, ph) = parent_size.unzip();
+                    placement.position = x.resolve(pw).zip(y.resolve(ph));
+                }
+            }
+            WidgetProperty::minimumsize => minimumsize = two_lengths(bv).map(|pair| (key, pair)),
+            WidgetProperty::maximumsize => maximumsize = two_lengths(bv).map(|pair| (key, pair)),
+            WidgetProperty::parentanchor => {
+                placement.parentanchor = bv.get_value().and_then(anchor);
+            }
+            WidgetProperty::widgetanchor => {
+                placement.widgetanchor = bv.get_value().and_then(anchor);
+            }
+            WidgetProperty::resizeparent => {
+                placement.resizeparent = bv.get_value().is_some_and(|v| v.is("yes"));
+            }
+            #[cfg(feature = "ck3")]
+            WidgetProperty::texture => texture = bv.get_value().filter(|v| !v.starts_with("[")),
+            #[cfg(feature = "ck3")]
+            WidgetProperty::framesize => framesize = bv.get_block().map(two_integers),
+            #[cfg(feature = "ck3")]
+            WidgetProperty::frame => frame = bv.get_value().and_then(Token::get_integer),
+            WidgetProperty::text => text.key = bv.get_value(),
+            WidgetProperty::font => text.font = bv.get_value(),
+            WidgetProperty::default_format => text.default_format = bv.get_value(),
+            WidgetProperty::fontsize => text.fontsize = bv.get_value().and_then(Token::get_number),
+            WidgetProperty::max_width => {
+                text.max_width = bv.get_value().and_then(Token::get_number);
+            }
+            WidgetProperty::margin => text.margin = two_lengths(bv),
+            WidgetProperty::multiline | WidgetProperty::autoresize => {
+                let yes = bv.get_value().is_some_and(|v| v.is("yes"));
+                if prop == WidgetProperty::multiline {
+                    text.multiline = yes;
+                } else {
+                    text.autoresize = yes;
+                }
+            }
+            // Texts with these settings adapt to the space they get.
+            WidgetProperty::elide | WidgetProperty::fontsize_min => text.adapts = true,
+            _ => (),
+        }
+    }
+
+    if check == Check::Layout {
+        check_minmax(minimumsize, maximumsize);
+    }
+
+    // The frame texture check has only been verified against ck3 textures.
+    #[cfg(feature = "ck3")]
+    if check == Check::Layout {
+        if let (Some(texture), Some(Some((width, height)))) = (texture, framesize) {
+            if let (Ok(width), Ok(height)) = (u32::try_from(width), u32::try_from(height)) {
+                let frame = frame.and_then(|f| u32::try_from(f).ok()).unwrap_or(1);
+                data.dds.validate_frame(texture, width, height, frame);
+            }
+        }
+    }
+
+    if let Some(key) = text.key.filter(|_| check == Check::TextWidths) {
+        let width = if text.max_width.is_some() {
+            text.max_width
+        } else if text.autoresize {
+            maximumsize.and_then(|(_, (w, _))| w.resolve(None)).filter(|w| *w >= 0.0)
+        } else {
+            placement.size.map(|(w, _)| w)
+        };
+        if let Some(width) = width {
+            let margin = text.margin.and_then(|(x, _)| x.resolve(Some(width))).unwrap_or(0.0);
+            check_text_width(key, &text, width - 2.0 * margin, data);
+        }
+    }
+
+    let children = gui_block.widgets();
+    let check_bounds = placement.size.is_some_and(|(w, h)| w > 0.0 && h > 0.0)
+        && gui_block
+            .builtin()
+            .or_else(|| BuiltinWidget::builtin_current_game(&Lowercase::new(key.as_str())))
+            .is_some_and(is_static);
+    let mut placements = Vec::new();
+    for (key, child) in &children {
+        placements.push((key, check_widget(key, child, placement.size, check, data)));
+    }
+    // If a child resizes its parent, the parent's size is not the one in its `size` field.
+    if check == Check::Layout && check_bounds && !placements.iter().any(|(_, p)| p.resizeparent) {
+        let parent_size = placement.size.unwrap();
+        for (key, child) in &placements {
+            check_bounds_in_parent(key, child, parent_size);
+        }
+    }
+    placement
+}
+
+/// Warn if the widget's `minimumsize` is larger than its `maximumsize`.
+fn check_minmax(
+    minimumsize: Option<(&Token, (Length, Length))>,
+    maximumsize: Option<(&Token, (Length, Length))>,
+) {
+    if let (Some((key, (min_w, min_h))), Some((_, (max_w, max_h)))) = (minimumsize, maximumsize) {
+        // A negative maximum means there is no maximum.
+        for (min, max) in [(min_w, max_w), (min_h, max_h)] {
+            if let (Length::Pixels(min), Length::Pixels(max)) = (min, max) {
+                if max >= 0.0 && min > max {
+                    let msg = format!("minimumsize {min} is larger than maximumsize {max}");
+                    warn(ErrorKey::Gui).msg(msg).loc(key).push();
+                }
+            }
+        }
+    }
+}
+
+fn check_bounds_in_parent(key: &Token, child: &Placement, (pw, ph): (f64, f64)) {
+    let Some((cw, ch)) = child.size else {
+        return;
+    };
+    if cw <= 0.0 || ch <= 0.0 {
+        return;
+    }
+    let (px, py) = child.position.unwrap_or((0.0, 0.0));
+    let parentanchor = child.parentanchor.unwrap_or((0.0, 0.0));
+    let widgetanchor = child.widgetanchor.unwrap_or(parentanchor);
+    let x = pw * parentanchor.0 + px - cw * widgetanchor.0;
+    let y = ph * parentanchor.1 + py - ch * widgetanchor.1;
+    if x >= pw || y >= ph || x + cw <= 0.0 || y + ch <= 0.0 {
+        let msg = "widget is positioned completely outside its parent";
+        let info = format!(
+            "the parent is {pw}x{ph} and this widget is {cw}x{ch} at position ({x}, {y}) within it"
+        );
+        warn(ErrorKey::Gui).weak().msg(msg).info(info).loc(key).push();
+    }
+}
+
+/// The properties of a widget that determine how wide its text is.
+#[derive(Debug, Default)]
+struct TextSettings<'a> {
+    key: Option<&'a Token>,
+    font: Option<&'a Token>,
+    fontsize: Option<f64>,
+    default_format: Option<&'a Token>,
+    max_width: Option<f64>,
+    margin: Option<(Length, Length)>,
+    multiline: bool,
+    autoresize: bool,
+    /// Whether the text is elided or shrunk when it doesn't fit.
+    adapts: bool,
+}
+
+/// Estimate whether a static text fits in `width` pixels, in each of the mod's languages.
+fn check_text_width(key: &Token, text: &TextSettings, width: f64, data: &Everything) {
+    // The font size comes from the widget or its types, or else from its default format.
+    // If neither says, the size is unknown and the text can't be measured.
+    let fontsize = text.fontsize.or_else(|| {
+        text.default_format
+            .and_then(|format| data.gui.format_setting(format.as_str(), "fontsize"))
+            .and_then(|size| size.parse().ok())
+    });
+    let Some(fontsize) = fontsize else {
+        return;
+    };
+    let font = text.font.map(Token::as_str).or_else(|| {
+        text.default_format.and_then(|format| data.gui.format_setting(format.as_str(), "font"))
+    });
+    if text.multiline || text.adapts || width <= 0.0 || key.starts_with("[") {
+        return;
+    }
+    for lang in data.localization.mod_langs() {
+        let Some(rendered) =
+            data.localization.preview(key.as_str(), lang, PreviewFormat::Plain, data)
+        else {
+            continue;
+        };
+        let mut longest: f64 = 0.0;
+        for line in rendered.lines() {
+            if let Some(em) = data.gui.text_widths().measure(line, lang, font, data) {
+                longest = longest.max(em * fontsize);
+            }
+        }
+        // Allow for some inaccuracy in the estimate.
+        if longest > width + 2.0 {
+            let msg = format!("the {lang} text of `{key}` is probably too wide for this widget");
+            let info = format!(
+                "it is about {longest:.0} pixels wide at font size {fontsize}, but there are {width:.0} pixels; consider `multiline`, `elide` or `fontsize_min`"
+            );
+            warn(ErrorKey::Gui).weak().msg(msg).info(info).loc(key).push();
+        }
+    }
+}
+
+/// Widgets that don't move or resize their children.
+fn is_static(builtin: BuiltinWidget) -> bool {
+    matches!(
+        builtin,
+        BuiltinWidget::widget
+            | BuiltinWidget::window
+            | BuiltinWidget::icon
+            | BuiltinWidget::button
+            | BuiltinWidget::checkbutton
+    )
+}
+
+fn two_lengths(bv: &BV) -> Option<(Length, Length)> {
+    let block = bv.get_block()?;
+    let values: Vec<&Token> = block.iter_values().collect();
+    if values.len() == 2 && block.iter_items().count() == 2 {
+        Length::parse(values[0]).zip(Length::parse(values[1]))
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "ck3")]
+fn two_integers(block: &Block) -> Option<(i64, i64)> {
+    let values: Vec<&Token> = block.iter_values().collect();
+    if values.len() == 2 && block.iter_items().count() == 2 {
+        values[0].get_integer().zip(values[1].get_integer())
+    } else {
+        None
+    }
+}
+
+/// Parse an `Align` value into fractions of the width and height.
+fn anchor(value: &Token) -> Option<(f64, f64)> {
+    if value.starts_with("[") {
+        return None;
+    }
+    let mut anchor = (0.0, 0.0);
+    for part in value.split('|') {
+        match part.as_str() {
+            "left" => anchor.0 = 0.0,
+            "hcenter" => anchor.0 = 0.5,
+            "right" => anchor.0 = 1.0,
+            "top" => anchor.1 = 0.0,
+            "vcenter" => anchor.1 = 0.5,
+            "bottom" => anchor.1 = 1.0,
+            "center" => anchor = (0.5, 0.5),
+            _ => (),
+        }
+    }
+    Some(anchor)
+}
@@ -3,12 +3,15 @@
 pub use self::block::{GuiBlock, GuiBlockFrom};
 pub use self::builtins::BuiltinWidget;
 pub use self::categories::GuiCategories;
+pub use self::layout::{check_layout, check_text_widths};
 pub use self::properties::{GuiValidation, PropertyContainer, WidgetProperty};
 #[cfg(any(feature = "ck3", feature = "vic3"))]
 pub use self::validate::validate_datatype_field;
+pub use self::validate::DataContext;
 
 mod block;
 mod builtins;
 mod categories;
+mod layout;
 mod properties;
 mod validate;
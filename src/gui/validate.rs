@@ -1,13 +1,15 @@
 use crate::block::{Block, BV};
 use crate::context::ScopeContext;
 use crate::data::localization::LocaValue;
-use crate::datatype::{validate_datatypes, Datatype};
+use crate::datatype::{
+    datacontext_promote, validate_datatypes_from, validate_format, CodeChain, Datatype,
+};
 use crate::everything::Everything;
 #[cfg(feature = "ck3")]
 use crate::game::Game;
 use crate::game::GameFlags;
 use crate::gui::properties::{ALIGN, BLENDMODES};
-use crate::gui::{GuiCategories, GuiValidation, PropertyContainer, WidgetProperty};
+use crate::gui::{GuiBlockFrom, GuiCategories, GuiValidation, PropertyContainer, WidgetProperty};
 use crate::helpers::stringify_choices;
 use crate::item::Item;
 use crate::parse::localization::ValueParser;
@@ -16,12 +18,70 @@ use crate::scopes::Scopes;
 use crate::token::Token;
 use crate::validator::Validator;
 
+/// The types of the datacontexts that are available to a widget. These are set by the `datacontext`
+/// fields of the widget and its parents.
+#[derive(Clone, Debug, Default)]
+pub struct DataContext {
+    types: Vec<Datatype>,
+    /// Whether there are datacontexts whose type is not known, such as the items of a `datamodel`.
+    open: bool,
+    /// Whether this is inside a `recursive = yes` widget that is being expanded for validation.
+    /// Those are not expanded again inside each other, because they can contain themselves.
+    recursive: bool,
+}
+
+impl DataContext {
+    pub fn add(&mut self, dtype: Datatype) {
+        if dtype == Datatype::Unknown {
+            self.open = true;
+        } else if !self.types.contains(&dtype) {
+            self.types.push(dtype);
+        }
+    }
+
+    /// Mark this context as having a datacontext of unknown type.
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    /// A context for gui types and templates validated on their own. Their datacontexts come from
+    /// the widgets that use them, which check their contents again with the real context.
+    pub fn unknown() -> Self {
+        Self { open: true, ..Self::default() }
+    }
+
+    /// Return the type that the chain starts from if its first code is a datatype name, like the
+    /// `Character` in `[Character.GetName]`, that refers to one of the available datacontexts.
+    /// Warn if there is no such datacontext.
+    fn start_type(&self, chain: &CodeChain) -> Option<Datatype> {
+        if chain.codes.len() < 2 {
+            return None;
+        }
+        let name = &chain.codes[0].name;
+        let dtype = datacontext_promote(name.as_str())?;
+        if self.types.contains(&dtype) {
+            return Some(dtype);
+        }
+        if !self.open && !self.types.is_empty() {
+            let msg = format!("no {dtype} datacontext is set here");
+            let types: Vec<String> = self.types.iter().map(ToString::to_string).collect();
+            let info = format!(
+                "the datacontexts set by this widget and its parents are {}; the game may provide others",
+                types.join(", ")
+            );
+            warn(ErrorKey::Gui).weak().msg(msg).info(info).loc(name).push();
+        }
+        None
+    }
+}
+
 pub fn validate_property(
     property: WidgetProperty,
     container: Option<PropertyContainer>,
     key: &Token,
     bv: &BV,
     data: &Everything,
+    context: &DataContext,
 ) {
     let game = GameFlags::game();
     let gameflags = property.to_game_flags();
@@ -51,20 +111,32 @@ pub fn validate_property(
         }
     }
     match GuiValidation::from_property(property) {
-        GuiValidation::UncheckedValue | GuiValidation::Format => {
-            // TODO: validate Format as a format string
+        GuiValidation::UncheckedValue => {
             _ = bv.expect_value();
         }
+        GuiValidation::Format => {
+            if let Some(value) = bv.expect_value() {
+                if let Some(formats) = value.as_str().strip_prefix('#') {
+                    // Formats with a value, such as `color:{1,1,1}`, are not checked.
+                    for format in formats.split(';').filter(|f| !f.is_empty() && !f.contains(':')) {
+                        data.verify_exists_implied(Item::TextFormat, format, value);
+                    }
+                } else {
+                    let msg = "expected a text format starting with #";
+                    warn(ErrorKey::Validation).msg(msg).loc(value).push();
+                }
+            }
+        }
         GuiValidation::DatatypeExpr | GuiValidation::Datamodel => {
-            validate_datatype_field(Datatype::Unknown, key, bv, data, false);
+            validate_datatype_field_in(Datatype::Unknown, key, bv, data, false, context);
         }
         GuiValidation::Datacontext => {
-            validate_datatype_field(Datatype::Unknown, key, bv, data, true);
+            validate_datatype_field_in(Datatype::Unknown, key, bv, data, true, context);
         }
         GuiValidation::Boolean => {
             if let Some(value) = bv.expect_value() {
                 if value.starts_with("[") {
-                    validate_datatype_field(Datatype::bool, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::bool, key, bv, data, false, context);
                 } else if !value.lowercase_is("yes") && !value.lowercase_is("no") {
                     // TODO: decide based on the field name whether to upgrade to error?
                     warn(ErrorKey::Validation).msg("expected yes or no").loc(value).push();
@@ -92,7 +164,7 @@ pub fn validate_property(
         GuiValidation::Integer => {
             if let Some(value) = bv.expect_value() {
                 if value.starts_with("[") {
-                    validate_datatype_field(Datatype::int32, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::int32, key, bv, data, false, context);
                 } else {
                     value.expect_integer();
                 }
@@ -101,7 +173,7 @@ pub fn validate_property(
         GuiValidation::UnsignedInteger => {
             if let Some(value) = bv.expect_value() {
                 if value.starts_with("[") {
-                    validate_datatype_field(Datatype::uint32, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::uint32, key, bv, data, false, context);
                 } else if let Some(i) = value.expect_integer() {
                     if i < 0 {
                         let msg = format!("{key} needs an unsigned integer");
@@ -113,7 +185,7 @@ pub fn validate_property(
         GuiValidation::Number => {
             if let Some(value) = bv.expect_value() {
                 if value.starts_with("[") {
-                    validate_datatype_field(Datatype::float, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::float, key, bv, data, false, context);
                 } else {
                     value.expect_number();
                 }
@@ -123,7 +195,7 @@ pub fn validate_property(
             if let Some(value) = bv.expect_value() {
                 if value.starts_with("[") {
                     // TODO: need a way to express it can be int32 or float
-                    validate_datatype_field(Datatype::Unknown, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::Unknown, key, bv, data, false, context);
                 } else {
                     value.expect_number();
                 }
@@ -133,7 +205,7 @@ pub fn validate_property(
             if let Some(value) = bv.expect_value() {
                 if value.starts_with("[") {
                     // TODO: need a way to express it can be int32 or float
-                    validate_datatype_field(Datatype::float, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::float, key, bv, data, false, context);
                 } else if let Some(value) = value.strip_suffix("f") {
                     // TODO: this f is used in vanilla; check it really works.
                     value.expect_number();
@@ -146,7 +218,7 @@ pub fn validate_property(
             if let Some(value) = bv.expect_value() {
                 if value.starts_with("[") {
                     // TODO: need a way to express it can be int32 or float
-                    validate_datatype_field(Datatype::Unknown, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::Unknown, key, bv, data, false, context);
                 } else if let Some(value) = value.strip_suffix("%") {
                     value.expect_number();
                 } else {
@@ -156,7 +228,7 @@ pub fn validate_property(
         }
         GuiValidation::TwoNumberOrPercent => match bv {
             BV::Value(_) => {
-                validate_datatype_field(Datatype::CVector2f, key, bv, data, false);
+                validate_datatype_field_in(Datatype::CVector2f, key, bv, data, false, context);
             }
             BV::Block(block) => {
                 for value in block.iter_values_warn() {
@@ -170,7 +242,7 @@ pub fn validate_property(
         },
         GuiValidation::CVector2f => match bv {
             BV::Value(_) => {
-                validate_datatype_field(Datatype::CVector2f, key, bv, data, false);
+                validate_datatype_field_in(Datatype::CVector2f, key, bv, data, false, context);
             }
             BV::Block(block) => {
                 let mut vd = Validator::new(block, data);
@@ -180,7 +252,7 @@ pub fn validate_property(
         },
         GuiValidation::CVector2i => match bv {
             BV::Value(_) => {
-                validate_datatype_field(Datatype::CVector2i, key, bv, data, false);
+                validate_datatype_field_in(Datatype::CVector2i, key, bv, data, false, context);
             }
             BV::Block(block) => {
                 let mut vd = Validator::new(block, data);
@@ -190,7 +262,7 @@ pub fn validate_property(
         },
         GuiValidation::CVector3f => match bv {
             BV::Value(_) => {
-                validate_datatype_field(Datatype::CVector3f, key, bv, data, false);
+                validate_datatype_field_in(Datatype::CVector3f, key, bv, data, false, context);
             }
             BV::Block(block) => {
                 let mut vd = Validator::new(block, data);
@@ -200,7 +272,7 @@ pub fn validate_property(
         },
         GuiValidation::CVector4f => match bv {
             BV::Value(_) => {
-                validate_datatype_field(Datatype::CVector4f, key, bv, data, false);
+                validate_datatype_field_in(Datatype::CVector4f, key, bv, data, false, context);
             }
             BV::Block(block) => {
                 let mut vd = Validator::new(block, data);
@@ -211,20 +283,20 @@ pub fn validate_property(
         GuiValidation::Color => match bv {
             BV::Value(_) => {
                 // TODO: can be CVector4f or CString
-                validate_datatype_field(Datatype::Unknown, key, bv, data, false);
+                validate_datatype_field_in(Datatype::Unknown, key, bv, data, false, context);
             }
             BV::Block(block) => {
                 validate_gui_color(block, data);
             }
         },
         GuiValidation::CString => {
-            validate_datatype_field(Datatype::CString, key, bv, data, false);
+            validate_datatype_field_in(Datatype::CString, key, bv, data, false, context);
         }
         GuiValidation::Item(itype) => {
             if let Some(value) = bv.expect_value() {
                 if value.starts_with("[") {
                     // TODO: need some way of specifying "stringable" datatypes
-                    validate_datatype_field(Datatype::Unknown, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::Unknown, key, bv, data, false, context);
                 } else {
                     data.verify_exists(itype, value);
                 }
@@ -234,7 +306,7 @@ pub fn validate_property(
             if let Some(value) = bv.expect_value() {
                 if value.starts_with("[") {
                     // TODO: need some way of specifying "stringable" datatypes
-                    validate_datatype_field(Datatype::Unknown, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::Unknown, key, bv, data, false, context);
                 } else if !value.is("") {
                     data.verify_exists(itype, value);
                 }
@@ -256,7 +328,7 @@ pub fn validate_property(
                 // Is it valid for the others?
                 if value.starts_with("[") {
                     // TODO: need some way of specifying "stringable" datatypes
-                    validate_datatype_field(Datatype::Unknown, key, bv, data, false);
+                    validate_datatype_field_in(Datatype::Unknown, key, bv, data, false, context);
                 } else {
                     let value_lc = value.as_str().to_ascii_lowercase();
                     if !choices.contains(&&*value_lc) {
@@ -306,13 +378,30 @@ pub fn validate_property(
                 BV::Value(value) => {
                     data.verify_exists(Item::GuiTemplate, value);
                     // Templates are validated separately, and this Widget field adds no context to that.
-                    // TODO: verify that this is a template containing one widget.
+                    if let Some(gui_block) = data.gui.expand_template(value.as_str()) {
+                        if gui_block.count_widgets() != 1 {
+                            let msg =
+                                format!("template `{value}` should contain exactly one widget");
+                            warn(ErrorKey::Gui).msg(msg).loc(value).push();
+                        }
+                    }
                 }
-                BV::Block(_block) => {
-                    // TODO: verify that this block contains only one widget (though it may also
-                    // have a recursive = yes field).
-                    // TODO: if this block is not recursive, validate it now.
-                    // TODO: perform blockoverrides on this block.
+                BV::Block(block) => {
+                    // Non-recursive widget blocks are expanded with the rest of the widget, so
+                    // only the recursive ones (and tooltipwidgets) end up here. Blockoverrides from
+                    // the containing widget are not applied to them.
+                    if context.recursive {
+                        return;
+                    }
+                    let gui_block =
+                        data.gui.expand_block(GuiBlockFrom::PropertyKey(property), block);
+                    if gui_block.count_widgets() != 1 {
+                        let msg = format!("{key} should contain exactly one widget");
+                        warn(ErrorKey::Gui).msg(msg).loc(key).push();
+                    }
+                    let mut context = context.clone();
+                    context.recursive = true;
+                    gui_block.validate(None, &context, data);
                 }
             }
         }
@@ -337,7 +426,7 @@ pub fn validate_property(
         GuiValidation::RawText | GuiValidation::Text => {
             if let Some(text) = bv.expect_value() {
                 let value = ValueParser::new(vec![text]).parse();
-                validate_gui_loca(key, value, data);
+                validate_gui_loca(key, value, data, context);
                 if !text.starts_with("[") && !text.as_str().contains(' ') {
                     // even raw text can still be a localization key sometimes
                     data.mark_used(Item::Localization, text.as_str());
@@ -347,6 +436,7 @@ pub fn validate_property(
     }
 }
 
+#[cfg(any(feature = "ck3", feature = "vic3"))]
 pub fn validate_datatype_field(
     dtype: Datatype,
     key: &Token,
@@ -354,14 +444,27 @@ pub fn validate_datatype_field(
     data: &Everything,
     allow_promote: bool,
 ) {
+    validate_datatype_field_in(dtype, key, bv, data, allow_promote, &DataContext::default());
+}
+
+/// Like [`validate_datatype_field`], but checks the expression against the datacontexts that are
+/// available to the widget. Returns the datatype of the expression.
+pub fn validate_datatype_field_in(
+    dtype: Datatype,
+    key: &Token,
+    bv: &BV,
+    data: &Everything,
+    allow_promote: bool,
+    context: &DataContext,
+) -> Datatype {
     if let Some(value) = bv.expect_value() {
         if value.starts_with("[") {
             let loca_value = ValueParser::new(vec![value]).parse();
             let mut sc = ScopeContext::new(Scopes::None, key);
             match loca_value {
-                // TODO: validate format
                 LocaValue::Code(chain, format) => {
-                    validate_datatypes(
+                    let dtype = validate_datatypes_from(
+                        context.start_type(&chain),
                         &chain,
                         data,
                         &mut sc,
@@ -370,6 +473,10 @@ pub fn validate_datatype_field(
                         format.as_ref(),
                         allow_promote,
                     );
+                    if let Some(format) = format {
+                        validate_format(&format, dtype, data);
+                    }
+                    return dtype;
                 }
                 LocaValue::Error => (),
                 _ => {
@@ -382,13 +489,14 @@ pub fn validate_datatype_field(
             warn(ErrorKey::Validation).msg(msg).loc(value).push();
         }
     }
+    Datatype::Unknown
 }
 
-fn validate_gui_loca(key: &Token, loca_value: LocaValue, data: &Everything) {
+fn validate_gui_loca(key: &Token, loca_value: LocaValue, data: &Everything, context: &DataContext) {
     match loca_value {
         LocaValue::Concat(v) => {
             for loca_value in v {
-                validate_gui_loca(key, loca_value, data);
+                validate_gui_loca(key, loca_value, data, context);
             }
         }
         LocaValue::Code(chain, format) => {
@@ -406,7 +514,8 @@ fn validate_gui_loca(key: &Token, loca_value: LocaValue, data: &Everything) {
             }
 
             let mut sc = ScopeContext::new(Scopes::None, key);
-            validate_datatypes(
+            let dtype = validate_datatypes_from(
+                context.start_type(&chain),
                 &chain,
                 data,
                 &mut sc,
@@ -415,6 +524,9 @@ fn validate_gui_loca(key: &Token, loca_value: LocaValue, data: &Everything) {
                 format.as_ref(),
                 false,
             );
+            if let Some(format) = format {
+                validate_format(&format, dtype, data);
+            }
         }
         LocaValue::Icon(token) => {
             data.verify_exists(Item::TextIcon, &token);
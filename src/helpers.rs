@@ -3,7 +3,10 @@ use ahash::{HashMap, HashSet, RandomState};
 use bimap::BiHashMap;
 
 use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
 
+#[cfg(feature = "ck3")]
+use crate::block::Block;
 use crate::report::{tips, warn, ErrorKey};
 use crate::token::Token;
 
@@ -89,6 +92,52 @@ pub fn stringify_list(v: &[&str]) -> String {
     format!("{}", Choices::AndChoices(v))
 }
 
+/// Convert a color block, such as `{ 255 0 0 }` or `hsv { 0.5 1.0 1.0 }`, to its RGB values.
+/// Returns `None` if the block is not a well-formed color. See [`crate::validate::validate_color`].
+#[cfg(feature = "ck3")] // happens not to be used by vic3 and imperator
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+pub fn color_to_rgb(block: &Block) -> Option<[u8; 3]> {
+    let tag = block.tag.as_deref().map_or("rgb", Token::as_str);
+    let values: Vec<f64> = block.iter_values().map(Token::get_number).collect::<Option<_>>()?;
+    if values.len() != 3 && values.len() != 4 {
+        return None;
+    }
+    let (a, b, c) = (values[0], values[1], values[2]);
+    let to_byte = |f: f64| (f.clamp(0.0, 1.0) * 255.0).round() as u8;
+    match tag {
+        "hsv" => Some(hsv_to_rgb(a, b, c).map(to_byte)),
+        "hsv360" => Some(hsv_to_rgb(a / 360.0, b / 100.0, c / 100.0).map(to_byte)),
+        _ => {
+            if block.iter_values().all(Token::is_integer) {
+                Some([a, b, c].map(|f| f.clamp(0.0, 255.0) as u8))
+            } else {
+                Some([a, b, c].map(to_byte))
+            }
+        }
+    }
+}
+
+/// Convert hue, saturation, and value (all between 0.0 and 1.0) to red, green, and blue (also
+/// between 0.0 and 1.0).
+#[cfg(feature = "ck3")]
+#[allow(clippy::many_single_char_names)] // the names are the standard ones for this formula
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> [f64; 3] {
+    let h = (h.rem_euclid(1.0)) * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match h {
+        h if h < 1.0 => (c, x, 0.0),
+        h if h < 2.0 => (x, c, 0.0),
+        h if h < 3.0 => (0.0, c, x),
+        h if h < 4.0 => (0.0, x, c),
+        h if h < 5.0 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    [r + m, g + m, b + m]
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TriBool {
     True,
@@ -114,3 +163,80 @@ pub const BANNED_NAMES: &[&str] = &[
 ];
 
 pub(crate) type BiTigerHashMap<L, R> = BiHashMap<L, R, RandomState, RandomState>;
+
+/// Resolve a path from the config file relative to the directory the config file is in.
+pub fn config_path(token: &Token) -> PathBuf {
+    let path = Path::new(token.as_str());
+    match token.loc.fullpath().parent() {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Check if `s` matches `pattern`, in which `*` stands for any text. Case does not matter.
+pub fn matches_pattern(pattern: &str, s: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let mut s = s.to_ascii_lowercase();
+    let mut parts = pattern.split('*');
+    // The first part must be at the start and the last part must be at the end. The parts in
+    // between can be anywhere, so take the earliest place for each.
+    let first = parts.next().unwrap_or_default();
+    let Some(rest) = s.strip_prefix(first) else {
+        return false;
+    };
+    s = rest.to_string();
+    let Some(last) = parts.next_back() else {
+        return s.is_empty();
+    };
+    for part in parts {
+        let Some(pos) = s.find(part) else {
+            return false;
+        };
+        s = s[pos + part.len()..].to_string();
+    }
+    s.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_pattern_leading() {
+        assert!(matches_pattern("*_event", "my_event"));
+        assert!(matches_pattern("*_event", "_event"));
+        assert!(!matches_pattern("*_event", "my_event_2"));
+    }
+
+    #[test]
+    fn test_matches_pattern_trailing() {
+        assert!(matches_pattern("my_mod.*", "my_mod.1"));
+        assert!(matches_pattern("my_mod.*", "my_mod."));
+        assert!(!matches_pattern("my_mod.*", "other_mod.1"));
+    }
+
+    #[test]
+    fn test_matches_pattern_middle() {
+        assert!(matches_pattern("my_*_decision", "my_big_decision"));
+        assert!(matches_pattern("my_*_decision", "my__decision"));
+        assert!(matches_pattern("a*b*c", "abbc"));
+        assert!(!matches_pattern("my_*_decision", "my_decision"));
+        // The parts must not overlap.
+        assert!(!matches_pattern("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn test_matches_pattern_empty() {
+        assert!(matches_pattern("", ""));
+        assert!(!matches_pattern("", "x"));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("*", "anything"));
+    }
+
+    #[test]
+    fn test_matches_pattern_case() {
+        assert!(matches_pattern("My_Mod.*", "my_mod.1"));
+        assert!(matches_pattern("my_mod.*", "MY_MOD.1"));
+        assert!(matches_pattern("exact", "EXACT"));
+    }
+}
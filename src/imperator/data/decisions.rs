@@ -36,6 +36,10 @@ impl Decisions {
     pub fn iter_keys(&self) -> impl Iterator<Item = &Token> {
         self.decisions.values().map(|item| &item.key)
     }
+
+    pub fn iter_key_block(&self) -> impl Iterator<Item = (&Token, &Block)> {
+        self.decisions.values().map(|item| (&item.key, &item.block))
+    }
     pub fn validate(&self, data: &Everything) {
         for item in self.decisions.values() {
             item.validate(data);
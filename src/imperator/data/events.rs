@@ -163,6 +163,10 @@ impl Event {
 
         Self { key, block, expects_scope, expects_from_token }
     }
+
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
     pub fn validate(&self, data: &Everything) {
         let mut vd = Validator::new(&self.block, data);
 
@@ -178,6 +182,7 @@ impl Event {
 
         let mut sc = ScopeContext::new(self.expects_scope, &self.expects_from_token);
         sc.set_strict_scopes(false);
+        sc.set_origin(Item::Event, &self.key);
 
         vd.field_choice("type", EVENT_TYPES);
         vd.field_bool("hidden");
@@ -243,6 +248,8 @@ impl Event {
         vd.field_validated_block("after", |block, data| {
             validate_effect(block, data, &mut sc, tooltipped_immediate);
         });
+
+        data.event_chains.record_inputs(Item::Event, &self.key, &self.block, &sc);
     }
 }
 
@@ -8,19 +8,24 @@ compile_error!("features \"ck3\", \"vic3\", and \"imperator\" cannot be enabled
 #[cfg(all(not(feature = "ck3"), not(feature = "vic3"), not(feature = "imperator")))]
 compile_error!("exactly one of the features \"ck3\", \"vic3\", \"imperator\" must be enabled");
 
+pub use crate::block::{Block, BlockItem, Comparator, Field, BV};
 pub use crate::config_load::validate_config_file;
+pub use crate::data::localization::PreviewFormat;
+pub use crate::db::DbKind;
 pub use crate::everything::Everything;
-pub use crate::fileset::FileKind;
+pub use crate::fileset::{FileEntry, FileHandler, FileKind};
 pub use crate::game::Game;
 pub use crate::item::Item;
 #[cfg(feature = "vic3")]
 pub use crate::mod_metadata::ModMetadata;
 #[cfg(any(feature = "ck3", feature = "imperator"))]
 pub use crate::modfile::ModFile;
+pub use crate::pdxfile::PdxFile;
+pub use crate::plugin::{plugin_report, Plugin, PluginLoader};
 pub use crate::report::{
     add_loaded_mod_root, disable_ansi_colors, emit_reports, log, set_output_file, set_output_style,
     set_show_loaded_mods, set_show_vanilla, suppress_from_json, take_reports, Confidence,
-    LogReport, PointedMessage, Severity,
+    LogReport, PointedMessage, ReportBuilderStage2, ReportBuilderStage3, Severity,
 };
 pub use crate::token::{Loc, Token};
 
@@ -40,15 +45,20 @@ mod date;
 mod db;
 mod dds;
 mod desc;
+mod dlc;
 mod effect;
 mod effect_validation;
+mod event_chains;
 mod everything;
 mod fileset;
 mod game;
+mod glyphs;
 mod gui;
 mod helpers;
 mod item;
+mod lint;
 mod lowercase;
+mod macro_report;
 mod macros;
 #[cfg(feature = "vic3")]
 mod mod_metadata;
@@ -59,13 +69,20 @@ mod on_action;
 mod parse;
 mod pathtable;
 mod pdxfile;
+mod plugin;
 mod report;
 mod rivers;
+mod schema;
+mod scope_probe;
 mod scopes;
 mod script_value;
+mod spelling;
 mod token;
+mod tooltip;
 mod tooltipped;
 mod trigger;
+mod trigger_logic;
 mod util;
 mod validate;
 mod validator;
+mod variables;
@@ -0,0 +1,220 @@
+//! Custom lint rules from the `lint` sections of the config file.
+//!
+//! Each rule selects items by their type and by an optional `match` trigger, and reports every
+//! selected item that fails its `assert` trigger. Only items defined in the mod itself are
+//! checked, because the rules are meant to enforce a mod team's own house style.
+
+use std::path::PathBuf;
+
+use strum::IntoEnumIterator;
+
+use crate::block::{Block, BlockItem, Field, BV};
+use crate::everything::Everything;
+use crate::fileset::FileKind;
+use crate::game::Game;
+use crate::helpers::matches_pattern;
+use crate::item::{Item, ItemLoader};
+use crate::report::{report, ErrorKey, Severity};
+use crate::token::{Loc, Token};
+
+/// A single `lint` section from the config file.
+#[derive(Debug)]
+pub struct LintRule {
+    /// The location of the rule in the config file.
+    pub loc: Loc,
+    /// An optional name for the rule, shown in its reports.
+    pub name: Option<Token>,
+    /// The item types this rule applies to. If empty, it applies to all of them.
+    pub items: Vec<Item>,
+    /// Items must match this to be checked by the rule.
+    pub matches: LintCondition,
+    /// Items that match the rule must also match this, or they are reported.
+    pub assert: LintCondition,
+    /// The message to report instead of the default one.
+    pub message: Option<String>,
+    pub severity: Severity,
+}
+
+/// A condition on an item, built from a trigger in a `lint` section.
+#[derive(Debug)]
+pub enum LintCondition {
+    /// Configured by the `always` key.
+    Always(bool),
+    /// Configured by the AND-key. Items must match all enclosed conditions.
+    Conjunction(Vec<LintCondition>),
+    /// Configured by the OR-key. Items must match at least one of the enclosed conditions.
+    Disjunction(Vec<LintCondition>),
+    /// Configured by the NOT-key. Items must not match the enclosed condition.
+    Negation(Box<LintCondition>),
+    /// The item's key must match the pattern, in which `*` stands for any text.
+    Key(String),
+    /// The item must be defined in the given file or directory.
+    File(PathBuf),
+    /// The item's block must have a field with the given name.
+    HasField(String),
+    /// The item's block must have a field with the given name whose value matches the pattern.
+    HasValue(String, String),
+    /// The given key must appear somewhere in the item's block, at any depth.
+    Uses(String),
+}
+
+impl LintCondition {
+    fn apply(&self, key: &Token, block: &Block) -> bool {
+        match self {
+            LintCondition::Always(value) => *value,
+            LintCondition::Conjunction(children) => {
+                children.iter().all(|child| child.apply(key, block))
+            }
+            LintCondition::Disjunction(children) => {
+                children.iter().any(|child| child.apply(key, block))
+            }
+            LintCondition::Negation(child) => !child.apply(key, block),
+            LintCondition::Key(pattern) => matches_pattern(pattern, key.as_str()),
+            LintCondition::File(path) => key.loc.pathname().starts_with(path),
+            LintCondition::HasField(name) => block.has_key(name),
+            LintCondition::HasValue(name, pattern) => block
+                .get_field_values(name)
+                .iter()
+                .any(|value| matches_pattern(pattern, value.as_str())),
+            LintCondition::Uses(name) => find_key(block, name).is_some(),
+        }
+    }
+
+    /// Collect the names from all the `uses` conditions in this condition.
+    fn used_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            LintCondition::Conjunction(children) | LintCondition::Disjunction(children) => {
+                for child in children {
+                    child.used_names(names);
+                }
+            }
+            LintCondition::Negation(child) => child.used_names(names),
+            LintCondition::Uses(name) => names.push(name),
+            _ => (),
+        }
+    }
+}
+
+impl LintRule {
+    pub fn check(&self, data: &Everything) {
+        let mut used_names = Vec::new();
+        self.assert.used_names(&mut used_names);
+
+        let itypes = if self.items.is_empty() {
+            Item::iter().filter(|&itype| can_lint(itype)).collect()
+        } else {
+            self.items.clone()
+        };
+        for itype in itypes {
+            for (key, block) in item_definitions(itype, data) {
+                if key.loc.kind != FileKind::Mod
+                    || !self.matches.apply(key, block)
+                    || self.assert.apply(key, block)
+                {
+                    continue;
+                }
+                let msg = self.message.clone().unwrap_or_else(|| {
+                    format!("{itype} `{key}` does not follow the lint rules in the config")
+                });
+                let info = self.name.as_ref().map(|name| format!("from lint rule `{name}`"));
+                // If the rule is about keys that should not be used, point at the first one.
+                let used = used_names.iter().find_map(|name| find_key(block, name));
+                report(ErrorKey::Lint, self.severity)
+                    .msg(msg)
+                    .opt_info(info)
+                    .loc(key)
+                    .opt_loc_msg(used, "used here")
+                    .loc_msg(self.loc, "rule defined here")
+                    .push();
+            }
+        }
+    }
+}
+
+/// Item types that are kept in the `Db` but have no item loader of their own, because the loaders
+/// of other item types add them.
+const SUBITEMS: &[Item] = &[
+    Item::AccessoryVariationLayout,
+    Item::AccessoryVariationTextures,
+    #[cfg(feature = "ck3")]
+    Item::AmenitySetting,
+    Item::CoaColorList,
+    Item::CoaColoredEmblemList,
+    Item::CoaPatternList,
+    Item::CoaTexturedEmblemList,
+    #[cfg(feature = "ck3")]
+    Item::Faith,
+    Item::Fontfiles,
+    Item::GeneAgePreset,
+    #[cfg(feature = "imperator")]
+    Item::Invention,
+    #[cfg(any(feature = "ck3", feature = "imperator"))]
+    Item::Law,
+    #[cfg(feature = "imperator")]
+    Item::MilitaryTradition,
+    #[cfg(feature = "imperator")]
+    Item::MissionTask,
+    #[cfg(feature = "vic3")]
+    Item::TerrainMask,
+    #[cfg(any(feature = "ck3", feature = "vic3"))]
+    Item::TutorialLessonStep,
+];
+
+/// Return whether lint rules can check items of type `itype`. The rules need the key and block of
+/// each item, and those are only kept for the item types in the `Db` and the ones special-cased
+/// in [`item_definitions`].
+pub fn can_lint(itype: Item) -> bool {
+    match itype {
+        Item::Event | Item::ScriptedEffect | Item::ScriptedTrigger => true,
+        #[cfg(feature = "imperator")]
+        Item::Decision if Game::is_imperator() => true,
+        _ => {
+            SUBITEMS.contains(&itype)
+                || inventory::iter::<ItemLoader>
+                    .into_iter()
+                    .any(|loader| loader.for_game(Game::game()) && loader.itype() == itype)
+        }
+    }
+}
+
+/// Return the key and block of every definition of `itype`.
+fn item_definitions(itype: Item, data: &Everything) -> Vec<(&Token, &Block)> {
+    match itype {
+        Item::Event => data
+            .iter_keys(Item::Event)
+            .filter_map(|key| Some((key, data.get_event_block(key.as_str())?)))
+            .collect(),
+        Item::ScriptedEffect => data
+            .effects
+            .iter_keys()
+            .filter_map(|key| Some((key, data.effects.get(key.as_str())?.block())))
+            .collect(),
+        Item::ScriptedTrigger => data
+            .triggers
+            .iter_keys()
+            .filter_map(|key| Some((key, data.triggers.get(key.as_str())?.block())))
+            .collect(),
+        #[cfg(feature = "imperator")]
+        Item::Decision if Game::is_imperator() => {
+            data.decisions_imperator.iter_key_block().collect()
+        }
+        _ => data.database.iter_key_block(itype).collect(),
+    }
+}
+
+/// Return the first use of `name` as a key anywhere in `block`.
+fn find_key<'a>(block: &'a Block, name: &str) -> Option<&'a Token> {
+    block.iter_items().find_map(|item| match item {
+        BlockItem::Field(Field(key, _, bv)) => {
+            if key.as_str().eq_ignore_ascii_case(name) {
+                Some(key)
+            } else if let BV::Block(block) = bv {
+                find_key(block, name)
+            } else {
+                None
+            }
+        }
+        BlockItem::Block(block) => find_key(block, name),
+        BlockItem::Value(_) => None,
+    })
+}
@@ -0,0 +1,181 @@
+//! A report on how the parameters of scripted effects and triggers are used.
+//!
+//! Errors inside a macro expansion are reported at the definition, with a link to the call site,
+//! which makes it hard to see which arguments caused them. When enabled, every call of a scripted
+//! effect or trigger with parameters records its arguments during validation. The report then
+//! lists, for each such effect or trigger in the mod, the values passed for each parameter, and
+//! for each call site the missing and unneeded arguments and the reports from its expansion.
+
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use crate::block::Block;
+use crate::fileset::FileKind;
+use crate::helpers::TigerHashSet;
+use crate::report::LogReport;
+use crate::token::{Loc, Token};
+
+#[derive(Debug, Default)]
+pub struct MacroCalls {
+    enabled: bool,
+    calls: Mutex<Vec<MacroCall>>,
+    /// The call sites and arguments of the recorded calls.
+    seen: Mutex<TigerHashSet<CallKey>>,
+}
+
+/// The location of a call site and the `name = value` arguments at it.
+type CallKey = (Loc, Vec<(&'static str, &'static str)>);
+
+/// A scripted effect or trigger to include in the report.
+#[derive(Debug)]
+pub struct MacroDefinition<'a> {
+    /// "scripted effect" or "scripted trigger".
+    pub what: &'static str,
+    pub key: &'a Token,
+    /// The parameters that the definition uses.
+    pub parms: Vec<&'static str>,
+}
+
+/// One call of a scripted effect or trigger with arguments.
+#[derive(Debug, Clone)]
+struct MacroCall {
+    /// The location of the key of the definition.
+    definition: Loc,
+    /// The key at the call site.
+    call: Token,
+    /// The `name = value` arguments at the call site.
+    args: Vec<(Token, Token)>,
+}
+
+impl MacroCall {
+    /// Whether `loc` is in this call. The tokens in an expansion are linked either to the key of
+    /// the call or, for substituted arguments, to the argument's value.
+    fn is_here(&self, loc: Loc) -> bool {
+        loc == self.call.loc || self.args.iter().any(|(_, value)| value.loc == loc)
+    }
+}
+
+impl MacroCalls {
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    /// Record a call of the scripted effect or trigger `definition` at `call` with the arguments
+    /// in `block`.
+    pub fn record(&self, definition: &Token, call: &Token, block: &Block) {
+        if !self.enabled {
+            return;
+        }
+        let args: Vec<_> =
+            block.iter_assignments().map(|(name, value)| (name.clone(), value.clone())).collect();
+        // The same call site is validated again for each expansion of the item it is in.
+        let key = args.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+        if self.seen.lock().unwrap().insert((call.loc, key)) {
+            let call = MacroCall { definition: definition.loc, call: call.clone(), args };
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    /// Build the report for those `definitions` that are from the mod and have parameters, from
+    /// the recorded calls and from the `reports` that were made during validation. Returns `None`
+    /// if there are no such definitions.
+    pub fn report(
+        &self,
+        mut definitions: Vec<MacroDefinition>,
+        reports: &[LogReport],
+    ) -> Option<String> {
+        definitions.retain(|def| def.key.loc.kind == FileKind::Mod && !def.parms.is_empty());
+        definitions.sort_by_key(|def| sort_key(def.key.loc));
+        if definitions.is_empty() {
+            return None;
+        }
+        let mut calls = self.calls.lock().unwrap().clone();
+        calls.sort_by_key(|call| sort_key(call.call.loc));
+
+        let mut out = String::new();
+        for def in definitions {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            _ = writeln!(out, "{} `{}` at {}", def.what, def.key, place(def.key.loc));
+            let calls: Vec<&MacroCall> =
+                calls.iter().filter(|call| call.definition == def.key.loc).collect();
+            if calls.is_empty() {
+                let parms: Vec<String> = def.parms.iter().map(|parm| format!("${parm}$")).collect();
+                _ = writeln!(out, "  parameters: {}", parms.join(", "));
+                _ = writeln!(out, "  never called with arguments");
+                continue;
+            }
+
+            for parm in &def.parms {
+                let mut values: Vec<&str> = calls
+                    .iter()
+                    .flat_map(|call| &call.args)
+                    .filter(|(name, _)| name.is(parm))
+                    .map(|(_, value)| value.as_str())
+                    .collect();
+                values.sort_unstable();
+                values.dedup();
+                if values.is_empty() {
+                    _ = writeln!(out, "  ${parm}$: never passed");
+                } else {
+                    _ = writeln!(out, "  ${parm}$: {}", values.join(", "));
+                }
+            }
+
+            let places = if calls.len() == 1 { "place" } else { "places" };
+            _ = writeln!(out, "  called from {} {places}:", calls.len());
+            for call in calls {
+                let args: Vec<String> =
+                    call.args.iter().map(|(name, value)| format!("{name} = {value}")).collect();
+                _ = writeln!(out, "    {}: {}", place(call.call.loc), args.join(" "));
+                let missing: Vec<&str> = def
+                    .parms
+                    .iter()
+                    .copied()
+                    .filter(|parm| !call.args.iter().any(|(name, _)| name.is(parm)))
+                    .collect();
+                if !missing.is_empty() {
+                    _ = writeln!(out, "      missing: {}", missing.join(", "));
+                }
+                let unneeded: Vec<&str> = call
+                    .args
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .filter(|name| !def.parms.contains(name))
+                    .collect();
+                if !unneeded.is_empty() {
+                    _ = writeln!(
+                        out,
+                        "      not used by the {}: {}",
+                        def.what,
+                        unneeded.join(", ")
+                    );
+                }
+                // Reports from inside the expansion have this call in their chain of links.
+                for report in reports.iter().filter(|report| {
+                    !call.is_here(report.pointers[0].loc)
+                        && report.pointers[1..].iter().any(|pointer| call.is_here(pointer.loc))
+                }) {
+                    _ = writeln!(
+                        out,
+                        "      {}({}): {} at {}",
+                        report.severity,
+                        report.key,
+                        report.msg,
+                        place(report.pointers[0].loc)
+                    );
+                }
+            }
+        }
+        Some(out)
+    }
+}
+
+fn place(loc: Loc) -> String {
+    format!("{}:{}:{}", loc.pathname().display(), loc.line, loc.column)
+}
+
+fn sort_key(loc: Loc) -> (String, u32, u32) {
+    (loc.pathname().to_string_lossy().into_owned(), loc.line, loc.column)
+}
@@ -683,7 +683,7 @@ impl<'a> ValueParser<'a> {
             // Clean up leftover state at end
             match state {
                 State::InKey(_) => {
-                    self.value.push(LocaValue::Markup);
+                    self.value.push(LocaValue::Markup(Token::new(&text[1..], loc)));
                 }
                 State::InValue(key, value, loc, bracecount) => {
                     if key.to_ascii_lowercase() == "tooltip" {
@@ -694,7 +694,7 @@ impl<'a> ValueParser<'a> {
                         warn(ErrorKey::Markup).msg(msg).loc(self.loc).push();
                         self.value.push(LocaValue::Error);
                     } else {
-                        self.value.push(LocaValue::Markup);
+                        self.value.push(LocaValue::Markup(Token::new(&text[1..], loc)));
                     }
                 }
             }
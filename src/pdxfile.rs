@@ -26,6 +26,7 @@ pub enum PdxEncoding {
     Detect,
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct PdxFile {}
 
 impl PdxFile {
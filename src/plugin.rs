@@ -0,0 +1,111 @@
+//! An extension point for validators built on this library.
+//!
+//! A [`Plugin`] is added to [`Everything`] before loading. It gets to read its own script files at
+//! the end of [`Everything::load_all`], and to check all the loaded data at the end of
+//! [`Everything::validate_all`]. Its reports go through the normal report system, with error
+//! keys of its own that are named after the plugin.
+//!
+//! The definitions that a plugin loads with [`PluginLoader::load_items`] are stored alongside the
+//! items declared in schema files. They are validated by their [`DbKind`], and can be looked up
+//! with [`Everything::custom_item_exists`] and [`Everything::verify_exists_custom`].
+
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use crate::block::Block;
+use crate::db::{Db, DbKind};
+use crate::everything::Everything;
+use crate::fileset::{FileHandler, Fileset};
+use crate::pdxfile::PdxFile;
+use crate::report::{report, ErrorKey, ReportBuilderStage2, Severity};
+use crate::token::Token;
+
+/// Checks of its own that a validator built on this library can add with
+/// [`Everything::add_plugin`].
+pub trait Plugin: Debug + Sync + Send {
+    /// The name of the plugin. The error keys of its reports are written as `name.key`, so that
+    /// they can be told apart from other plugins' keys in the output and in the config file's
+    /// filters. It should not contain a `.`.
+    fn name(&self) -> &'static str;
+
+    /// The error keys that the plugin reports with [`plugin_report`]. The config file's filters
+    /// can only name these keys, so that a typo in a filter is reported instead of ignored.
+    fn keys(&self) -> &'static [&'static str];
+
+    /// Load the plugin's own script files, by passing its [`FileHandler`]s to
+    /// [`PluginLoader::handle`] or its item types to [`PluginLoader::load_items`]. This is called
+    /// at the end of [`Everything::load_all`].
+    fn load(&mut self, _loader: &mut PluginLoader) {}
+
+    /// Check the loaded data. This is called at the end of [`Everything::validate_all`].
+    fn validate(&self, data: &Everything);
+}
+
+/// Gives plugins access to the mod and game files during loading.
+#[derive(Debug)]
+pub struct PluginLoader<'a> {
+    fileset: &'a Fileset,
+    db: &'a mut Db,
+}
+
+impl<'a> PluginLoader<'a> {
+    pub(crate) fn new(fileset: &'a Fileset, db: &'a mut Db) -> Self {
+        Self { fileset, db }
+    }
+
+    /// Pass the files under the handler's subpath to the handler, in the order the game loads
+    /// them. Files from the mod replace files with the same path from vanilla. The files are not
+    /// reported for being in an unexpected directory.
+    pub fn handle<T: Send, H: FileHandler<T>>(&self, handler: &mut H) {
+        self.fileset.add_custom_dir(handler.subpath());
+        self.fileset.handle(handler);
+    }
+
+    /// Load the `key = { ... }` definitions in the `.txt` files under `subpath` as items of the
+    /// type `itype`, which are validated by the [`DbKind`] that `kind` makes for each of them.
+    pub fn load_items<P, F>(&mut self, itype: &'static str, subpath: P, kind: F)
+    where
+        P: Into<PathBuf>,
+        F: Fn() -> Box<dyn DbKind>,
+    {
+        let subpath = subpath.into();
+        self.fileset.add_custom_dir(subpath.clone());
+        let blocks = self.fileset.filter_map_under(&subpath, |entry| {
+            if entry.filename().to_string_lossy().ends_with(".txt") {
+                PdxFile::read(entry)
+            } else {
+                None
+            }
+        });
+        for mut block in blocks {
+            for (key, block) in block.drain_definitions_warn() {
+                self.db.add_custom(itype, key, block, kind());
+            }
+        }
+    }
+
+    /// Add one item of the type `itype`, for plugins that read their files with their own
+    /// [`FileHandler`].
+    pub fn add_item(
+        &mut self,
+        itype: &'static str,
+        key: Token,
+        block: Block,
+        kind: Box<dyn DbKind>,
+    ) {
+        self.db.add_custom(itype, key, block, kind);
+    }
+}
+
+/// Start a report from the plugin named `plugin`, with the error key `key`, which is written as
+/// `plugin.key` in the output and in filters. Continue it with the same builder calls as the
+/// validator's own reports, for example
+/// `plugin_report(self.name(), "missing-widget", Severity::Warning, "message").loc(token).push()`.
+pub fn plugin_report<S: Into<String>>(
+    plugin: &'static str,
+    key: &'static str,
+    severity: Severity,
+    msg: S,
+) -> ReportBuilderStage2 {
+    report(ErrorKey::Plugin(plugin, key), severity).msg(msg)
+}
@@ -1,10 +1,14 @@
-use serde::{Deserialize, Serialize};
-use strum_macros::{Display, EnumString};
+use std::fmt::{Display, Formatter};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, EnumString, Hash, Serialize, Deserialize)]
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use strum_macros::{EnumString, IntoStaticStr};
+
+use crate::token::bump;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumString, IntoStaticStr, Hash)]
 #[strum(use_phf)]
 #[strum(serialize_all = "kebab-case")]
-#[serde(rename_all = "kebab-case")]
 pub enum ErrorKey {
     Config,
     ReadError,
@@ -19,6 +23,7 @@ pub enum ErrorKey {
     Encoding,
     Localization,
     Markup,
+    Spelling,
     DuplicateItem,
     ExactDuplicateItem,
     DuplicateField,
@@ -28,11 +33,13 @@ pub enum ErrorKey {
     MissingLocalization,
     MissingFile,
     MissingSound,
+    MissingGlyph,
     MissingItem,
     MissingPerspective,
     WrongGender,
     Conflict,
     ImageFormat,
+    FontFormat,
     Unneeded,
     Scopes,
     /// This seems like it solves the same problem that `Confidence` solves.
@@ -52,9 +59,12 @@ pub enum ErrorKey {
     FieldMissing,
     UnknownField,
     TitleTier,
+    DeJure,
     Colors,
     UnusedLocalization,
     UnusedFile,
+    UnusedGui,
+    Variables,
     UnknownList,
     Choice,
     UseOfThis,
@@ -63,8 +73,51 @@ pub enum ErrorKey {
     Bookmarks,
     WrongGame,
     Gui,
+    Lint,
+    /// A key of a plugin's own, which is written as `plugin-name.key`.
+    #[strum(disabled)]
+    Plugin(&'static str, &'static str),
 
     PrincesOfDarkness,
 
     Internal,
 }
+
+impl ErrorKey {
+    /// Parse an error key as written in the output, including the keys of plugins. This does not
+    /// check that a plugin key belongs to a plugin that was added; the config loader does that.
+    pub fn from_name(name: &'static str) -> Option<Self> {
+        if let Ok(key) = name.parse() {
+            Some(key)
+        } else if let Some((plugin, key)) = name.split_once('.') {
+            (!plugin.is_empty() && !key.is_empty()).then_some(ErrorKey::Plugin(plugin, key))
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for ErrorKey {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        if let ErrorKey::Plugin(plugin, key) = self {
+            write!(f, "{plugin}.{key}")
+        } else {
+            let name: &'static str = self.into();
+            f.write_str(name)
+        }
+    }
+}
+
+impl Serialize for ErrorKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        ErrorKey::from_name(bump(&name))
+            .ok_or_else(|| D::Error::custom(format!("unknown error key `{name}`")))
+    }
+}
@@ -370,6 +370,17 @@ pub fn set_show_loaded_mods(v: bool) {
     Errors::get_mut().filter.show_loaded_mods = v;
 }
 
+/// Let the config's filters use the error keys of the plugin named `plugin`.
+pub(crate) fn add_plugin_keys(plugin: &'static str, keys: &[&'static str]) {
+    let plugin_keys = &mut Errors::get_mut().filter.plugin_keys;
+    plugin_keys.extend(keys.iter().map(|key| ErrorKey::Plugin(plugin, key)));
+}
+
+/// Return whether `key` is one of the error keys of the plugins that have been added.
+pub(crate) fn is_plugin_key(key: ErrorKey) -> bool {
+    Errors::get_mut().filter.plugin_keys.contains(&key)
+}
+
 /// Configure the error reporter to only show errors that match this [`FilterRule`].
 pub(crate) fn set_predicate(predicate: FilterRule) {
     Errors::get_mut().filter.predicate = predicate;
@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use crate::block::Comparator;
 
 use crate::fileset::FileKind;
+use crate::helpers::TigerHashSet;
 use crate::report::{Confidence, ErrorKey, LogReport, Severity};
 use crate::token::Loc;
 
@@ -16,6 +17,9 @@ pub struct ReportFilter {
     pub show_loaded_mods: bool,
     /// A complex trigger that evaluates a report to assess whether it should be printed.
     pub predicate: FilterRule,
+    /// The error keys of the plugins that have been added. Only these plugin keys can be used
+    /// in the config's filters.
+    pub plugin_keys: TigerHashSet<ErrorKey>,
 }
 
 impl ReportFilter {
@@ -1,6 +1,7 @@
 //! Error report collection and printing facilities.
 
 pub(crate) use builder::{err, fatal, report, tips, untidy, warn};
+pub use builder::{ReportBuilderStage2, ReportBuilderStage3};
 pub(crate) use error_key::ErrorKey;
 pub(crate) use error_loc::ErrorLoc;
 pub use errors::*;
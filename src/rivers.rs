@@ -7,6 +7,7 @@ use std::ops::{RangeInclusive, RangeToInclusive};
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Result};
+use image::{Rgb, RgbImage};
 use png::{ColorType, Decoder};
 
 use crate::everything::Everything;
@@ -47,6 +48,11 @@ pub struct Rivers {
     color_type: Option<ColorType>,
     palette: Option<Vec<u8>>,
     pixels: Vec<u8>,
+    /// Coordinates of the pixels that problems were reported for.
+    /// These are highlighted in the debug image.
+    problems: Vec<(u32, u32)>,
+    /// Do the full check even if the problems won't be logged, because a debug image is wanted.
+    debug_image: bool,
 }
 
 impl Rivers {
@@ -114,8 +120,19 @@ impl Rivers {
         self.pixels[idx]
     }
 
+    /// Report a problem with the river pixels at `coords`, and remember them for the debug image.
+    fn problem(&mut self, coords: &[(u32, u32)], msg: String) {
+        warn(ErrorKey::Rivers).msg(msg).loc(self.entry.as_ref().unwrap()).push();
+        self.problems.extend_from_slice(coords);
+    }
+
+    /// Enable the collection of problem pixels even when their reports would be filtered out.
+    pub fn set_debug_image(&mut self) {
+        self.debug_image = true;
+    }
+
     fn validate_segments(
-        &self,
+        &mut self,
         river_segments: TigerHashMap<(u32, u32), (u32, u32)>,
         mut specials: TigerHashMap<(u32, u32), bool>,
     ) {
@@ -135,16 +152,16 @@ impl Rivers {
                         "({}, {}) river pixel connects two special pixels",
                         start.0, start.1
                     );
-                    warn(ErrorKey::Rivers).msg(msg).loc(self.entry.as_ref().unwrap()).push();
+                    self.problem(&[start], msg);
                 } else if special_neighbors.is_empty() {
                     let msg = format!("({}, {}) orphan river pixel", start.0, start.1);
-                    warn(ErrorKey::Rivers).msg(msg).loc(self.entry.as_ref().unwrap()).push();
+                    self.problem(&[start], msg);
                 } else {
                     let s = special_neighbors[0];
                     if specials[&s] {
                         let msg =
                             format!("({}, {}) pixel terminates multiple river segments", s.0, s.1);
-                        warn(ErrorKey::Rivers).msg(msg).loc(self.entry.as_ref().unwrap()).push();
+                        self.problem(&[s], msg);
                     } else {
                         specials.insert(s, true);
                     }
@@ -157,19 +174,19 @@ impl Rivers {
                         "({}, {}) - ({}, {}) orphan river segment",
                         start.0, start.1, end.0, end.1
                     );
-                    warn(ErrorKey::Rivers).msg(msg).loc(self.entry.as_ref().unwrap()).push();
+                    self.problem(&[start, end], msg);
                 } else if special_neighbors.len() > 1 {
                     let msg = format!(
                         "({}, {}) - ({}, {}) river segment has two terminators",
                         start.0, start.1, end.0, end.1
                     );
-                    warn(ErrorKey::Rivers).msg(msg).loc(self.entry.as_ref().unwrap()).push();
+                    self.problem(&[start, end], msg);
                 } else {
                     let s = special_neighbors[0];
                     if specials[&s] {
                         let msg =
                             format!("({}, {}) pixel terminates multiple river segments", s.0, s.1);
-                        warn(ErrorKey::Rivers).msg(msg).loc(self.entry.as_ref().unwrap()).push();
+                        self.problem(&[s], msg);
                     } else {
                         specials.insert(s, true);
                     }
@@ -178,7 +195,7 @@ impl Rivers {
         }
     }
 
-    pub fn validate(&self, _data: &Everything) {
+    pub fn validate(&mut self, _data: &Everything) {
         // TODO: check image width and height against world defines
 
         if self.color_type != Some(ColorType::Indexed) {
@@ -194,7 +211,7 @@ impl Rivers {
         }
 
         // Early exit before expensive loop, if errors won't be logged anyway
-        if !will_maybe_log(self.entry.as_ref().unwrap(), ErrorKey::Rivers) {
+        if !self.debug_image && !will_maybe_log(self.entry.as_ref().unwrap(), ErrorKey::Rivers) {
             return;
         }
 
@@ -224,10 +241,7 @@ impl Rivers {
                         } else {
                             let msg =
                                 format!("({x}, {y}) river source (green) not at source of a river");
-                            warn(ErrorKey::Rivers)
-                                .msg(msg)
-                                .loc(self.entry.as_ref().unwrap())
-                                .push();
+                            self.problem(&[(x, y)], msg);
                             bad_problem = true;
                         }
                     }
@@ -239,10 +253,7 @@ impl Rivers {
                             let msg = format!(
                                 "({x}, {y}) river tributary (red) not joining another river",
                             );
-                            warn(ErrorKey::Rivers)
-                                .msg(msg)
-                                .loc(self.entry.as_ref().unwrap())
-                                .push();
+                            self.problem(&[(x, y)], msg);
                             bad_problem = true;
                         }
                     }
@@ -254,10 +265,7 @@ impl Rivers {
                             let msg = format!(
                                 "({x}, {y}) river split (yellow) not splitting off from a river",
                             );
-                            warn(ErrorKey::Rivers)
-                                .msg(msg)
-                                .loc(self.entry.as_ref().unwrap())
-                                .push();
+                            self.problem(&[(x, y)], msg);
                             bad_problem = true;
                         }
                     }
@@ -275,10 +283,7 @@ impl Rivers {
                                         // though.
                                         if third_end == (x, y) {
                                             let msg = format!("({x}, {y}) river forms a loop");
-                                            warn(ErrorKey::Rivers)
-                                                .msg(msg)
-                                                .loc(self.entry.as_ref().unwrap())
-                                                .push();
+                                            self.problem(&[(x, y)], msg);
                                             bad_problem = true;
                                         } else {
                                             river_segments.insert(other_end, third_end);
@@ -303,10 +308,7 @@ impl Rivers {
                                 "({x}, {y}) river pixel has {} neighbors",
                                 river_neighbors.len()
                             );
-                            warn(ErrorKey::Rivers)
-                                .msg(msg)
-                                .loc(self.entry.as_ref().unwrap())
-                                .push();
+                            self.problem(&[(x, y)], msg);
                             bad_problem = true;
                         }
                     }
@@ -318,6 +320,57 @@ impl Rivers {
             self.validate_segments(river_segments, specials);
         }
     }
+
+    /// Radius of the circle drawn around each problem pixel in the debug image, so that single
+    /// pixels can be found when the image is zoomed out.
+    const MARK_RADIUS: i64 = 6;
+
+    /// Write an image with the same dimensions as `rivers.png`, in which the river pixels are
+    /// drawn in plain colors and the pixels with problems are circled.
+    ///
+    /// Map artists can layer this over the `rivers.png` in their image editor.
+    pub fn write_debug_png(&self, path: &Path) -> Result<()> {
+        if self.color_type != Some(ColorType::Indexed) {
+            bail!("rivers.png is not in indexed color format");
+        }
+
+        let mut img = RgbImage::from_pixel(self.width, self.height, Rgb([255, 255, 255]));
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let color = match self.pixel(x, y) {
+                    RiverPixels::SOURCE => Rgb([0, 200, 0]),
+                    RiverPixels::TRIBUTARY => Rgb([230, 0, 0]),
+                    RiverPixels::SPLIT => Rgb([230, 200, 0]),
+                    RiverPixels::FIRST_NORMAL..=RiverPixels::LAST_NORMAL => Rgb([0, 60, 220]),
+                    RiverPixels::FIRST_IGNORE.. => continue,
+                };
+                img.put_pixel(x, y, color);
+            }
+        }
+
+        let r = Self::MARK_RADIUS;
+        for &(px, py) in &self.problems {
+            for dx in -r..=r {
+                for dy in -r..=r {
+                    let dist2 = dx * dx + dy * dy;
+                    // Draw a ring, so that the problem pixel itself stays visible.
+                    if dist2 > r * r || dist2 < (r - 2) * (r - 2) {
+                        continue;
+                    }
+                    let x = i64::from(px) + dx;
+                    let y = i64::from(py) + dy;
+                    if let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y)) {
+                        if x < self.width && y < self.height {
+                            img.put_pixel(x, y, Rgb([255, 0, 255]));
+                        }
+                    }
+                }
+            }
+        }
+
+        img.save(path)?;
+        Ok(())
+    }
 }
 
 impl FileHandler<()> for Rivers {
@@ -0,0 +1,265 @@
+//! Item types that mods declare in schema files.
+//!
+//! Schema files are named in the config file with `schema = "filename"`. Each `item_type` block in
+//! a schema file declares a folder of `key = { ... }` definitions, with the fields they may have
+//! and the localization they need. The items are loaded into the [`Db`] and validated like the
+//! game's own items, and they can refer to each other and to the game's items.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use strum::IntoEnumIterator;
+
+use crate::block::{Block, Field};
+use crate::context::ScopeContext;
+use crate::db::{Db, DbKind};
+use crate::effect::validate_effect;
+use crate::everything::Everything;
+use crate::fileset::{FileEntry, FileKind};
+use crate::helpers::{config_path, matches_pattern};
+use crate::item::Item;
+use crate::pdxfile::PdxFile;
+use crate::report::{err, warn, ErrorKey};
+use crate::scopes::Scopes;
+use crate::token::Token;
+use crate::tooltipped::Tooltipped;
+use crate::trigger::validate_trigger;
+use crate::validator::Validator;
+
+/// The fields of an `item_type` block.
+const SCHEMA_KEYS: &[&str] =
+    &["name", "path", "key", "scope", "localization", "fields", "required", "other_fields"];
+
+/// What a field of a custom item must contain.
+#[derive(Debug, Clone, Copy)]
+enum FieldType {
+    Bool,
+    Integer,
+    Number,
+    Date,
+    /// Any single value.
+    Value,
+    /// Any block.
+    Block,
+    Trigger,
+    Effect,
+    ScriptValue,
+    /// The key of one of the game's items.
+    Item(Item),
+    /// The key of an item of a type declared in a schema file.
+    Custom(&'static str),
+}
+
+/// An item type declared by an `item_type` block in a schema file.
+#[derive(Debug)]
+pub struct ItemSchema {
+    name: &'static str,
+    /// The folder with the definitions, relative to the game or mod root.
+    path: PathBuf,
+    /// A pattern that the keys must match, in which `*` stands for any text.
+    key: Option<Token>,
+    /// The scope type of `root` in the triggers, effects and script values of these items, or
+    /// `None` if the schema doesn't say. Then `root` can be any scope type.
+    scope: Option<Scopes>,
+    /// Localization keys that each item needs, in which `$` stands for the item's key.
+    localization: Vec<Token>,
+    fields: Vec<(&'static str, FieldType)>,
+    required: Vec<Token>,
+    /// Whether fields that are not in `fields` are allowed.
+    other_fields: bool,
+}
+
+impl ItemSchema {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Load all the definitions of this item type into `db`.
+    pub fn load(self: &Arc<Self>, db: &mut Db, blocks: Vec<Block>) {
+        for mut block in blocks {
+            for (key, block) in block.drain_definitions_warn() {
+                db.add_custom(self.name, key, block, Box::new(CustomItem(Arc::clone(self))));
+            }
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+/// Load the item types from the schema files named in the config.
+pub fn load_schemas(config: &Block) -> Vec<Arc<ItemSchema>> {
+    let mut blocks = Vec::new();
+    for token in config.get_field_values("schema") {
+        let fullpath = config_path(token);
+        let path = PathBuf::from(token.as_str());
+        if fullpath.is_file() && path.file_name().is_some() {
+            let entry = FileEntry::new(path, FileKind::Mod, fullpath);
+            blocks.extend(PdxFile::read_optional_bom(&entry));
+        } else {
+            let msg = format!("could not find schema file {}", fullpath.display());
+            err(ErrorKey::Config).msg(msg).loc(token).push();
+        }
+    }
+
+    // Collect the names first, so that the item types can refer to each other.
+    let item_types: Vec<&Block> =
+        blocks.iter().flat_map(|block| block.get_field_blocks("item_type")).collect();
+    let names: Vec<&'static str> = item_types
+        .iter()
+        .filter_map(|block| block.get_field_value("name").map(Token::as_str))
+        .collect();
+    item_types.into_iter().filter_map(|block| load_schema(block, &names)).map(Arc::new).collect()
+}
+
+/// Load a single `item_type` block. `names` are the names of all the declared item types.
+fn load_schema(block: &Block, names: &[&'static str]) -> Option<ItemSchema> {
+    for item in block.iter_items() {
+        if let Some(Field(key, _, _)) = item.expect_field() {
+            if !SCHEMA_KEYS.contains(&key.as_str()) {
+                err(ErrorKey::Config).msg("Unexpected key").loc(key).push();
+            }
+        }
+    }
+    let Some(name) = block.get_field_value("name") else {
+        err(ErrorKey::Config).msg("this item type has no `name`").loc(block).push();
+        return None;
+    };
+    if Item::iter().any(|itype| name.is(itype.into())) {
+        let msg = format!("`{name}` is already the name of one of the game's item types");
+        err(ErrorKey::Config).msg(msg).loc(name).push();
+        return None;
+    }
+    let Some(path) = block.get_field_value("path") else {
+        err(ErrorKey::Config).msg("this item type has no `path`").loc(block).push();
+        return None;
+    };
+    let scope = match block.get_field_value("scope") {
+        Some(token) => {
+            let Some(scope) = Scopes::from_snake_case_multi(token.as_str()) else {
+                let msg = format!("unknown scope type `{token}`");
+                err(ErrorKey::Config).msg(msg).loc(token).push();
+                return None;
+            };
+            Some(scope)
+        }
+        None => None,
+    };
+
+    let mut fields = Vec::new();
+    if let Some(block) = block.get_field_block("fields") {
+        for (field, token) in block.iter_assignments_warn() {
+            let ftype = match token.as_str() {
+                "bool" => FieldType::Bool,
+                "integer" => FieldType::Integer,
+                "number" => FieldType::Number,
+                "date" => FieldType::Date,
+                "value" => FieldType::Value,
+                "block" => FieldType::Block,
+                "trigger" => FieldType::Trigger,
+                "effect" => FieldType::Effect,
+                "script_value" => FieldType::ScriptValue,
+                s if names.contains(&s) => FieldType::Custom(token.as_str()),
+                _ => {
+                    if let Some(itype) = Item::iter().find(|itype| token.is(itype.into())) {
+                        FieldType::Item(itype)
+                    } else {
+                        let msg = format!("unknown field type `{token}`");
+                        let info = "use bool, integer, number, date, value, block, trigger, effect, script_value, or the name of an item type";
+                        err(ErrorKey::Config).msg(msg).info(info).loc(token).push();
+                        continue;
+                    }
+                }
+            };
+            fields.push((field.as_str(), ftype));
+        }
+    }
+
+    Some(ItemSchema {
+        name: name.as_str(),
+        path: PathBuf::from(path.as_str()),
+        key: block.get_field_value("key").cloned(),
+        scope,
+        localization: block.get_field_list("localization").unwrap_or_default(),
+        fields,
+        required: block.get_field_list("required").unwrap_or_default(),
+        other_fields: block.get_field_bool("other_fields").unwrap_or(false),
+    })
+}
+
+/// The `DbKind` for items of the types declared in schema files.
+#[derive(Debug)]
+struct CustomItem(Arc<ItemSchema>);
+
+impl DbKind for CustomItem {
+    fn validate(&self, key: &Token, block: &Block, data: &Everything) {
+        let schema = &self.0;
+        let mut vd = Validator::new(block, data);
+        let mut sc = ScopeContext::new(schema.scope.unwrap_or(Scopes::all()), key);
+        if schema.scope.is_none() {
+            // The root could be any scope type, so scope types cannot be checked strictly.
+            sc.set_strict_scopes(false);
+        }
+
+        if let Some(pattern) = &schema.key {
+            if !matches_pattern(pattern.as_str(), key.as_str()) {
+                let msg = format!("{} key should match `{pattern}`", schema.name);
+                warn(ErrorKey::Validation).msg(msg).loc(key).loc_msg(pattern, "pattern").push();
+            }
+        }
+        for loca in &schema.localization {
+            let loca = loca.as_str().replace('$', key.as_str());
+            data.verify_exists_implied(Item::Localization, &loca, key);
+        }
+        for name in &schema.required {
+            vd.req_field(name.as_str());
+        }
+        for &(name, ftype) in &schema.fields {
+            match ftype {
+                FieldType::Bool => {
+                    vd.field_bool(name);
+                }
+                FieldType::Integer => {
+                    vd.field_integer(name);
+                }
+                FieldType::Number => {
+                    vd.field_numeric(name);
+                }
+                FieldType::Date => {
+                    vd.field_date(name);
+                }
+                FieldType::Value => {
+                    vd.field_value(name);
+                }
+                FieldType::Block => {
+                    vd.field_block(name);
+                }
+                FieldType::Trigger => {
+                    vd.field_validated_block(name, |block, data| {
+                        validate_trigger(block, data, &mut sc, Tooltipped::No);
+                    });
+                }
+                FieldType::Effect => {
+                    vd.field_validated_block(name, |block, data| {
+                        validate_effect(block, data, &mut sc, Tooltipped::No);
+                    });
+                }
+                FieldType::ScriptValue => {
+                    vd.field_script_value(name, &mut sc);
+                }
+                FieldType::Item(itype) => {
+                    vd.field_item(name, itype);
+                }
+                FieldType::Custom(itype) => {
+                    if let Some(token) = vd.field_value(name) {
+                        data.verify_exists_custom(itype, token);
+                    }
+                }
+            }
+        }
+        if schema.other_fields {
+            vd.no_warn_remaining();
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Recording of the scope types that were inferred at one location in the script.
+//!
+//! Scope errors are often hard to understand without knowing what tiger thinks `this`, `root` and
+//! the named scopes are at that point. When a target location is set, every effect, trigger and
+//! script value field at that location records the state of its [`ScopeContext`] during
+//! validation.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::context::ScopeContext;
+use crate::token::{Loc, Token};
+
+#[derive(Debug, Default)]
+pub struct ScopeProbe {
+    /// The file, relative to the game or mod directory, and the line to record.
+    target: Option<(PathBuf, u32)>,
+    found: Mutex<Vec<(Loc, String)>>,
+}
+
+impl ScopeProbe {
+    pub fn set_target(&mut self, path: PathBuf, line: u32) {
+        self.target = Some((path, line));
+    }
+
+    /// Record the state of `sc` if `key` is at the target location.
+    pub fn record(&self, key: &Token, sc: &ScopeContext) {
+        let Some((path, line)) = &self.target else {
+            return;
+        };
+        if key.loc.line == *line && key.loc.pathname() == path {
+            let explanation = format!("`{key}` at column {}:\n{}", key.loc.column, sc.explain());
+            let mut found = self.found.lock().unwrap();
+            if !found.iter().any(|(loc, s)| *loc == key.loc && *s == explanation) {
+                found.push((key.loc, explanation));
+            }
+        }
+    }
+
+    /// Return everything that was recorded, in order of location.
+    pub fn explanations(&self) -> Vec<String> {
+        let mut found = self.found.lock().unwrap().clone();
+        found.sort_by_key(|(loc, _)| (loc.kind, loc.column));
+        found.into_iter().map(|(_, explanation)| explanation).collect()
+    }
+}
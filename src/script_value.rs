@@ -4,7 +4,9 @@
 //! Scriptvalues can also be non-dynamic, in the sense of being just a literal or the name of
 //! another script value.
 
-use crate::block::{Block, BlockItem, Comparator, Eq::*, BV};
+use std::fmt::{Display, Formatter};
+
+use crate::block::{Block, BlockItem, Comparator, Eq::*, Field, BV};
 use crate::context::{Reason, ScopeContext};
 use crate::everything::Everything;
 use crate::helpers::TriBool;
@@ -54,6 +56,7 @@ fn validate_inner(
     validate_ifelse_sequence(block, "if", "else_if", "else");
     vd.set_allow_questionmark_equals(true);
     vd.unknown_fields_cmp(|token, cmp, bv| {
+        data.scope_probe.record(token, sc);
         if token.is("save_temporary_scope_as") {
             // save_temporary_scope_as is now allowed in script values
             if let Some(name) = bv.expect_value() {
@@ -89,6 +92,9 @@ fn validate_inner(
                 let msg = format!("nothing to {token} yet");
                 warn(ErrorKey::Logic).msg(msg).loc(token).push();
             }
+            if token.is("divide") || token.is("modulo") {
+                check_divisor(token, bv, data);
+            }
             validate_bv(bv, data, sc, check_desc);
             made_changes = true;
             saved_value = false;
@@ -352,6 +358,262 @@ pub fn validate_script_value(bv: &BV, data: &Everything, sc: &mut ScopeContext)
     validate_bv(bv, data, sc, true);
 }
 
+/// How deep named script values are followed when computing [`Bounds`]. This protects against
+/// loops.
+const MAX_BOUNDS_DEPTH: usize = 10;
+
+/// The range of numbers that a script value can have, as far as can be told without running it.
+/// An end that depends on something unknown, such as a trigger value, is infinite.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Bounds {
+    pub const UNKNOWN: Bounds = Bounds { min: f64::NEG_INFINITY, max: f64::INFINITY };
+
+    pub fn exact(n: f64) -> Self {
+        Bounds { min: n, max: n }
+    }
+
+    /// Make bounds from two ends that may have come out of infinite arithmetic.
+    fn new(min: f64, max: f64) -> Self {
+        Bounds {
+            min: if min.is_nan() { f64::NEG_INFINITY } else { min },
+            max: if max.is_nan() { f64::INFINITY } else { max },
+        }
+    }
+
+    /// The bounds of a value that is either in `self` or in `other`.
+    #[must_use]
+    pub fn union(self, other: Self) -> Self {
+        Bounds::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    #[must_use]
+    pub fn add(self, other: Self) -> Self {
+        Bounds::new(self.min + other.min, self.max + other.max)
+    }
+
+    #[must_use]
+    pub fn subtract(self, other: Self) -> Self {
+        Bounds::new(self.min - other.max, self.max - other.min)
+    }
+
+    #[must_use]
+    pub fn multiply(self, other: Self) -> Self {
+        let products = [
+            self.min * other.min,
+            self.min * other.max,
+            self.max * other.min,
+            self.max * other.max,
+        ];
+        if products.iter().any(|p| p.is_nan()) {
+            return Bounds::UNKNOWN;
+        }
+        let min = products.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = products.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Bounds::new(min, max)
+    }
+
+    #[must_use]
+    pub fn divide(self, other: Self) -> Self {
+        if other.contains(0.0) {
+            return Bounds::UNKNOWN;
+        }
+        self.multiply(Bounds::new(1.0 / other.max, 1.0 / other.min))
+    }
+
+    /// The remainder after dividing by `other`. It is smaller than the divisor, and it is not
+    /// negative if `self` isn't.
+    #[must_use]
+    pub fn modulo(self, other: Self) -> Self {
+        let divisor = other.abs();
+        let range = Bounds::new(-divisor.max, divisor.max);
+        if self.min >= 0.0 {
+            range.at_least(Bounds::exact(0.0))
+        } else {
+            range
+        }
+    }
+
+    /// The result of `min = other`, which raises the value to at least `other`.
+    #[must_use]
+    pub fn at_least(self, other: Self) -> Self {
+        Bounds::new(self.min.max(other.min), self.max.max(other.max))
+    }
+
+    /// The result of `max = other`, which lowers the value to at most `other`.
+    #[must_use]
+    pub fn at_most(self, other: Self) -> Self {
+        Bounds::new(self.min.min(other.min), self.max.min(other.max))
+    }
+
+    #[must_use]
+    fn abs(self) -> Self {
+        if self.min >= 0.0 {
+            self
+        } else if self.max <= 0.0 {
+            Bounds::new(-self.max, -self.min)
+        } else {
+            Bounds::new(0.0, self.max.max(-self.min))
+        }
+    }
+
+    /// Widen the bounds by `amount` on both sides, to allow for rounding.
+    #[must_use]
+    fn widen(self, amount: f64) -> Self {
+        Bounds::new(self.min - amount, self.max + amount)
+    }
+
+    /// Whether there is only one value these bounds allow.
+    pub fn is_exact(self) -> bool {
+        self.min.total_cmp(&self.max).is_eq()
+    }
+
+    pub fn contains(self, n: f64) -> bool {
+        self.min <= n && n <= self.max
+    }
+}
+
+impl Display for Bounds {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match (self.min.is_finite(), self.max.is_finite()) {
+            _ if self.is_exact() => write!(f, "{}", self.min),
+            (true, true) => write!(f, "between {} and {}", self.min, self.max),
+            (true, false) => write!(f, "at least {}", self.min),
+            (false, true) => write!(f, "at most {}", self.max),
+            (false, false) => write!(f, "anything"),
+        }
+    }
+}
+
+/// Compute the [`Bounds`] of the script value `bv`.
+pub fn script_value_bounds(bv: &BV, data: &Everything) -> Bounds {
+    bounds_bv(bv, data, 0)
+}
+
+fn bounds_bv(bv: &BV, data: &Everything, depth: usize) -> Bounds {
+    match bv {
+        BV::Value(token) => bounds_token(token, data, depth),
+        BV::Block(block) => {
+            let values: Vec<&Token> = block.iter_values().collect();
+            if values.len() == 2 && block.iter_items().count() == 2 {
+                // It's a range like { 1 5 }
+                let low = bounds_token(values[0], data, depth);
+                let high = bounds_token(values[1], data, depth);
+                Bounds::new(low.min, high.max)
+            } else {
+                bounds_block(block, Bounds::exact(0.0), data, depth)
+            }
+        }
+    }
+}
+
+fn bounds_token(token: &Token, data: &Everything, depth: usize) -> Bounds {
+    if let Some(n) = token.get_number() {
+        Bounds::exact(n)
+    } else if depth >= MAX_BOUNDS_DEPTH {
+        Bounds::UNKNOWN
+    } else if let Some(bv) = data.script_values.get_bv(token.as_str()) {
+        bounds_bv(bv, data, depth + 1)
+    } else {
+        Bounds::UNKNOWN
+    }
+}
+
+/// Compute the bounds of the value after the calculations in `block`, starting from `current`.
+fn bounds_block(block: &Block, mut current: Bounds, data: &Everything, depth: usize) -> Bounds {
+    for Field(key, _, bv) in block.iter_fields() {
+        let key_lc = Lowercase::new(key.as_str());
+        let yes = bv.get_value().is_some_and(|v| v.is("yes"));
+        current = match key_lc.as_str() {
+            "value" => bounds_bv(bv, data, depth),
+            "add" => current.add(bounds_bv(bv, data, depth)),
+            "subtract" => current.subtract(bounds_bv(bv, data, depth)),
+            "multiply" => current.multiply(bounds_bv(bv, data, depth)),
+            "divide" => current.divide(bounds_bv(bv, data, depth)),
+            "modulo" => current.modulo(bounds_bv(bv, data, depth)),
+            "min" => current.at_least(bounds_bv(bv, data, depth)),
+            "max" => current.at_most(bounds_bv(bv, data, depth)),
+            "abs" if yes => current.abs(),
+            "round" | "floor" | "ceiling" if yes => current.widen(1.0),
+            "round_to" => current.widen(bounds_bv(bv, data, depth).abs().max),
+            "fixed_range" | "integer_range" => match bv.get_block() {
+                Some(block) => {
+                    let low = block
+                        .get_field("min")
+                        .map_or(Bounds::UNKNOWN, |bv| bounds_bv(bv, data, depth));
+                    let high = block
+                        .get_field("max")
+                        .map_or(Bounds::UNKNOWN, |bv| bounds_bv(bv, data, depth));
+                    Bounds::new(low.min, high.max)
+                }
+                None => Bounds::UNKNOWN,
+            },
+            "desc" | "format" | "save_temporary_scope_as" | "save_temporary_value_as" | "limit" => {
+                current
+            }
+            _ => match bv.get_block() {
+                Some(block)
+                    if key_lc.as_str().starts_with("every_")
+                        || key_lc.as_str().starts_with("ordered_")
+                        || key_lc.as_str().starts_with("random_") =>
+                {
+                    // The block may be applied any number of times.
+                    if bounds_block(block, current, data, depth) == current {
+                        current
+                    } else {
+                        Bounds::UNKNOWN
+                    }
+                }
+                Some(block) if key_lc.as_str() == "switch" => {
+                    let mut result = current;
+                    for (_, block) in block.iter_definitions() {
+                        result = result.union(bounds_block(block, current, data, depth));
+                    }
+                    result
+                }
+                // `if`, `else_if`, `else`, and blocks that switch to another scope might not be
+                // applied at all.
+                Some(block) => current.union(bounds_block(block, current, data, depth)),
+                None => current,
+            },
+        };
+    }
+    current
+}
+
+/// Warn if the divisor `bv` of a `divide` or `modulo` can be zero.
+fn check_divisor(token: &Token, bv: &BV, data: &Everything) {
+    let bounds = script_value_bounds(bv, data);
+    if bounds == Bounds::exact(0.0) {
+        let msg = format!("{token} by zero");
+        err(ErrorKey::Range).msg(msg).loc(token).push();
+    } else if bounds.contains(0.0) && bounds.min.is_finite() {
+        let msg = format!("{token} by a value that can be zero");
+        let info = format!("the value is {bounds}");
+        warn(ErrorKey::Range).msg(msg).info(info).loc(token).push();
+    }
+}
+
+/// Warn if the script value `bv` can fall outside `min..=max`, as far as can be told.
+/// Ends of the value's range that depend on something unknown are not reported.
+pub fn check_script_value_range(bv: &BV, data: &Everything, min: f64, max: f64) {
+    let bounds = script_value_bounds(bv, data);
+    if (bounds.min.is_finite() && bounds.min < min) || (bounds.max.is_finite() && bounds.max > max)
+    {
+        let msg = format!("this value should be between {min} and {max}");
+        let info = if bounds.is_exact() {
+            format!("it is {bounds}")
+        } else {
+            format!("it can be {bounds}")
+        };
+        warn(ErrorKey::Range).msg(msg).info(info).loc(bv).push();
+    }
+}
+
 #[allow(dead_code)]
 pub fn validate_script_value_no_breakdown(bv: &BV, data: &Everything, sc: &mut ScopeContext) {
     validate_bv(bv, data, sc, false);
@@ -373,3 +635,48 @@ pub fn validate_non_dynamic_script_value(bv: &BV, data: &Everything) {
     let info = "only literal numbers or the name of a simple script value";
     err(ErrorKey::Validation).msg(msg).info(info).loc(bv).push();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(min: f64, max: f64) -> Bounds {
+        Bounds { min, max }
+    }
+
+    #[test]
+    fn test_divide() {
+        assert_eq!(bounds(2.0, 4.0).divide(bounds(-2.0, -1.0)), bounds(-4.0, -1.0));
+        assert_eq!(bounds(-4.0, -2.0).divide(bounds(-2.0, -1.0)), bounds(1.0, 4.0));
+        assert_eq!(bounds(-4.0, 2.0).divide(bounds(1.0, 2.0)), bounds(-4.0, 2.0));
+        assert_eq!(bounds(2.0, 4.0).divide(bounds(-f64::INFINITY, -1.0)), bounds(-4.0, 0.0));
+        // Divisors that can be zero give unknown results.
+        assert_eq!(bounds(2.0, 4.0).divide(bounds(-1.0, 1.0)), Bounds::UNKNOWN);
+        assert_eq!(bounds(2.0, 4.0).divide(Bounds::exact(0.0)), Bounds::UNKNOWN);
+    }
+
+    #[test]
+    fn test_multiply() {
+        assert_eq!(
+            bounds(1.0, 2.0).multiply(bounds(0.0, f64::INFINITY)),
+            bounds(0.0, f64::INFINITY)
+        );
+        assert_eq!(
+            bounds(1.0, 2.0).multiply(bounds(-f64::INFINITY, -1.0)),
+            bounds(-f64::INFINITY, -1.0)
+        );
+        assert_eq!(bounds(-1.0, 1.0).multiply(bounds(2.0, f64::INFINITY)), Bounds::UNKNOWN);
+        // Zero times infinity is not a number, so nothing is known.
+        assert_eq!(bounds(0.0, 1.0).multiply(Bounds::UNKNOWN), Bounds::UNKNOWN);
+        assert_eq!(bounds(-3.0, 2.0).multiply(bounds(-1.0, 4.0)), bounds(-12.0, 8.0));
+    }
+
+    #[test]
+    fn test_modulo() {
+        assert_eq!(bounds(0.0, 100.0).modulo(Bounds::exact(7.0)), bounds(0.0, 7.0));
+        assert_eq!(bounds(0.0, 100.0).modulo(Bounds::exact(-7.0)), bounds(0.0, 7.0));
+        assert_eq!(bounds(-100.0, 100.0).modulo(bounds(2.0, 7.0)), bounds(-7.0, 7.0));
+        assert_eq!(bounds(-100.0, -1.0).modulo(bounds(-7.0, 3.0)), bounds(-7.0, 7.0));
+        assert_eq!(bounds(1.0, 2.0).modulo(bounds(1.0, f64::INFINITY)), bounds(0.0, f64::INFINITY));
+    }
+}
@@ -0,0 +1,369 @@
+//! Spellchecking of the text parts of localization, with Hunspell dictionaries.
+//!
+//! Only the common parts of the Hunspell format are supported: the word list, flags, aliases, and
+//! single prefix and suffix rules with their cross products. Compounding and the other advanced
+//! options are ignored, which may cause some correct words to be reported. The reports are
+//! therefore made with weak confidence.
+
+use std::fs::read;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use encoding_rs::{Encoding, WINDOWS_1252};
+
+use crate::block::Block;
+use crate::data::localization::KNOWN_LANGUAGES;
+use crate::helpers::{config_path, TigerHashMap, TigerHashSet};
+use crate::report::{err, warn, ErrorKey};
+use crate::token::Token;
+
+/// The dictionaries and project word list from the `spellcheck` block of the config file.
+#[derive(Debug, Default)]
+pub struct Spellchecker {
+    dictionaries: TigerHashMap<&'static str, Dictionary>,
+    /// Words that are correct in every language, stored lowercased.
+    words: TigerHashSet<String>,
+}
+
+impl Spellchecker {
+    /// Load the dictionaries configured in `block`. Paths are relative to the config file.
+    pub fn from_config(block: &Block) -> Self {
+        let mut spellchecker = Self::default();
+        for (key, token) in block.iter_assignments() {
+            let Some(lang) = KNOWN_LANGUAGES.iter().find(|lang| key.is(lang)) else {
+                let msg = format!("unknown language {key}");
+                err(ErrorKey::Config).msg(msg).loc(key).push();
+                continue;
+            };
+            let dic = config_path(token);
+            match Dictionary::read(&dic.with_extension("aff"), &dic) {
+                Ok(dictionary) => {
+                    spellchecker.dictionaries.insert(lang, dictionary);
+                }
+                Err(e) => {
+                    let msg = format!("could not read dictionary: {e:#}");
+                    err(ErrorKey::Config).msg(msg).loc(token).push();
+                }
+            }
+        }
+        if let Some(words) = block.get_field_list("words") {
+            spellchecker.words.extend(words.iter().map(|word| word.as_str().to_lowercase()));
+        }
+        spellchecker
+    }
+
+    /// Check the spelling of the text fragments of each language that has a dictionary.
+    /// Each misspelled word is only reported once per language.
+    pub fn check(&self, texts: &[(&'static str, Vec<Token>)]) {
+        for (lang, texts) in texts {
+            let Some(dictionary) = self.dictionaries.get(lang) else {
+                continue;
+            };
+            let mut reported = TigerHashSet::default();
+            for text in texts {
+                for word in words(text) {
+                    let lower = word.as_str().to_lowercase();
+                    if self.words.contains(&lower) || reported.contains(&lower) {
+                        continue;
+                    }
+                    if !dictionary.is_correct(&word.as_str().replace('’', "'")) {
+                        let msg = format!("`{word}` is not in the {lang} dictionary");
+                        let info = "if this spelling is intended, add it to the `words` list in the `spellcheck` section of the config file";
+                        warn(ErrorKey::Spelling).weak().msg(msg).info(info).loc(&word).push();
+                        reported.insert(lower);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_apostrophe(c: char) -> bool {
+    c == '\'' || c == '’'
+}
+
+/// Split a text fragment into the words that should be spellchecked. Words with digits or
+/// underscores are skipped, and so are words with capitals after the first letter, because they
+/// are usually acronyms or names.
+fn words(text: &Token) -> Vec<Token> {
+    let mut result = Vec::new();
+    let mut chars = text.as_str().char_indices().peekable();
+    let mut start: Option<(usize, u32)> = None;
+    let mut column = 0;
+    loop {
+        let next = chars.next();
+        let is_word_char = next.is_some_and(|(_, c)| {
+            c.is_alphanumeric()
+                || c == '_'
+                || (is_apostrophe(c)
+                    && start.is_some()
+                    && chars.peek().is_some_and(|(_, c)| c.is_alphabetic()))
+        });
+        if is_word_char {
+            start.get_or_insert((next.unwrap().0, column));
+        } else if let Some((begin, begin_column)) = start.take() {
+            let end = next.map_or(text.as_str().len(), |(i, _)| i);
+            let word = &text.as_str()[begin..end];
+            if word.chars().all(|c| c.is_alphabetic() || is_apostrophe(c))
+                && !word.chars().skip(1).any(char::is_uppercase)
+            {
+                let mut loc = text.loc;
+                loc.column += begin_column;
+                result.push(text.subtoken(begin..end, loc));
+            }
+        }
+        let Some((_, c)) = next else {
+            break;
+        };
+        column += 1;
+        // Skip escape sequences such as `\n`.
+        if c == '\\' {
+            chars.next();
+            column += 1;
+        }
+    }
+    result
+}
+
+/// How the flags of the words and affixes are written in the dictionary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum FlagType {
+    /// One character per flag.
+    #[default]
+    Char,
+    /// Two characters per flag.
+    Long,
+    /// Comma-separated numbers.
+    Num,
+}
+
+impl FlagType {
+    fn parse(self, flags: &str) -> Vec<String> {
+        match self {
+            FlagType::Char => flags.chars().map(String::from).collect(),
+            FlagType::Long => {
+                let chars: Vec<char> = flags.chars().collect();
+                chars.chunks(2).map(|chunk| chunk.iter().collect()).collect()
+            }
+            FlagType::Num => flags.split(',').map(|flag| flag.trim().to_owned()).collect(),
+        }
+    }
+}
+
+/// One element of an affix condition.
+#[derive(Debug)]
+enum CharClass {
+    Any,
+    Char(char),
+    Set(bool, Vec<char>),
+}
+
+impl CharClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharClass::Any => true,
+            CharClass::Char(ch) => c == *ch,
+            CharClass::Set(negated, set) => set.contains(&c) != *negated,
+        }
+    }
+}
+
+fn parse_condition(condition: &str) -> Vec<CharClass> {
+    let mut result = Vec::new();
+    let mut chars = condition.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => result.push(CharClass::Any),
+            '[' => {
+                let mut set = Vec::new();
+                let mut negated = false;
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => break,
+                        '^' if set.is_empty() && !negated => negated = true,
+                        _ => set.push(c),
+                    }
+                }
+                result.push(CharClass::Set(negated, set));
+            }
+            _ => result.push(CharClass::Char(c)),
+        }
+    }
+    result
+}
+
+/// A prefix or suffix rule.
+#[derive(Debug)]
+struct Affix {
+    flag: String,
+    cross_product: bool,
+    strip: String,
+    add: String,
+    condition: Vec<CharClass>,
+}
+
+impl Affix {
+    /// Undo this suffix on `word`, if the result matches the condition.
+    fn strip_suffix(&self, word: &str) -> Option<String> {
+        let stem = format!("{}{}", word.strip_suffix(self.add.as_str())?, self.strip);
+        if stem.is_empty() || stem.chars().count() < self.condition.len() {
+            return None;
+        }
+        let tail = stem.chars().rev().take(self.condition.len());
+        tail.zip(self.condition.iter().rev()).all(|(c, class)| class.matches(c)).then_some(stem)
+    }
+
+    /// Undo this prefix on `word`, if the result matches the condition.
+    fn strip_prefix(&self, word: &str) -> Option<String> {
+        let stem = format!("{}{}", self.strip, word.strip_prefix(self.add.as_str())?);
+        if stem.is_empty() || stem.chars().count() < self.condition.len() {
+            return None;
+        }
+        stem.chars().zip(self.condition.iter()).all(|(c, class)| class.matches(c)).then_some(stem)
+    }
+}
+
+/// A Hunspell dictionary: the `.dic` word list with the rules from its `.aff` file.
+#[derive(Debug, Default)]
+struct Dictionary {
+    /// The words with their flags.
+    words: TigerHashMap<String, Vec<String>>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+    /// The lowercase letters used in the word list.
+    alphabet: TigerHashSet<char>,
+}
+
+impl Dictionary {
+    fn read(aff: &Path, dic: &Path) -> std::io::Result<Self> {
+        let aff_bytes = read(aff)?;
+        let dic_bytes = read(dic)?;
+
+        let mut dictionary = Self::default();
+        let mut flag_type = FlagType::default();
+        let mut aliases = Vec::new();
+        let mut seen_af_count = false;
+        let mut cross_products: TigerHashMap<String, bool> = TigerHashMap::default();
+        // The SET line decides how the rest of both files is decoded, so look for it first.
+        // Without it, Hunspell assumes ISO8859-1.
+        let mut encoding = WINDOWS_1252;
+        for line in String::from_utf8_lossy(&aff_bytes).lines() {
+            if let Some(set) = line.strip_prefix("SET ") {
+                let label = set.trim();
+                let label = label.strip_prefix("microsoft-").unwrap_or(label);
+                let Some(found) = Encoding::for_label(label.as_bytes()) else {
+                    let msg =
+                        format!("unsupported encoding `SET {}` in {}", set.trim(), aff.display());
+                    return Err(Error::new(ErrorKind::InvalidData, msg));
+                };
+                encoding = found;
+            }
+        }
+        let decode = |bytes: &[u8]| encoding.decode_without_bom_handling(bytes).0.into_owned();
+
+        for line in decode(&aff_bytes).lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long"] => flag_type = FlagType::Long,
+                ["FLAG", "num"] => flag_type = FlagType::Num,
+                ["TRY", letters] => {
+                    dictionary.alphabet.extend(letters.chars().flat_map(char::to_lowercase));
+                }
+                ["AF", flags, ..] => {
+                    // The first AF line only has the number of aliases.
+                    if seen_af_count {
+                        aliases.push(flag_type.parse(flags));
+                    }
+                    seen_af_count = true;
+                }
+                // The header line of an affix class: `SFX flag cross_product count`
+                ["PFX" | "SFX", flag, cross @ ("Y" | "N"), count]
+                    if count.parse::<usize>().is_ok() =>
+                {
+                    cross_products.insert((*flag).to_owned(), *cross == "Y");
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, rest @ ..] => {
+                    let add = add.split('/').next().unwrap_or_default();
+                    let affix = Affix {
+                        flag: (*flag).to_owned(),
+                        cross_product: cross_products.get(*flag).copied().unwrap_or(false),
+                        strip: if *strip == "0" { String::new() } else { (*strip).to_owned() },
+                        add: if add == "0" { String::new() } else { add.to_owned() },
+                        condition: parse_condition(rest.first().unwrap_or(&".")),
+                    };
+                    if *kind == "PFX" {
+                        dictionary.prefixes.push(affix);
+                    } else {
+                        dictionary.suffixes.push(affix);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // The first line of the word list is the approximate number of words.
+        for line in decode(&dic_bytes).lines().skip(1) {
+            let Some(entry) = line.split_whitespace().next() else {
+                continue;
+            };
+            let (word, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            let flags = match flags.parse::<usize>() {
+                Ok(n) if !aliases.is_empty() => {
+                    aliases.get(n.wrapping_sub(1)).cloned().unwrap_or_default()
+                }
+                _ => flag_type.parse(flags),
+            };
+            dictionary.alphabet.extend(word.chars().flat_map(char::to_lowercase));
+            dictionary.words.entry(word.to_owned()).or_default().extend(flags);
+        }
+        Ok(dictionary)
+    }
+
+    fn has_flag(&self, word: &str, flag: &str) -> bool {
+        self.words.get(word).is_some_and(|flags| flags.iter().any(|f| f == flag))
+    }
+
+    fn check(&self, word: &str) -> bool {
+        if self.words.contains_key(word) {
+            return true;
+        }
+        for suffix in &self.suffixes {
+            let Some(stem) = suffix.strip_suffix(word) else {
+                continue;
+            };
+            if self.has_flag(&stem, &suffix.flag) {
+                return true;
+            }
+            if suffix.cross_product {
+                for prefix in self.prefixes.iter().filter(|prefix| prefix.cross_product) {
+                    if let Some(root) = prefix.strip_prefix(&stem) {
+                        if self.has_flag(&root, &prefix.flag) && self.has_flag(&root, &suffix.flag)
+                        {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+        self.prefixes.iter().any(|prefix| {
+            prefix.strip_prefix(word).is_some_and(|root| self.has_flag(&root, &prefix.flag))
+        })
+    }
+
+    /// Check a word, also accepting capitalized forms of lowercase dictionary words.
+    /// Words in another script than the dictionary's are not checked.
+    fn is_correct(&self, word: &str) -> bool {
+        if word
+            .chars()
+            .flat_map(char::to_lowercase)
+            .any(|c| !self.alphabet.contains(&c) && !is_apostrophe(c))
+        {
+            return true;
+        }
+        if self.check(word) {
+            return true;
+        }
+        let lower = word.to_lowercase();
+        lower != word && self.check(&lower)
+    }
+}
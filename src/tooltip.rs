@@ -0,0 +1,601 @@
+//! Approximate simulation of the tooltips that the game generates for effects and triggers.
+//!
+//! Everything that script controls is rendered the way the game would: `custom_tooltip`,
+//! `custom_description` with its effect or trigger localization, `show_as_tooltip`, and the
+//! contents of scripted effects and triggers. Hidden effects are left out.
+//!
+//! Builtin effects and triggers are described with an effect or trigger localization of the same
+//! name, or else with the localization key that is their name in uppercase (with `NOT_` in front
+//! for negated triggers). Their `$PARAMETERS$` are filled in from the script. Builtins that have
+//! neither are shown the way they are written in the script.
+//!
+//! The headings that the engine adds, such as "If:" and "Otherwise:", are always in English,
+//! because the game does not take them from its localization files.
+
+use crate::block::{Block, BlockItem, Comparator, Eq::Single, Field, BV};
+use crate::data::localization::PreviewFormat;
+use crate::everything::Everything;
+#[cfg(feature = "ck3")]
+use crate::game::Game;
+use crate::item::Item;
+use crate::lowercase::Lowercase;
+use crate::scopes::{scope_to_scope, Scopes};
+use crate::token::Token;
+
+/// Fields that configure an effect, trigger, iterator or event option rather than doing anything.
+const PARAMETERS: &[&str] = &[
+    "limit",
+    "filter",
+    "alternative_limit",
+    "order_by",
+    "position",
+    "min",
+    "max",
+    "check_range_bounds",
+    "even_if_dead",
+    "count",
+    "percent",
+    "weight",
+    "modifier",
+    "show_chance",
+    "desc",
+    "text",
+    "subject",
+    "object",
+    "value",
+    "chance",
+    // event options
+    "name",
+    "trigger",
+    "show_as_unavailable",
+    "ai_chance",
+    "highlight_portrait",
+    "flavor",
+    "is_cancel_option",
+    "fallback",
+    "exclusive",
+    "clicksound",
+    "skill",
+    "trait",
+    "reason",
+    "add_internal_flag",
+];
+
+/// How deep scripted effects and triggers are followed before giving up. This protects against
+/// loops.
+const MAX_DEPTH: usize = 20;
+
+/// Builds the text of a tooltip line by line.
+struct Tooltip<'a> {
+    data: &'a Everything,
+    lang: &'a str,
+    output: String,
+}
+
+impl Tooltip<'_> {
+    fn line(&mut self, indent: usize, text: &str) {
+        for _ in 0..indent {
+            self.output.push_str("  ");
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    /// Render the localization `key`, or show the key itself if it does not exist.
+    fn loca(&self, key: &str) -> String {
+        self.try_loca(key).unwrap_or_else(|| format!("[missing localization {key}]"))
+    }
+
+    /// Render the localization `key`, if it exists in this language.
+    fn try_loca(&self, key: &str) -> Option<String> {
+        let text =
+            self.data.localization.preview(key, self.lang, PreviewFormat::Text, self.data)?;
+        Some(text.lines().collect::<Vec<_>>().join(" "))
+    }
+
+    /// Render a dynamic description such as an option's `name`, using its first text.
+    fn desc(&self, bv: &BV) -> String {
+        match bv {
+            BV::Value(token) => self.loca(token.as_str()),
+            BV::Block(block) => match first_text(block) {
+                Some(token) => self.loca(token.as_str()),
+                None => "[dynamic description]".to_string(),
+            },
+        }
+    }
+
+    /// Render the perspective of the effect or trigger localization item `key`. The `suffix` is
+    /// `_not` for negated triggers, which can also use the `NOT_` version of the positive text.
+    fn perspective(&self, itype: Item, key: &str, suffix: &str) -> Option<String> {
+        let (_, block) = self.data.get_key_block(itype, key)?;
+        for field in ["first", "global", "third", "none"] {
+            if let Some(loca) = block.get_field_value(&format!("{field}{suffix}")) {
+                return Some(self.loca(loca.as_str()));
+            }
+        }
+        if suffix == "_not" {
+            for field in ["first", "global", "third", "none"] {
+                if let Some(loca) = block.get_field_value(field) {
+                    if let Some(text) = self.try_loca(&format!("NOT_{loca}")) {
+                        return Some(text);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Describe a builtin effect or trigger with the game's text for it, or return `None` if
+    /// there is none. The `$PARAMETERS$` in the text are filled in from `bv`.
+    fn builtin(&self, itype: Item, key: &str, bv: &BV, negated: bool) -> Option<String> {
+        let suffix = if negated { "_not" } else { "" };
+        let text = self.perspective(itype, key, suffix).or_else(|| {
+            let loca = key.to_uppercase();
+            let loca = if negated { format!("NOT_{loca}") } else { loca };
+            self.try_loca(&loca)
+        })?;
+        Some(fill_parameters(&text, |parm| match bv {
+            BV::Value(value) => parm.eq_ignore_ascii_case("value").then(|| value.to_string()),
+            BV::Block(block) => block.get_field_value(&parm.to_lowercase()).map(Token::to_string),
+        }))
+    }
+
+    fn effect(&mut self, block: &Block, indent: usize, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        for item in block.iter_items() {
+            match item {
+                BlockItem::Field(Field(key, cmp, bv)) => {
+                    self.effect_field(key, *cmp, bv, indent, depth);
+                }
+                BlockItem::Value(token) => self.line(indent, &format!("• {token}")),
+                BlockItem::Block(_) => (),
+            }
+        }
+    }
+
+    fn effect_field(&mut self, key: &Token, cmp: Comparator, bv: &BV, indent: usize, depth: usize) {
+        let key_lc = Lowercase::new(key.as_str());
+        let key_lc = key_lc.as_str();
+        if PARAMETERS.contains(&key_lc) || is_hidden_effect(key_lc) {
+            return;
+        }
+        match (key_lc, bv) {
+            ("custom_tooltip" | "custom_label", BV::Value(text)) => {
+                let text = self.loca(text.as_str());
+                self.line(indent, &format!("• {text}"));
+            }
+            ("custom_tooltip" | "custom_label", BV::Block(block)) => {
+                if let Some(text) = block.get_field_value("text") {
+                    let text = self.loca(text.as_str());
+                    self.line(indent, &format!("• {text}"));
+                }
+            }
+            ("custom_description" | "custom_description_no_bullet", BV::Block(block)) => {
+                if let Some(text) = block.get_field_value("text") {
+                    let text = self
+                        .perspective(Item::EffectLocalization, text.as_str(), "")
+                        .unwrap_or_else(|| format!("[{text}]"));
+                    if key_lc == "custom_description" {
+                        self.line(indent, &format!("• {text}"));
+                    } else {
+                        self.line(indent, &text);
+                    }
+                }
+            }
+            ("show_as_tooltip", BV::Block(block)) => self.effect(block, indent, depth + 1),
+            ("if" | "else_if", BV::Block(block)) => {
+                let header = if key_lc == "if" { "If:" } else { "Else if:" };
+                self.line(indent, header);
+                if let Some(limit) = block.get_field_block("limit") {
+                    self.trigger(limit, indent + 2, false, depth + 1);
+                }
+                self.line(indent + 1, "then:");
+                self.effect(block, indent + 2, depth + 1);
+            }
+            ("else", BV::Block(block)) => {
+                self.line(indent, "Otherwise:");
+                self.effect(block, indent + 1, depth + 1);
+            }
+            ("random", BV::Block(block)) => {
+                match block.get_field_value("chance").and_then(Token::get_number) {
+                    Some(chance) => self.line(indent, &format!("With a {chance}% chance:")),
+                    None => self.line(indent, "With a chance:"),
+                }
+                self.effect(block, indent + 1, depth + 1);
+            }
+            ("random_list", BV::Block(block)) => self.random_list(block, indent, depth),
+            (_, BV::Block(block)) => {
+                if let Some(effect) = self.data.get_effect(key) {
+                    if let Some(expanded) = expand(effect.block(), &effect.macro_parms(), key, bv) {
+                        self.effect(&expanded, indent, depth + 1);
+                        return;
+                    }
+                }
+                if is_scope_change(key) {
+                    self.line(indent, &format!("{key}:"));
+                    self.effect(block, indent + 1, depth + 1);
+                } else if let Some(text) = self.builtin(Item::EffectLocalization, key_lc, bv, false)
+                {
+                    self.line(indent, &format!("• {text}"));
+                } else {
+                    self.line(indent, &format!("• {key} {cmp} {}", condense(block)));
+                }
+            }
+            (_, BV::Value(value)) => {
+                if let Some(effect) = self.data.get_effect(key) {
+                    if value.is("yes") && effect.macro_parms().is_empty() {
+                        self.effect(effect.block(), indent, depth + 1);
+                    }
+                    return;
+                }
+                match self.builtin(Item::EffectLocalization, key_lc, bv, false) {
+                    Some(text) => self.line(indent, &format!("• {text}")),
+                    None => self.line(indent, &format!("• {key} {cmp} {value}")),
+                }
+            }
+        }
+    }
+
+    fn random_list(&mut self, block: &Block, indent: usize, depth: usize) {
+        self.line(indent, "One of the following will happen:");
+        let weights: Vec<Option<f64>> =
+            block.iter_fields().map(|Field(key, _, _)| key.get_number()).collect();
+        let total: Option<f64> = weights.iter().copied().sum();
+        for (Field(key, _, bv), weight) in block.iter_fields().zip(weights) {
+            let Some(block) = bv.get_block() else {
+                continue;
+            };
+            let chance = match (weight, total) {
+                (Some(weight), Some(total)) if total > 0.0 => {
+                    format!("{:.0}%", weight / total * 100.0)
+                }
+                _ => format!("weight {key}"),
+            };
+            match block.get_field("desc") {
+                Some(desc) => self.line(indent + 1, &format!("{chance}: {}", self.desc(desc))),
+                None => self.line(indent + 1, &format!("{chance}:")),
+            }
+            self.effect(block, indent + 2, depth + 1);
+        }
+    }
+
+    /// Show the triggers in `block`, which must all be true, or if `negated`, must not all be
+    /// true.
+    fn trigger(&mut self, block: &Block, indent: usize, negated: bool, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        if negated && count_conditions(block) > 1 {
+            self.line(indent, "At least one of the following must be false:");
+            self.trigger(block, indent + 1, false, depth + 1);
+            return;
+        }
+        self.each_trigger(block, indent, negated, depth);
+    }
+
+    /// Show the triggers in `block` one by one, each negated if `negated`.
+    fn each_trigger(&mut self, block: &Block, indent: usize, negated: bool, depth: usize) {
+        for item in block.iter_items() {
+            match item {
+                BlockItem::Field(Field(key, cmp, bv)) => {
+                    self.trigger_field(key, *cmp, bv, indent, negated, depth);
+                }
+                BlockItem::Value(token) => self.line(indent, &format!("• {token}")),
+                BlockItem::Block(_) => (),
+            }
+        }
+    }
+
+    fn trigger_field(
+        &mut self,
+        key: &Token,
+        cmp: Comparator,
+        bv: &BV,
+        indent: usize,
+        negated: bool,
+        depth: usize,
+    ) {
+        let key_lc = Lowercase::new(key.as_str());
+        let key_lc = key_lc.as_str();
+        if PARAMETERS.contains(&key_lc) || key_lc.starts_with("save_") {
+            return;
+        }
+        let not = if negated { "not " } else { "" };
+        match (key_lc, bv) {
+            ("custom_tooltip", BV::Value(text)) => {
+                let text = self.loca(text.as_str());
+                self.line(indent, &format!("• {text}"));
+            }
+            ("custom_tooltip", BV::Block(block)) => {
+                if let Some(text) = block.get_field_value("text") {
+                    let text = self.loca(text.as_str());
+                    self.line(indent, &format!("• {text}"));
+                }
+            }
+            ("custom_description", BV::Block(block)) => {
+                if let Some(text) = block.get_field_value("text") {
+                    let suffix = if negated { "_not" } else { "" };
+                    let text = self
+                        .perspective(Item::TriggerLocalization, text.as_str(), suffix)
+                        .unwrap_or_else(|| format!("[{text}]"));
+                    self.line(indent, &format!("• {text}"));
+                }
+            }
+            ("and", BV::Block(block)) => {
+                let header = if negated {
+                    "At least one of the following must be false:"
+                } else {
+                    "All of the following must be true:"
+                };
+                self.line(indent, header);
+                self.trigger(block, indent + 1, false, depth + 1);
+            }
+            ("or", BV::Block(block)) => {
+                let header = if negated {
+                    "None of the following must be true:"
+                } else {
+                    "At least one of the following must be true:"
+                };
+                self.line(indent, header);
+                self.trigger(block, indent + 1, false, depth + 1);
+            }
+            ("not" | "nor", BV::Block(block)) => {
+                // The triggers in a NOT must all be false. Negating that again means that at
+                // least one of them must be true.
+                if !negated {
+                    self.each_trigger(block, indent, true, depth + 1);
+                } else if count_conditions(block) > 1 {
+                    self.line(indent, "At least one of the following must be true:");
+                    self.trigger(block, indent + 1, false, depth + 1);
+                } else {
+                    self.trigger(block, indent, false, depth + 1);
+                }
+            }
+            ("nand", BV::Block(block)) => {
+                let header = if negated {
+                    "All of the following must be true:"
+                } else {
+                    "At least one of the following must be false:"
+                };
+                self.line(indent, header);
+                self.trigger(block, indent + 1, false, depth + 1);
+            }
+            ("trigger_if" | "trigger_else_if", BV::Block(block)) => {
+                let header = if key_lc == "trigger_if" { "If:" } else { "Else if:" };
+                self.line(indent, header);
+                if let Some(limit) = block.get_field_block("limit") {
+                    self.trigger(limit, indent + 2, false, depth + 1);
+                }
+                self.line(indent + 1, "then:");
+                self.trigger(block, indent + 2, negated, depth + 1);
+            }
+            ("trigger_else", BV::Block(block)) => {
+                self.line(indent, "Otherwise:");
+                self.trigger(block, indent + 1, negated, depth + 1);
+            }
+            (_, BV::Block(block)) => {
+                if let Some(trigger) = self.data.get_trigger(key) {
+                    if let Some(expanded) = expand(trigger.block(), &trigger.macro_parms(), key, bv)
+                    {
+                        self.trigger(&expanded, indent, negated, depth + 1);
+                        return;
+                    }
+                }
+                if is_scope_change(key) {
+                    self.line(indent, &format!("{not}{key}:"));
+                    self.trigger(block, indent + 1, false, depth + 1);
+                } else if let Some(text) =
+                    self.builtin(Item::TriggerLocalization, key_lc, bv, negated)
+                {
+                    self.line(indent, &format!("• {text}"));
+                } else {
+                    self.line(indent, &format!("• {not}{key} {cmp} {}", condense(block)));
+                }
+            }
+            (_, BV::Value(value)) => {
+                if let Some(trigger) = self.data.get_trigger(key) {
+                    if trigger.macro_parms().is_empty() {
+                        let negated = negated ^ value.is("no");
+                        self.trigger(trigger.block(), indent, negated, depth + 1);
+                        return;
+                    }
+                }
+                // `is_adult = no` is described like `NOT = { is_adult = yes }`.
+                let negated_value = negated ^ value.is("no");
+                let text = if value.is("yes") || value.is("no") {
+                    self.builtin(Item::TriggerLocalization, key_lc, bv, negated_value)
+                } else {
+                    self.builtin(Item::TriggerLocalization, key_lc, bv, negated)
+                };
+                match text {
+                    Some(text) => self.line(indent, &format!("• {text}")),
+                    None => self.line(indent, &format!("• {not}{key} {cmp} {value}")),
+                }
+            }
+        }
+    }
+
+    /// Show the requirements in `trigger` and the effects in `effect`, under headings.
+    fn requirements_and_effects(
+        &mut self,
+        trigger: Option<&Block>,
+        effect: Option<&Block>,
+        indent: usize,
+    ) {
+        if let Some(trigger) = trigger {
+            self.line(indent, "Requirements:");
+            self.trigger(trigger, indent + 1, false, 0);
+        }
+        if let Some(effect) = effect {
+            self.line(indent, "Effects:");
+            self.effect(effect, indent + 1, 0);
+        }
+    }
+
+    /// Simulate the tooltips of an event's options. If `option` is given, only show the option
+    /// with that number (counting from 1) or that `name`.
+    fn event(&mut self, block: &Block, option: Option<&str>) -> bool {
+        let mut found = false;
+        for (i, option_block) in block.get_field_blocks("option").into_iter().enumerate() {
+            let number = (i + 1).to_string();
+            let name = option_block.get_field("name");
+            if let Some(option) = option {
+                let matches_name = name.and_then(BV::get_value).is_some_and(|name| name.is(option));
+                if option != number && !matches_name {
+                    continue;
+                }
+            }
+            found = true;
+            let title = name.map_or_else(String::new, |name| self.desc(name));
+            self.line(0, &format!("Option {number}: {title}"));
+            let trigger = option_block.get_field_block("trigger");
+            self.requirements_and_effects(trigger, Some(option_block), 1);
+        }
+        found
+    }
+}
+
+/// Simulate the tooltip of the event, decision or character interaction `key` in language `lang`.
+/// For events, `option` selects one option by number or `name`; otherwise all options are shown.
+/// Returns `None` if there is no such item.
+pub fn simulate(key: &str, option: Option<&str>, lang: &str, data: &Everything) -> Option<String> {
+    let mut tooltip = Tooltip { data, lang, output: String::new() };
+    if let Some(block) = data.get_event_block(key) {
+        if !tooltip.event(block, option) {
+            return None;
+        }
+        return Some(tooltip.output);
+    }
+    #[cfg(feature = "ck3")]
+    if Game::is_ck3() {
+        if let Some((key, block)) = data.get_key_block(Item::Decision, key) {
+            let title = block
+                .get_field("title")
+                .map_or_else(|| tooltip.loca(key.as_str()), |t| tooltip.desc(t));
+            tooltip.line(0, &format!("Decision: {title}"));
+            let trigger = block
+                .get_field_block("is_valid")
+                .or_else(|| block.get_field_block("is_valid_showing_failures_only"));
+            tooltip.requirements_and_effects(trigger, block.get_field_block("effect"), 0);
+            return Some(tooltip.output);
+        }
+        if let Some((key, block)) = data.get_key_block(Item::CharacterInteraction, key) {
+            tooltip.line(0, &format!("Interaction: {}", tooltip.loca(key.as_str())));
+            let trigger = block
+                .get_field_block("is_valid_showing_failures_only")
+                .or_else(|| block.get_field_block("is_valid"));
+            tooltip.requirements_and_effects(trigger, block.get_field_block("on_accept"), 0);
+            return Some(tooltip.output);
+        }
+    }
+    None
+}
+
+/// Effects that only change internal state, which the game does not show in tooltips.
+fn is_hidden_effect(key: &str) -> bool {
+    key.starts_with("hidden_effect")
+        || key.starts_with("save_")
+        || key.starts_with("debug_")
+        || key.contains("variable")
+        || key.ends_with("_flag")
+        || key == "trigger_event"
+}
+
+/// Whether `key` switches to another scope, so that its block applies to that scope.
+fn is_scope_change(key: &Token) -> bool {
+    let key_lc = Lowercase::new(key.as_str());
+    ["every_", "random_", "ordered_", "any_"]
+        .iter()
+        .any(|prefix| key_lc.as_str().starts_with(prefix))
+        || key.as_str().contains([':', '.'])
+        || scope_to_scope(key, Scopes::all()).is_some()
+}
+
+/// Expand a call of a scripted effect or trigger, with its arguments if it has parameters.
+fn expand(block: &Block, parms: &[&str], key: &Token, bv: &BV) -> Option<Block> {
+    if parms.is_empty() {
+        return None;
+    }
+    let args: Vec<(&str, Token)> = bv
+        .get_block()?
+        .iter_assignments()
+        .map(|(key, value)| (key.as_str(), value.clone()))
+        .collect();
+    block.expand_macro(&args, key.loc)
+}
+
+/// Count the fields in `block` that are triggers rather than parameters.
+fn count_conditions(block: &Block) -> usize {
+    block
+        .iter_items()
+        .filter(|item| match item {
+            BlockItem::Field(Field(key, _, _)) => {
+                let key_lc = Lowercase::new(key.as_str());
+                !PARAMETERS.contains(&key_lc.as_str()) && !key_lc.as_str().starts_with("save_")
+            }
+            BlockItem::Value(_) => true,
+            BlockItem::Block(_) => false,
+        })
+        .count()
+}
+
+/// Replace the `$NAME$` parameters in `text` with what `lookup` returns for them. Formatting
+/// after a `|`, as in `$VALUE|=+0$`, is dropped. Unknown parameters are left alone.
+fn fill_parameters(text: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        let Some(len) = rest[start + 1..].find('$') else {
+            break;
+        };
+        let inner = &rest[start + 1..start + 1 + len];
+        let name = inner.split_once('|').map_or(inner, |(name, _)| name);
+        output.push_str(&rest[..start]);
+        match lookup(name) {
+            Some(value) => output.push_str(&value),
+            None => output.push_str(&rest[start..start + len + 2]),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Show the contents of `block` on one line.
+fn condense(block: &Block) -> String {
+    let mut parts = Vec::new();
+    for item in block.iter_items() {
+        match item {
+            BlockItem::Value(token) => parts.push(token.to_string()),
+            BlockItem::Field(Field(key, cmp, BV::Value(value))) => {
+                parts.push(format!("{key} {cmp} {value}"));
+            }
+            BlockItem::Field(Field(key, cmp, BV::Block(block))) => {
+                parts.push(format!("{key} {cmp} {}", condense(block)));
+            }
+            BlockItem::Block(block) => parts.push(condense(block)),
+        }
+    }
+    format!("{{ {} }}", parts.join(" "))
+}
+
+/// Find the first localization key in a dynamic description block.
+fn first_text(block: &Block) -> Option<&Token> {
+    for Field(key, cmp, bv) in block.iter_fields() {
+        if *cmp != Comparator::Equals(Single) {
+            continue;
+        }
+        match bv {
+            BV::Value(value) if key.is("desc") || key.is("text") => return Some(value),
+            BV::Block(block) if !key.is("trigger") && !key.is("limit") => {
+                if let Some(text) = first_text(block) {
+                    return Some(text);
+                }
+            }
+            _ => (),
+        }
+    }
+    block.iter_values().next()
+}
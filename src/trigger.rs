@@ -24,11 +24,13 @@ use crate::scopes::{
 use crate::script_value::validate_script_value;
 use crate::token::{Loc, Token};
 use crate::tooltipped::Tooltipped;
+use crate::trigger_logic::check_trigger_logic;
 use crate::validate::{
     precheck_iterator_fields, validate_ifelse_sequence, validate_inside_iterator,
     validate_iterator_fields, ListType,
 };
 use crate::validator::Validator;
+use crate::variables::{VariableKind, VariableUse};
 
 /// Look up a trigger token that evaluates to a trigger value.
 ///
@@ -225,8 +227,10 @@ pub fn validate_trigger_internal(
     }
 
     validate_ifelse_sequence(block, "trigger_if", "trigger_else_if", "trigger_else");
+    check_trigger_logic(caller, block);
 
     vd.unknown_fields_any_cmp(|key, cmp, bv| {
+        data.scope_probe.record(key, sc);
         if key.is("value") {
             validate_script_value(bv, data, sc);
             side_effects = true;
@@ -338,6 +342,7 @@ pub fn validate_trigger_key_bv(
                     let msg = "this scripted trigger does not need macro arguments";
                     fatal(ErrorKey::Macro).msg(msg).loc(block).push();
                 } else {
+                    data.macro_calls.record(&trigger.key, key, block);
                     let mut vec = Vec::new();
                     let mut vd = Validator::new(block, data);
                     vd.set_max_severity(max_sev);
@@ -610,6 +615,7 @@ fn match_trigger_bv(
     negated: bool,
     max_sev: Severity,
 ) -> bool {
+    data.variables.record_trigger(name, bv);
     let mut side_effects = false;
     // True iff the comparator must be Comparator::Equals
     let mut must_be_eq = true;
@@ -1369,6 +1375,15 @@ pub fn validate_argument_scope(
     validate_inscopes(part_flags, func, inscopes, sc);
     validate_argument_internal(arg, validation, data, sc);
 
+    if func.lowercase_is("var") || func.lowercase_is("local_var") || func.lowercase_is("global_var")
+    {
+        let kind = VariableKind::from_key(func.as_str(), false);
+        data.variables.read(kind, arg);
+        if !part_flags.contains(PartFlags::Last) {
+            data.variables.use_as(kind, arg, VariableUse::Scope);
+        }
+    }
+
     let mut outscopes_token = func.clone();
     outscopes_token.combine(arg, ':');
     if func.lowercase_is("scope") {
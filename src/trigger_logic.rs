@@ -0,0 +1,399 @@
+//! Static analysis of the logic in trigger blocks.
+//!
+//! Each trigger block is checked on its own, comparing the simple conditions directly in it:
+//! yes/no triggers, comparisons with a number, and conditions that also appear inside a `NOT`.
+//! Conditions that contradict each other make an `AND` block never true, and conditions that
+//! together cover every case make an `OR` block always true. Other kinds of blocks are rewritten
+//! to one of those two by applying their negation to each condition.
+//!
+//! Nested blocks that join their conditions the same way as the block around them, such as an
+//! `AND` in a trigger or an `OR` in an `OR`, are flattened into it.
+
+use std::cmp::Ordering;
+
+use crate::block::{Block, BlockItem, Comparator, Eq::Single, Field, BV};
+use crate::lowercase::Lowercase;
+use crate::report::{untidy, warn, ErrorKey};
+use crate::token::Token;
+
+/// Fields in trigger blocks that are not conditions.
+const NOT_CONDITIONS: &[&str] = &[
+    "limit",
+    "count",
+    "percent",
+    "amount",
+    "even_if_dead",
+    "text",
+    "subject",
+    "object",
+    "value",
+    "desc",
+    "type",
+    "list",
+    "variable",
+];
+
+/// A simple condition directly in a trigger block.
+#[derive(Debug, Clone, Copy)]
+struct Atom<'a> {
+    key: &'a Token,
+    cmp: Comparator,
+    value: &'a Token,
+    /// False if the condition is required to be false, for example because it is in a `NOT`.
+    positive: bool,
+    /// Whether the condition was written inside a `NOT`.
+    in_not: bool,
+    /// Whether the condition is in a nested block that was flattened into this one.
+    nested: bool,
+    /// The token to point at when reporting this condition.
+    loc: &'a Token,
+}
+
+impl Atom<'_> {
+    fn describe(&self) -> String {
+        // `positive` may have been flipped by the block's own negation, so describe the condition
+        // as it was written.
+        if self.in_not {
+            format!("NOT = {{ {} {} {} }}", self.key, self.cmp, self.value)
+        } else {
+            format!("{} {} {}", self.key, self.cmp, self.value)
+        }
+    }
+
+    fn same_condition(&self, other: &Self) -> bool {
+        self.key.as_str().eq_ignore_ascii_case(other.key.as_str())
+            && self.cmp == other.cmp
+            && self.value == other.value
+    }
+
+    /// The truth of a `key = yes` or `key = no` condition, taking negation into account.
+    fn yes_no(&self) -> Option<bool> {
+        if self.cmp != Comparator::Equals(Single) {
+            return None;
+        }
+        if self.value.is("yes") {
+            Some(self.positive)
+        } else if self.value.is("no") {
+            Some(!self.positive)
+        } else {
+            None
+        }
+    }
+
+    /// The range of numbers that the left side of a numeric comparison must be in, taking
+    /// negation into account.
+    fn bound(&self) -> Option<Bound> {
+        let n = self.value.get_number()?;
+        let cmp = if self.positive {
+            self.cmp
+        } else {
+            match self.cmp {
+                Comparator::LessThan => Comparator::AtLeast,
+                Comparator::AtMost => Comparator::GreaterThan,
+                Comparator::GreaterThan => Comparator::AtMost,
+                Comparator::AtLeast => Comparator::LessThan,
+                _ => return None,
+            }
+        };
+        match cmp {
+            Comparator::LessThan => Some(Bound::Upper(n, false)),
+            Comparator::AtMost => Some(Bound::Upper(n, true)),
+            Comparator::GreaterThan => Some(Bound::Lower(n, false)),
+            Comparator::AtLeast => Some(Bound::Lower(n, true)),
+            _ => None,
+        }
+    }
+}
+
+/// One side of a numeric range, with whether the number itself is included.
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+    Lower(f64, bool),
+    Upper(f64, bool),
+}
+
+/// Check the conditions directly in the trigger block `block`, which was opened by `caller`.
+pub fn check_trigger_logic(caller: &Lowercase, block: &Block) {
+    let caller = caller.as_str();
+    // These count or weigh their conditions instead of combining them.
+    if caller == "calc_true_if" || caller == "modifier" || caller == "switch" {
+        return;
+    }
+    let negate_all = matches!(caller, "not" | "nor" | "nand" | "all_false");
+    let or = matches!(caller, "or" | "nand");
+
+    let atoms = collect_atoms(caller, block);
+
+    let what = if matches!(caller, "and" | "or" | "not" | "nor" | "nand" | "all_false") {
+        format!("`{}`", caller.to_uppercase())
+    } else {
+        "trigger".to_string()
+    };
+    // If the conditions were negated an odd number of times above, then a conflict between them
+    // means that the conditions as written cover every case, not that they contradict each other.
+    let relation =
+        if negate_all ^ or { "together cover every case" } else { "contradict each other" };
+    let outcome = if or { "always true" } else { "never true" };
+
+    for (i, atom) in atoms.iter().enumerate() {
+        for earlier in &atoms[..i] {
+            // Conditions that are both in the same nested block are reported when that block is
+            // checked.
+            if atom.nested && earlier.nested {
+                continue;
+            }
+            if atom.same_condition(earlier) && atom.positive == earlier.positive {
+                let msg = format!("duplicate condition `{}`", atom.describe());
+                untidy(ErrorKey::Logic)
+                    .msg(msg)
+                    .loc(atom.loc)
+                    .loc_msg(earlier.loc, "first here")
+                    .push();
+            } else if conflict(atom, earlier) {
+                let msg = format!(
+                    "`{}` and `{}` {relation}, so this {what} is {outcome}",
+                    earlier.describe(),
+                    atom.describe()
+                );
+                warn(ErrorKey::Logic)
+                    .msg(msg)
+                    .loc(atom.loc)
+                    .loc_msg(earlier.loc, "conflicts with this")
+                    .push();
+            }
+        }
+    }
+
+    check_ranges(&atoms, &what, relation, outcome);
+}
+
+/// Collect the simple conditions in the trigger block `block`, which was opened by `caller`.
+///
+/// A block is always true if the negations of its `OR`ed conditions are never all true together,
+/// so the conditions are negated where needed to check every kind of block as an `AND`. The
+/// block is then never or always true if two of the conditions conflict.
+fn collect_atoms<'a>(caller: &str, block: &'a Block) -> Vec<Atom<'a>> {
+    let negate_all = matches!(caller, "not" | "nor" | "nand" | "all_false");
+    let or = matches!(caller, "or" | "nand");
+    // The kind of nested block whose conditions are joined the same way as this block's.
+    let flatten = if matches!(caller, "or" | "not" | "nor" | "all_false") { "or" } else { "and" };
+    let mut atoms = Vec::new();
+    add_atoms(block, flatten, negate_all ^ or, false, &mut atoms);
+    atoms
+}
+
+fn add_atoms<'a>(
+    block: &'a Block,
+    flatten: &str,
+    flip: bool,
+    nested: bool,
+    atoms: &mut Vec<Atom<'a>>,
+) {
+    for item in block.iter_items() {
+        if let BlockItem::Field(Field(key, cmp, bv)) = item {
+            if let Some(mut atom) = atom(key, *cmp, bv) {
+                atom.positive ^= flip;
+                atom.nested = nested;
+                atoms.push(atom);
+            } else if let BV::Block(inner) = bv {
+                if key.lowercase_is(flatten) {
+                    add_atoms(inner, flatten, flip, true, atoms);
+                }
+            }
+        }
+    }
+}
+
+/// Extract a simple condition from a field in a trigger block, if it is one.
+fn atom<'a>(key: &'a Token, cmp: Comparator, bv: &'a BV) -> Option<Atom<'a>> {
+    match bv {
+        BV::Value(value) => {
+            let key_lc = Lowercase::new(key.as_str());
+            if NOT_CONDITIONS.contains(&key_lc.as_str())
+                || key_lc.as_str().starts_with("save_")
+                || key.as_str().contains('$')
+                || value.as_str().contains('$')
+            {
+                return None;
+            }
+            Some(Atom { key, cmp, value, positive: true, in_not: false, nested: false, loc: key })
+        }
+        BV::Block(block) => {
+            // A `NOT` around a single condition is that condition being false.
+            if !(key.is("NOT") || key.is("not") || key.is("NOR") || key.is("nor")) {
+                return None;
+            }
+            let mut items = block.iter_items();
+            let (Some(BlockItem::Field(Field(inner_key, inner_cmp, inner_bv))), None) =
+                (items.next(), items.next())
+            else {
+                return None;
+            };
+            let atom = atom(inner_key, *inner_cmp, inner_bv)?;
+            // Double negations are left alone.
+            atom.positive.then_some(Atom { positive: false, in_not: true, loc: key, ..atom })
+        }
+    }
+}
+
+/// Whether two conditions can never both be true.
+fn conflict(a: &Atom, b: &Atom) -> bool {
+    if !a.key.as_str().eq_ignore_ascii_case(b.key.as_str()) {
+        return false;
+    }
+    if a.same_condition(b) {
+        return a.positive != b.positive;
+    }
+    matches!((a.yes_no(), b.yes_no()), (Some(x), Some(y)) if x != y)
+}
+
+/// Check whether the numeric comparisons on each trigger leave any number that satisfies them all.
+fn check_ranges(atoms: &[Atom], what: &str, relation: &str, outcome: &str) {
+    let mut done: Vec<&str> = Vec::new();
+    for atom in atoms {
+        if atom.bound().is_none()
+            || done.iter().any(|key| atom.key.as_str().eq_ignore_ascii_case(key))
+        {
+            continue;
+        }
+        done.push(atom.key.as_str());
+        if let Some((first, second)) = empty_range(atoms, atom.key.as_str()) {
+            if first.nested && second.nested {
+                continue;
+            }
+            let msg = format!(
+                "`{}` and `{}` {relation}, so this {what} is {outcome}",
+                first.describe(),
+                second.describe()
+            );
+            warn(ErrorKey::Logic)
+                .msg(msg)
+                .loc(second.loc)
+                .loc_msg(first.loc, "conflicts with this")
+                .push();
+        }
+    }
+}
+
+/// If the numeric comparisons on trigger `key` leave no number that satisfies them all, return
+/// the two that conflict, in the order they appear.
+fn empty_range<'b, 'a>(atoms: &'b [Atom<'a>], key: &str) -> Option<(&'b Atom<'a>, &'b Atom<'a>)> {
+    let mut lower: Option<(f64, bool, &Atom)> = None;
+    let mut upper: Option<(f64, bool, &Atom)> = None;
+    for other in atoms.iter().filter(|other| other.key.as_str().eq_ignore_ascii_case(key)) {
+        match other.bound() {
+            Some(Bound::Lower(n, incl))
+                if lower.map_or(true, |(m, m_incl, _)| {
+                    n.total_cmp(&m).then((!incl).cmp(&!m_incl)).is_gt()
+                }) =>
+            {
+                lower = Some((n, incl, other));
+            }
+            Some(Bound::Upper(n, incl))
+                if upper.map_or(true, |(m, m_incl, _)| {
+                    n.total_cmp(&m).then(incl.cmp(&m_incl)).is_lt()
+                }) =>
+            {
+                upper = Some((n, incl, other));
+            }
+            _ => (),
+        }
+    }
+    let ((low, low_incl, low_atom), (high, high_incl, high_atom)) = (lower?, upper?);
+    // The range is empty if the lower bound is past the upper bound, or if they are the same
+    // number and it is excluded.
+    let touching = if low_incl && high_incl { Ordering::Equal } else { Ordering::Greater };
+    if low.total_cmp(&high).then(touching).is_gt() {
+        if low_atom.loc.loc <= high_atom.loc.loc {
+            Some((low_atom, high_atom))
+        } else {
+            Some((high_atom, low_atom))
+        }
+    } else {
+        None
+    }
+}
+
+/// Return the earlier limit that makes the branch with `limit` unreachable, if any.
+///
+/// A branch is unreachable if every condition in an earlier branch's limit is also in its own
+/// limit, because then the earlier branch is taken whenever this one could be. Limits that save
+/// scopes are skipped, because they may be there for their side effects.
+pub fn shadowing_limit<'a>(limit: &Block, earlier: &[&'a Block]) -> Option<&'a Block> {
+    let saves_scopes = |block: &Block| {
+        block
+            .iter_fields()
+            .any(|Field(key, _, _)| key.as_str().to_ascii_lowercase().starts_with("save_"))
+    };
+    if saves_scopes(limit) {
+        return None;
+    }
+    earlier.iter().copied().find(|earlier| {
+        earlier.iter_items().next().is_some()
+            && !saves_scopes(earlier)
+            && earlier
+                .iter_items()
+                .all(|item| limit.iter_items().any(|other| other.equivalent(item)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::pdxfile::parse_pdx_internal;
+
+    /// Whether `check_trigger_logic` would report the trigger block `text` opened by `caller` as
+    /// never or always true.
+    fn has_conflict(caller: &str, text: &'static str) -> bool {
+        let block = parse_pdx_internal(text, "trigger logic test");
+        let atoms = collect_atoms(caller, &block);
+        atoms.iter().enumerate().any(|(i, a)| atoms[..i].iter().any(|b| conflict(a, b)))
+            || atoms.iter().any(|a| empty_range(&atoms, a.key.as_str()).is_some())
+    }
+
+    #[test]
+    fn test_polarity() {
+        // `x > 5` and `x < 3` are never both true, but one of them can be false.
+        let disjoint = "x > 5 x < 3";
+        // `x > 3` and `x < 5` are never both false, but one of them can be true.
+        let covering = "x > 3 x < 5";
+        for (caller, expect_disjoint, expect_covering) in [
+            ("and", true, false),
+            ("trigger", true, false),
+            ("or", false, true),
+            ("not", false, true),
+            ("nor", false, true),
+            ("all_false", false, true),
+            ("nand", true, false),
+        ] {
+            assert_eq!(has_conflict(caller, disjoint), expect_disjoint, "{caller} {disjoint}");
+            assert_eq!(has_conflict(caller, covering), expect_covering, "{caller} {covering}");
+        }
+        assert!(has_conflict("and", "a = yes NOT = { a = yes }"));
+        assert!(has_conflict("or", "a = yes NOT = { a = yes }"));
+        assert!(!has_conflict("and", "a = yes b = no"));
+    }
+
+    #[test]
+    fn test_flatten() {
+        assert!(has_conflict("and", "x > 5 AND = { x < 3 }"));
+        assert!(has_conflict("or", "x > 3 OR = { x < 5 }"));
+        assert!(has_conflict("not", "x > 3 OR = { x < 5 }"));
+        assert!(has_conflict("nand", "x > 5 AND = { x < 3 }"));
+        assert!(!has_conflict("and", "x > 5 OR = { x < 3 }"));
+        assert!(!has_conflict("or", "x > 3 AND = { x < 5 }"));
+    }
+
+    #[test]
+    fn test_interval_edges() {
+        assert!(!has_conflict("and", "x >= 3 x <= 3"));
+        assert!(has_conflict("and", "x > 3 x <= 3"));
+        assert!(has_conflict("and", "x >= 3 x < 3"));
+        assert!(has_conflict("and", "x > 3 x < 3"));
+        assert!(!has_conflict("and", "x > 2.5 x < 3"));
+        // The strictest bound on each side counts.
+        assert!(has_conflict("and", "x >= 3 x > 3 x <= 3"));
+        assert!(has_conflict("and", "x <= 3 x < 3 x >= 3"));
+        assert!(!has_conflict("and", "x >= 3 x >= 1 x <= 3"));
+    }
+}
@@ -27,7 +27,9 @@ use crate::trigger::{
     partition, validate_argument, validate_argument_scope, validate_inscopes, validate_trigger,
     validate_trigger_internal, warn_not_first, Part, PartFlags,
 };
+use crate::trigger_logic::shadowing_limit;
 use crate::validator::Validator;
+use crate::variables::VariableKind;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ListType {
@@ -361,9 +363,15 @@ pub fn validate_inside_iterator(
             sc.expect_list(token);
             sc.replace_list_entry(token.as_str(), token);
         }
+        if let Some(token) = block.get_field_value("variable") {
+            data.variables.read(VariableKind::VariableList, token);
+        }
     } else if name == "in_local_list" || name == "in_global_list" {
         vd.req_field("variable");
         vd.ban_field("list", || format!("{listtype}_in_list"));
+        if let Some(token) = block.get_field_value("variable") {
+            data.variables.read(VariableKind::from_key(name.as_str(), true), token);
+        }
     } else {
         vd.ban_field("list", || format!("{listtype}_in_list"));
         vd.ban_field("variable", || {
@@ -723,15 +731,20 @@ pub fn validate_scope_chain(
 
 pub fn validate_ifelse_sequence(block: &Block, key_if: &str, key_elseif: &str, key_else: &str) {
     let mut seen_if = false;
+    // The limits of the earlier branches in the current sequence.
+    let mut limits = Vec::new();
     for (key, block) in block.iter_definitions() {
         if key.is(key_if) {
             seen_if = true;
+            limits.clear();
+            limits.extend(block.get_field_block("limit"));
             continue;
         } else if key.is(key_elseif) {
             if !seen_if {
                 let msg = format!("`{key_elseif} without preceding `{key_if}`");
                 warn(ErrorKey::IfElse).msg(msg).loc(key).push();
             }
+            check_shadowed(key, block, &mut limits);
             seen_if = true;
             continue;
         } else if key.is(key_else) {
@@ -741,11 +754,31 @@ pub fn validate_ifelse_sequence(block: &Block, key_if: &str, key_elseif: &str, k
             }
             if block.has_key("limit") {
                 // `else` with a `limit`, followed by another `else`, does work.
+                check_shadowed(key, block, &mut limits);
                 seen_if = true;
                 continue;
             }
         }
         seen_if = false;
+        limits.clear();
+    }
+}
+
+/// Warn if the branch `key` can never be taken because an earlier branch in the same sequence has
+/// a limit that is true whenever this one's is. Then add this branch's limit to `limits`.
+fn check_shadowed<'a>(key: &Token, block: &'a Block, limits: &mut Vec<&'a Block>) {
+    if let Some(limit) = block.get_field_block("limit") {
+        if let Some(earlier) = shadowing_limit(limit, limits) {
+            let msg = format!("this `{key}` can never be taken");
+            let info = "an earlier branch is always taken instead, because its limit is true whenever this one's is";
+            warn(ErrorKey::Logic)
+                .msg(msg)
+                .info(info)
+                .loc(key)
+                .loc_msg(earlier, "earlier limit")
+                .push();
+        }
+        limits.push(limit);
     }
 }
 
@@ -17,7 +17,7 @@ use crate::report::{report, ErrorKey, Severity};
 use crate::scopes::Scopes;
 #[cfg(any(feature = "ck3", feature = "vic3"))]
 use crate::script_value::validate_script_value_no_breakdown;
-use crate::script_value::{validate_bv, validate_script_value};
+use crate::script_value::{check_script_value_range, validate_bv, validate_script_value};
 use crate::token::Token;
 use crate::tooltipped::Tooltipped;
 use crate::trigger::{validate_target, validate_target_ok_this, validate_trigger_internal};
@@ -784,6 +784,21 @@ impl<'a> Validator<'a> {
         })
     }
 
+    /// Just like [`Validator::field_script_value`], but also warns if the script value can fall
+    /// outside the range `min..=max`, as far as can be told without running it.
+    pub fn field_script_value_in_range(
+        &mut self,
+        name: &str,
+        sc: &mut ScopeContext,
+        min: f64,
+        max: f64,
+    ) -> bool {
+        self.field_check(name, |_, bv| {
+            validate_script_value(bv, self.data, sc);
+            check_script_value_range(bv, self.data, min, max);
+        })
+    }
+
     /// Just like [`Validator::field_script_value`], but does not warn if it is an inline script value and the `desc` fields
     /// in it do not contain valid localizations. This is generally used for script values that will never be shown to
     /// the user except in debugging contexts, such as `ai_will_do`.
@@ -0,0 +1,192 @@
+//! Tracking of script variables and flags across the whole mod.
+//!
+//! Every place that sets or reads a variable or flag is recorded during validation, so that
+//! afterward the ones that are read but never set, or set but never read, can be reported.
+
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+use crate::block::BV;
+use crate::helpers::TigerHashMap;
+use crate::lowercase::Lowercase;
+use crate::report::{warn, ErrorKey};
+use crate::scopes::Scopes;
+use crate::token::Token;
+
+/// The separate namespaces that variables and flags live in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VariableKind {
+    Variable,
+    LocalVariable,
+    GlobalVariable,
+    VariableList,
+    LocalVariableList,
+    GlobalVariableList,
+    #[cfg(feature = "ck3")]
+    CharacterFlag,
+}
+
+impl VariableKind {
+    /// The kind of variable that an effect, trigger or prefix such as `set_global_variable`,
+    /// `has_local_variable_list` or `global_var` works on.
+    pub fn from_key(key: &str, list: bool) -> Self {
+        let key = key.to_ascii_lowercase();
+        match (key.contains("global"), key.contains("local"), list) {
+            (true, _, false) => VariableKind::GlobalVariable,
+            (true, _, true) => VariableKind::GlobalVariableList,
+            (false, true, false) => VariableKind::LocalVariable,
+            (false, true, true) => VariableKind::LocalVariableList,
+            (false, false, false) => VariableKind::Variable,
+            (false, false, true) => VariableKind::VariableList,
+        }
+    }
+}
+
+impl Display for VariableKind {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let s = match self {
+            VariableKind::Variable => "variable",
+            VariableKind::LocalVariable => "local variable",
+            VariableKind::GlobalVariable => "global variable",
+            VariableKind::VariableList => "variable list",
+            VariableKind::LocalVariableList => "local variable list",
+            VariableKind::GlobalVariableList => "global variable list",
+            #[cfg(feature = "ck3")]
+            VariableKind::CharacterFlag => "character flag",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// What kind of value a variable holds, as far as can be told from how it's used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VariableUse {
+    Number,
+    Scope,
+}
+
+impl VariableUse {
+    /// Guess the kind of value from the scopes that a `value` target resolves to.
+    /// Returns `None` if it could be either, or something else entirely such as a flag.
+    pub fn from_scopes(scopes: Scopes) -> Option<Self> {
+        if scopes == Scopes::Value {
+            Some(VariableUse::Number)
+        } else if !scopes.intersects(Scopes::primitive() | Scopes::None) {
+            Some(VariableUse::Scope)
+        } else {
+            None
+        }
+    }
+}
+
+/// The first places where a variable was set, read, and used as each kind of value.
+#[derive(Debug, Default)]
+struct Uses {
+    write: Option<Token>,
+    read: Option<Token>,
+    number: Option<Token>,
+    scope: Option<Token>,
+}
+
+/// Remember `token` in `slot` unless it already holds a better one.
+/// Mod locations are preferred because those are the ones that get reported.
+fn remember(slot: &mut Option<Token>, token: &Token) {
+    let better = |old: &Token| {
+        (token.loc.kind, std::cmp::Reverse(token.loc)) > (old.loc.kind, std::cmp::Reverse(old.loc))
+    };
+    if slot.as_ref().map_or(true, better) {
+        *slot = Some(token.clone());
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Variables {
+    uses: Mutex<TigerHashMap<(VariableKind, &'static str), Uses>>,
+}
+
+impl Variables {
+    fn record(&self, kind: VariableKind, name: &Token, f: impl FnOnce(&mut Uses)) {
+        // Names built from macro parameters are recorded when the macro is expanded.
+        if name.as_str().is_empty() || name.as_str().contains('$') {
+            return;
+        }
+        let mut uses = self.uses.lock().unwrap();
+        f(uses.entry((kind, name.as_str())).or_default());
+    }
+
+    /// Record that the variable or flag `name` is set here.
+    pub fn write(&self, kind: VariableKind, name: &Token) {
+        self.record(kind, name, |uses| remember(&mut uses.write, name));
+    }
+
+    /// Record that the variable or flag `name` is read here.
+    pub fn read(&self, kind: VariableKind, name: &Token) {
+        self.record(kind, name, |uses| remember(&mut uses.read, name));
+    }
+
+    /// Record that the variable `name` is used as a number or as a scope here.
+    pub fn use_as(&self, kind: VariableKind, name: &Token, how: VariableUse) {
+        self.record(kind, name, |uses| match how {
+            VariableUse::Number => remember(&mut uses.number, name),
+            VariableUse::Scope => remember(&mut uses.scope, name),
+        });
+    }
+
+    /// Record the variables and flags read by triggers such as `has_variable` or
+    /// `is_target_in_global_variable_list`.
+    pub fn record_trigger(&self, name: &Token, bv: &BV) {
+        let name_lc = Lowercase::new(name.as_str());
+        let name_lc = name_lc.as_str();
+        #[cfg(feature = "ck3")]
+        if name_lc == "has_character_flag" || name_lc == "has_dead_character_flag" {
+            if let Some(flag) = bv.get_value() {
+                self.read(VariableKind::CharacterFlag, flag);
+            }
+            return;
+        }
+        if name_lc.starts_with("has_") && name_lc.ends_with("variable") {
+            if let Some(var) = bv.get_value() {
+                self.read(VariableKind::from_key(name_lc, false), var);
+            }
+        } else if name_lc.starts_with("has_") && name_lc.ends_with("variable_list") {
+            if let Some(var) = bv.get_value() {
+                self.read(VariableKind::from_key(name_lc, true), var);
+            }
+        } else if (name_lc.starts_with("is_target_in_") && name_lc.ends_with("variable_list"))
+            || name_lc.ends_with("variable_list_size")
+        {
+            if let Some(var) = bv.get_block().and_then(|block| block.get_field_value("name")) {
+                self.read(VariableKind::from_key(name_lc, true), var);
+            }
+        }
+    }
+
+    /// Report the variables and flags that are read but never set, set but never read, or used
+    /// both as numbers and as scopes.
+    pub fn check(&self) {
+        let uses = self.uses.lock().unwrap();
+        let mut entries: Vec<_> = uses.iter().collect();
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        for ((kind, name), uses) in entries {
+            match (&uses.write, &uses.read) {
+                (None, Some(read)) => {
+                    let msg = format!("{kind} `{name}` is read but never set");
+                    warn(ErrorKey::Variables).msg(msg).loc(read).push();
+                }
+                (Some(write), None) => {
+                    let msg = format!("{kind} `{name}` is set but never read");
+                    warn(ErrorKey::Variables).weak().msg(msg).loc(write).push();
+                }
+                _ => (),
+            }
+            if let (Some(number), Some(scope)) = (&uses.number, &uses.scope) {
+                let msg = format!("{kind} `{name}` is used both as a number and as a scope");
+                warn(ErrorKey::Variables)
+                    .msg(msg)
+                    .loc_msg(number, "used as a number here")
+                    .loc_msg(scope, "used as a scope here")
+                    .push();
+            }
+        }
+    }
+}
@@ -151,6 +151,10 @@ impl Event {
         Self { key, block, expects_scope, expects_from_token }
     }
 
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+
     pub fn validate(&self, data: &Everything) {
         let mut vd = Validator::new(&self.block, data);
 
@@ -174,6 +178,7 @@ impl Event {
 
         let mut sc = ScopeContext::new(self.expects_scope, &self.expects_from_token);
         sc.set_strict_scopes(false);
+        sc.set_origin(Item::Event, &self.key);
 
         vd.field_bool("hidden");
         let hidden = self.block.field_value_is("hidden", "yes");
@@ -243,6 +248,8 @@ impl Event {
         vd.multi_field_validated_block("option", |block, data| {
             validate_event_option(block, data, &mut sc, tooltipped);
         });
+
+        data.event_chains.record_inputs(Item::Event, &self.key, &self.block, &sc);
     }
 }
 
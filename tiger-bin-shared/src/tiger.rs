@@ -8,7 +8,7 @@ use tiger_lib::ModFile;
 use tiger_lib::ModMetadata;
 use tiger_lib::{
     disable_ansi_colors, emit_reports, set_show_loaded_mods, set_show_vanilla, suppress_from_json,
-    validate_config_file, Everything,
+    validate_config_file, Everything, PreviewFormat,
 };
 
 use crate::gamedir::find_game_directory_steam;
@@ -24,7 +24,10 @@ struct Cli {
     command: Option<Commands>,
 
     #[clap(flatten)]
-    validate_args: Option<ValidateArgs>,
+    mod_args: Option<ModArgs>,
+
+    #[clap(flatten)]
+    validate_args: ValidateArgs,
 }
 
 #[derive(Subcommand)]
@@ -35,10 +38,63 @@ enum Commands {
         /// release version (e.g. 0.9.3)
         version: Option<String>,
     },
+    /// Show what a localization key looks like, with macros expanded and code shown as
+    /// placeholders.
+    Preview {
+        /// The localization key to show.
+        key: String,
+        /// The language to show it in.
+        #[clap(long, default_value = "english")]
+        lang: String,
+        /// Output HTML instead of plain text.
+        #[clap(long)]
+        html: bool,
+        #[clap(flatten)]
+        mod_args: ModArgs,
+    },
+    /// Show roughly what the tooltips of an event's options, a decision or a character interaction
+    /// look like, with their requirements and effects. Headings such as "If:" are always in
+    /// English.
+    Tooltip {
+        /// The event, decision or interaction to show.
+        key: String,
+        /// Only show the event option with this number (counting from 1) or name.
+        #[clap(long)]
+        option: Option<String>,
+        /// The language to show it in.
+        #[clap(long, default_value = "english")]
+        lang: String,
+        #[clap(flatten)]
+        mod_args: ModArgs,
+    },
+    /// Show the scope types that were inferred for `this`, `root`, `prev` and the named scopes
+    /// and lists at a location in the script.
+    ExplainScopes {
+        /// The location, as a file path relative to the mod or game directory, a colon, and a
+        /// line number. For example `events/my_events.txt:42`.
+        location: String,
+        #[clap(flatten)]
+        mod_args: ModArgs,
+    },
+    /// Show a gui window or other top-level widget with all its types, templates and
+    /// blockoverrides expanded.
+    GuiTree {
+        /// The `name` of the widget to show.
+        name: String,
+        #[clap(flatten)]
+        mod_args: ModArgs,
+    },
+    /// List the parameters of the mod's scripted effects and triggers, the arguments passed at
+    /// each call site, and the problems found in each expansion.
+    MacroReport {
+        #[clap(flatten)]
+        mod_args: ModArgs,
+    },
 }
 
+// The arguments that say which mod to load.
 #[derive(Args)]
-struct ValidateArgs {
+struct ModArgs {
     #[cfg(feature = "vic3")]
     /// Path to folder of mod to check.
     modpath: PathBuf,
@@ -54,6 +110,10 @@ struct ValidateArgs {
     /// Path to custom .conf file.
     #[clap(long)]
     config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ValidateArgs {
     /// Show errors in the base game script code as well
     #[clap(long)]
     show_vanilla: bool,
@@ -66,10 +126,41 @@ struct ValidateArgs {
     /// Warn about items that are defined but unused
     #[clap(long)]
     unused: bool,
+    /// Warn about script variables and flags that are read but never set, set but never read, or
+    /// used both as numbers and as scopes
+    #[clap(long)]
+    check_variables: bool,
+    /// Warn about named scopes that events and on-actions use but that some of their callers
+    /// don't set
+    #[clap(long)]
+    check_event_chains: bool,
+    /// Check that the fonts for each language have all the characters used in the localization,
+    /// and that the mod's static gui texts fit in their widgets.
+    #[clap(long)]
+    check_fonts: bool,
     /// Do checks specific to the Princes of Darkness mod
     #[cfg(feature = "ck3")]
     #[clap(long)]
     pod: bool,
+    /// Do extra checks on the de jure title hierarchy, such as counties that are not contiguous
+    /// and neighboring titles with similar colors.
+    #[cfg(feature = "ck3")]
+    #[clap(long)]
+    title_audit: bool,
+    /// Replay the history files up to each bookmark date and check the result, such as dead title
+    /// holders and loops in the liege hierarchy.
+    #[cfg(feature = "ck3")]
+    #[clap(long)]
+    check_bookmarks: bool,
+    /// Write the de jure title hierarchy to this file, as JSON or as CSV if it ends in .csv.
+    #[cfg(feature = "ck3")]
+    #[clap(long)]
+    export_titles: Option<PathBuf>,
+    /// Write the family trees from the character history to this file, as GEDCOM if it ends in
+    /// .ged or as Graphviz DOT if it ends in .dot.
+    #[cfg(feature = "ck3")]
+    #[clap(long)]
+    export_family_tree: Option<PathBuf>,
     /// Omit color from the output. False by default.
     /// Can also be configured in the config file.
     #[clap(long)]
@@ -77,14 +168,96 @@ struct ValidateArgs {
     /// Load a JSON file of reports to remove from the output.
     #[clap(long)]
     suppress: Option<PathBuf>,
+    /// Write images highlighting the problems in rivers.png and provinces.png to this directory.
+    #[clap(long)]
+    debug_images: Option<PathBuf>,
+}
+
+impl ModArgs {
+    /// Find the game directory if it was not given, and check that it is really the game
+    /// directory.
+    fn locate_game(&mut self, game_consts: &GameConsts) -> Result<()> {
+        let &GameConsts { name_short, app_id, signature_file, .. } = game_consts;
+        if self.game.is_none() {
+            self.game = find_game_directory_steam(app_id).ok();
+        }
+        if let Some(ref mut game) = self.game {
+            eprintln!("Using {name_short} directory: {}", game.display());
+            let mut sig = game.clone();
+            sig.push(signature_file);
+            if !sig.is_file() {
+                eprintln!("That does not look like a {name_short} directory.");
+                game.push("..");
+                eprintln!("Trying: {}", game.display());
+                sig.clone_from(game);
+                sig.push(signature_file);
+                if sig.is_file() {
+                    eprintln!("Ok.");
+                } else {
+                    bail!("Cannot find {name_short} directory. Please supply it as the --game option.");
+                }
+            }
+        } else {
+            bail!("Cannot find {name_short} directory. Please supply it as the --game option.");
+        }
+        Ok(())
+    }
+
+    /// Locate the game, check the config file and load the mod, for the subcommands that only
+    /// look things up in the loaded data. The [`Everything`] is leaked, because properly dropping
+    /// it takes a noticeable amount of time and the program exits right after.
+    fn load(mut self, game_consts: &GameConsts) -> Result<&'static mut Everything> {
+        self.locate_game(game_consts)?;
+        self.config = validate_config_file(self.config.take());
+        let mut everything = self.everything()?;
+        everything.load_all();
+        Ok(Box::leak(Box::new(everything)))
+    }
+
+    /// Read the mod's metadata and create an [`Everything`] for it. Nothing is loaded yet.
+    fn everything(&mut self) -> Result<Everything> {
+        #[cfg(any(feature = "ck3", feature = "imperator"))]
+        {
+            if self.modpath.is_dir() {
+                self.modpath.push("descriptor.mod");
+            }
+
+            let modfile = ModFile::read(&self.modpath)?;
+            let modpath = modfile.modpath();
+            if !modpath.exists() {
+                eprintln!("Looking for mod in {}", modpath.display());
+                bail!("Cannot find mod directory. Please make sure the .mod file is correct.");
+            }
+            eprintln!("Using mod directory: {}", modpath.display());
+
+            Everything::new(
+                self.config.as_deref(),
+                self.game.as_deref(),
+                &modpath,
+                modfile.replace_paths(),
+            )
+        }
+        #[cfg(feature = "vic3")]
+        {
+            let metadata = ModMetadata::read(&self.modpath)?;
+            eprintln!("Using mod directory: {}", metadata.modpath().display());
+
+            Everything::new(
+                self.config.as_deref(),
+                self.game.as_deref(),
+                &self.modpath,
+                metadata.replace_paths(),
+            )
+        }
+    }
 }
 
 /// Run the main tiger application.
 ///
 /// It provides a number of command line arguments, as well as self-updating capability with the `update` subcommand.
-#[allow(clippy::missing_panics_doc)] // it thinks we can panic on cli.validate_args.unwrap()
+#[allow(clippy::missing_panics_doc)] // it thinks we can panic on cli.mod_args.unwrap()
 pub fn run(game_consts: &GameConsts, current_version: &str) -> Result<()> {
-    let &GameConsts { name, name_short, version, app_id, signature_file, .. } = game_consts;
+    let &GameConsts { name, version, .. } = game_consts;
     let cli = Cli::parse();
 
     #[allow(clippy::single_match_else)]
@@ -93,8 +266,57 @@ pub fn run(game_consts: &GameConsts, current_version: &str) -> Result<()> {
             update(current_version, target_version.as_deref())?;
             Ok(())
         }
+        Some(Commands::Preview { key, lang, html, mod_args }) => {
+            let everything = mod_args.load(game_consts)?;
+            let format = if html { PreviewFormat::Html } else { PreviewFormat::Text };
+            match everything.preview_localization(&key, &lang, format) {
+                Some(preview) => println!("{preview}"),
+                None => bail!("Localization key {key} does not exist in {lang}."),
+            }
+            Ok(())
+        }
+        Some(Commands::Tooltip { key, option, lang, mod_args }) => {
+            let everything = mod_args.load(game_consts)?;
+            match everything.simulate_tooltip(&key, option.as_deref(), &lang) {
+                Some(tooltip) => print!("{tooltip}"),
+                None => bail!("No event option, decision or interaction {key} was found."),
+            }
+            Ok(())
+        }
+        Some(Commands::ExplainScopes { location, mod_args }) => {
+            let Some((path, line)) =
+                location.rsplit_once(':').and_then(|(path, line)| Some((path, line.parse().ok()?)))
+            else {
+                bail!("Expected a location like events/my_events.txt:42, got {location}.");
+            };
+            let everything = mod_args.load(game_consts)?;
+            match everything.explain_scopes(PathBuf::from(path), line) {
+                Some(explanation) => println!("{explanation}"),
+                None => {
+                    bail!("No effects, triggers or script values were validated at {location}.")
+                }
+            }
+            Ok(())
+        }
+        Some(Commands::GuiTree { name, mod_args }) => {
+            let everything = mod_args.load(game_consts)?;
+            match everything.dump_gui_widget(&name) {
+                Some(dump) => print!("{dump}"),
+                None => bail!("No gui widget named {name} was found."),
+            }
+            Ok(())
+        }
+        Some(Commands::MacroReport { mod_args }) => {
+            let everything = mod_args.load(game_consts)?;
+            match everything.macro_report() {
+                Some(report) => print!("{report}"),
+                None => bail!("The mod has no scripted effects or triggers with parameters."),
+            }
+            Ok(())
+        }
         None => {
-            let mut args = cli.validate_args.unwrap();
+            let mut mod_args = cli.mod_args.unwrap();
+            let args = cli.validate_args;
             #[cfg(windows)]
             if !args.no_color {
                 let _ = ansiterm::enable_ansi_support()
@@ -105,30 +327,8 @@ pub fn run(game_consts: &GameConsts, current_version: &str) -> Result<()> {
             eprintln!("If you are using a newer version of {name}, it may be inaccurate.");
             eprintln!("!! Currently it's inaccurate anyway because it's in beta state.");
 
-            if args.game.is_none() {
-                args.game = find_game_directory_steam(app_id).ok();
-            }
-            if let Some(ref mut game) = args.game {
-                eprintln!("Using {name_short} directory: {}", game.display());
-                let mut sig = game.clone();
-                sig.push(signature_file);
-                if !sig.is_file() {
-                    eprintln!("That does not look like a {name_short} directory.");
-                    game.push("..");
-                    eprintln!("Trying: {}", game.display());
-                    sig.clone_from(game);
-                    sig.push(signature_file);
-                    if sig.is_file() {
-                        eprintln!("Ok.");
-                    } else {
-                        bail!("Cannot find {name_short} directory. Please supply it as the --game option.");
-                    }
-                }
-            } else {
-                bail!("Cannot find {name_short} directory. Please supply it as the --game option.");
-            }
-
-            args.config = validate_config_file(args.config);
+            mod_args.locate_game(game_consts)?;
+            mod_args.config = validate_config_file(mod_args.config.take());
 
             if let Some(suppress) = args.suppress {
                 eprintln!("Suppressing reports from: {}", suppress.display());
@@ -145,7 +345,7 @@ pub fn run(game_consts: &GameConsts, current_version: &str) -> Result<()> {
 
             if args.unused {
                 eprintln!(
-                    "Showing warnings for unused localization. There will be many false positives."
+                    "Showing warnings for unused localization, files and gui definitions. There will be many false positives."
                 );
             }
 
@@ -159,41 +359,7 @@ pub fn run(game_consts: &GameConsts, current_version: &str) -> Result<()> {
                 disable_ansi_colors();
             }
 
-            let mut everything;
-
-            #[cfg(any(feature = "ck3", feature = "imperator"))]
-            {
-                if args.modpath.is_dir() {
-                    args.modpath.push("descriptor.mod");
-                }
-
-                let modfile = ModFile::read(&args.modpath)?;
-                let modpath = modfile.modpath();
-                if !modpath.exists() {
-                    eprintln!("Looking for mod in {}", modpath.display());
-                    bail!("Cannot find mod directory. Please make sure the .mod file is correct.");
-                }
-                eprintln!("Using mod directory: {}", modpath.display());
-
-                everything = Everything::new(
-                    args.config.as_deref(),
-                    args.game.as_deref(),
-                    &modpath,
-                    modfile.replace_paths(),
-                )?;
-            }
-            #[cfg(feature = "vic3")]
-            {
-                let metadata = ModMetadata::read(&args.modpath)?;
-                eprintln!("Using mod directory: {}", metadata.modpath().display());
-
-                everything = Everything::new(
-                    args.config.as_deref(),
-                    args.game.as_deref(),
-                    &args.modpath,
-                    metadata.replace_paths(),
-                )?;
-            }
+            let mut everything = mod_args.everything()?;
 
             // Print a blank line between the preamble and the first report:
             eprintln!();
@@ -220,12 +386,51 @@ pub fn run(game_consts: &GameConsts, current_version: &str) -> Result<()> {
 
             everything.load_all();
             everything.validate_all();
-            everything.check_rivers();
+            if let Some(dir) = &args.debug_images {
+                for e in everything.check_rivers_with_debug_images(dir) {
+                    eprintln!("Debug images: {e:#}");
+                }
+            } else {
+                everything.check_rivers();
+            }
 
             #[cfg(feature = "ck3")]
             if args.pod {
                 everything.check_pod();
             }
+            #[cfg(feature = "ck3")]
+            if args.title_audit {
+                if let Err(e) = everything.check_titles_audit() {
+                    eprintln!("Could not do the title audit: {e:#}");
+                }
+            }
+            #[cfg(feature = "ck3")]
+            if args.check_bookmarks {
+                everything.check_bookmark_history();
+            }
+            if args.check_variables {
+                everything.check_variables();
+            }
+            if args.check_event_chains {
+                everything.check_event_chains();
+            }
+            if args.check_fonts {
+                everything.check_fonts();
+            }
+            #[cfg(feature = "ck3")]
+            if let Some(path) = &args.export_titles {
+                eprintln!("Writing title hierarchy to {}", path.display());
+                if let Err(e) = everything.export_titles(path) {
+                    eprintln!("Could not write title hierarchy: {e:#}");
+                }
+            }
+            #[cfg(feature = "ck3")]
+            if let Some(path) = &args.export_family_tree {
+                eprintln!("Writing family trees to {}", path.display());
+                if let Err(e) = everything.export_family_trees(path) {
+                    eprintln!("Could not write family trees: {e:#}");
+                }
+            }
             emit_reports(args.json);
             if args.unused {
                 everything.check_unused();